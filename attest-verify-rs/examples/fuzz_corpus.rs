@@ -0,0 +1,85 @@
+use hsmattest::builder::{AttestationBuilder, KeySection};
+use hsmattest::tlv_mapping::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/* Writes the seed corpus for the fuzz targets in fuzz/, from synthetic bundles:
+ *   cargo run --example fuzz_corpus
+ *   cd fuzz && cargo +nightly fuzz run run_buf
+ * Signatures are filler, the parser doesn't check them.
+ * */
+
+fn sections() -> Vec<KeySection> {
+    let label = b"corpus-key\0".to_vec();
+    let public = KeySection::new(0x10)
+        .with(TLVMapping::OBJ_ATTR_CLASS, TLVValue::ClassKey(ClassKey::Pubkey))
+        .with(TLVMapping::OBJ_ATTR_KEY_TYPE, TLVValue::KeyType(KeyType::Rsa))
+        .with_raw(TLVMapping::OBJ_ATTR_LABEL.to_int(), label.clone())
+        .with(TLVMapping::OBJ_ATTR_MODULUS, TLVValue::Bytes(Bytes("c3".repeat(256))))
+        .with_raw(TLVMapping::OBJ_ATTR_PUBLIC_EXPONENT.to_int(), vec![1, 0, 1])
+        .with(TLVMapping::OBJ_ATTR_ID, TLVValue::HexStr128(HexStr128("5e".repeat(64))))
+        .with(TLVMapping::OBJ_ATTR_KCV, TLVValue::Bytes(Bytes("0a0b0c".into())));
+    let private = KeySection::new(0x11)
+        .with(TLVMapping::OBJ_ATTR_CLASS, TLVValue::ClassKey(ClassKey::Privkey))
+        .with(TLVMapping::OBJ_ATTR_EXTRACTABLE, TLVValue::Bool(Bool(false)))
+        .with(TLVMapping::OBJ_ATTR_NEVER_EXTRACTABLE, TLVValue::Bool(Bool(true)))
+        .with_raw(0x4242, vec![0xde, 0xad]);
+    let secret = KeySection::new(0x20)
+        .with(TLVMapping::OBJ_ATTR_CLASS, TLVValue::ClassKey(ClassKey::Secret))
+        .with(TLVMapping::OBJ_ATTR_KEY_TYPE, TLVValue::KeyType(KeyType::Aes))
+        .with_raw(TLVMapping::OBJ_ATTR_LABEL.to_int(), label)
+        .with(TLVMapping::OBJ_ATTR_VALUE_LEN, TLVValue::Int(Int(32)))
+        .with_raw(TLVMapping::OBJ_ATTR_TRUSTED.to_int(), vec![]);
+    let ec = KeySection::new(0x30)
+        .with(TLVMapping::OBJ_ATTR_CLASS, TLVValue::ClassKey(ClassKey::Pubkey))
+        .with(TLVMapping::OBJ_ATTR_KEY_TYPE, TLVValue::KeyType(KeyType::Ec))
+        .with(TLVMapping::OBJ_ATTR_EC_PARAMS, TLVValue::Curve(Curve::P384))
+        .with(TLVMapping::OBJ_ATTR_EC_POINT, TLVValue::EcPoint(EcPoint::new(vec![0x3c; 48], vec![0xc3; 48])));
+    vec![public, private, secret, ec]
+}
+
+fn bundles() -> Vec<(String, Vec<u8>)> {
+    let [public, private, secret, ec]: [KeySection; 4] = sections().try_into().unwrap();
+    let mut bundles = Vec::new();
+    for signature_len in [256, 384, 512] {
+        let base = || {
+            AttestationBuilder::new()
+                .with_request_id(0x11)
+                .with_response_payload(vec![0; 8])
+                .with_attribute_format(0x0002_0001)
+                .with_signature(vec![0xa5; signature_len])
+        };
+        let asymmetric = base().with_section(public.clone()).with_section(private.clone());
+        bundles.push((format!("rsa-{}", signature_len), asymmetric.build().unwrap()));
+        let symmetric = base().with_section(secret.clone());
+        bundles.push((format!("aes-{}", signature_len), symmetric.build().unwrap()));
+        let ec = base().with_section(ec.clone()).with_section(private.clone());
+        bundles.push((format!("ec-{}", signature_len), ec.build().unwrap()));
+    }
+    bundles
+}
+
+fn write(dir: &Path, name: &str, bytes: &[u8]) {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(dir.join(name), bytes).unwrap();
+}
+
+fn main() {
+    let corpus = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus");
+
+    for (name, bundle) in bundles() {
+        write(&corpus.join("run_buf"), &name, &bundle);
+        write(&corpus.join("writer"), &name, &bundle);
+    }
+
+    // encode_tlv takes one attribute as it appears on the wire
+    for section in sections() {
+        for attribute in section.attributes {
+            let mut tlv = attribute.type_id.to_be_bytes().to_vec();
+            tlv.extend_from_slice(&(attribute.raw.len() as u32).to_be_bytes());
+            tlv.extend_from_slice(&attribute.raw);
+            write(&corpus.join("encode_tlv"), &format!("{:08x}-{:08x}", section.object_handle, attribute.type_id), &tlv);
+        }
+    }
+    println!("Seed corpus written to {}", corpus.display());
+}
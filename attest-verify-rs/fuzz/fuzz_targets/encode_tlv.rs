@@ -0,0 +1,36 @@
+#![no_main]
+
+use hsmattest::tlv_mapping::*;
+use libfuzzer_sys::fuzz_target;
+
+// a decoded value has to survive being written back out and decoded again
+macro_rules! round_trip {
+    ($($typ:ident),*) => {
+        fn round_trip(bytes: &[u8], len: u32) {
+            $(
+                if let Some(value) = $typ::encode(bytes, len) {
+                    let _ = value.to_str();
+                    let again = $typ::encode(&value.to_bytes(), len);
+                    assert_eq!(again, Some(value), "{}", stringify!($typ));
+                }
+            )*
+        }
+    };
+}
+
+round_trip!(ClassKey, KeyType, Bool, HexStr128, Bytes, ByteStr, RawBytes, Int, Curve, EcPoint);
+
+// type (4) and declared length (4) followed by the value, as in the attribute buffer
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 8 {
+        return;
+    }
+    let type_id = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    let len = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    let bytes = &data[8..];
+
+    let value = TLVMapping::from_int(type_id).encode(bytes, len);
+    let _ = value.to_str();
+    let _ = value.to_bytes();
+    round_trip(bytes, len);
+});
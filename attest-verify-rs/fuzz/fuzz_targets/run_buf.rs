@@ -0,0 +1,23 @@
+#![no_main]
+
+use hsmattest::attestation::Attestation;
+use hsmattest::Machine;
+use libfuzzer_sys::fuzz_target;
+
+// errors are compared on their message, `ParseError` isn't comparable
+fn whole(data: &[u8]) -> Result<Attestation, String> {
+    let mut machine = Machine::new();
+    machine.run_buf(data);
+    machine.finish().map_err(|e| e.to_string())
+}
+
+fn bytewise(data: &[u8]) -> Result<Attestation, String> {
+    let mut machine = Machine::new();
+    data.iter().for_each(|byte| machine.parse(*byte));
+    machine.finish().map_err(|e| e.to_string())
+}
+
+// no input may panic, and however it's fed in the machine has to come to the same result
+fuzz_target!(|data: &[u8]| {
+    assert_eq!(whole(data), bytewise(data));
+});
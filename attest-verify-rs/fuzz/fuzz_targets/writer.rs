@@ -0,0 +1,19 @@
+#![no_main]
+
+use hsmattest::writer::Writer;
+use hsmattest::Machine;
+use libfuzzer_sys::fuzz_target;
+
+// whatever the attributes hold, the writer has to produce valid JSON
+fuzz_target!(|data: &[u8]| {
+    let mut machine = Machine::new();
+    machine.run_buf(data);
+    let Ok(attestation) = machine.finish() else {
+        return;
+    };
+
+    let writer = Writer::new(&attestation).with_pair("Fuzz", "\u{0}\"\n");
+    let json = writer.to_json_bytes();
+    serde_json::from_slice::<serde_json::Value>(&json).expect("writer produced invalid JSON");
+    let _ = writer.to_text();
+});
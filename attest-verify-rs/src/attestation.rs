@@ -0,0 +1,199 @@
+use crate::pkcs11::NameStyle;
+use crate::tlv_mapping::{ClassKey, TLVMapping, TLVValue};
+use crate::{KeyMode, Mode};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/* The typed result of a parse, as returned from `Machine::finish`.
+ *
+ * Values keep both their decoded form and the raw bytes they were decoded from, so callers never
+ * need to re-parse the strings produced by `EncodeTLV::to_str`.
+ * */
+
+/* Everything in the bundle outside the key attributes and the signature. Offsets are from the
+ * start of the bundle unless noted otherwise, all integers are big-endian.
+ *
+ *   0   response_code       u32   status of the attestation request, 0 on success
+ *   4   request_id          u32   identifies the request the HSM answered
+ *   8   total_size          u32   length of the whole bundle, signature included
+ *   12  buff_size           u32   length of the attribute buffer
+ *   16  response_payload          the HSM's response to the request (firmware and partition
+ *                                 details, certificates), up to attr_offset
+ *
+ * and at the start of the attribute buffer:
+ *   +0  attribute_format    u32   version/format of the attribute buffer that follows
+ *   +4  firstkey_offset     u16   first key section, relative to the attribute buffer
+ *   +6  secondkey_offset    u16   second key section, 0 when there is only one (symmetric keys)
+ * */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttestationHeader {
+    pub response_code: u32,
+    pub request_id: u32,
+    pub total_size: u32,
+    pub buff_size: u32,
+    pub response_payload: Vec<u8>,
+    pub attr_offset: u32,
+    pub attribute_format: u32,
+    pub firstkey_offset: u32,
+    pub secondkey_offset: u32,
+    // either supplied by the caller or detected, see `probe`
+    pub signature_len: u32,
+}
+
+impl AttestationHeader {
+    pub fn is_success(&self) -> bool {
+        self.response_code == 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    // the TLV type as it appeared on the wire, kept for attributes we don't have a mapping for
+    pub type_id: u32,
+    pub mapping: TLVMapping,
+    pub value: TLVValue,
+    pub raw: Vec<u8>,
+}
+
+impl Attribute {
+    /// Type, name, length and value on one line, the name in `style`; `Display` uses the Cavium names.
+    pub fn to_line(&self, style: NameStyle) -> String {
+        format!(
+            "Type = {:04x}  {} Len = {},  {:?}",
+            self.type_id,
+            self.mapping.label(style),
+            self.raw.len(),
+            self.value.to_str()
+        )
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_line(NameStyle::Cavium))
+    }
+}
+
+// each key section opens with the HSM object handle, the attribute count and the attribute size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyAttributes {
+    pub mode: KeyMode,
+    pub object_handle: u32,
+    pub attr_count: u32,
+    pub attr_size: u32,
+    pub attributes: Vec<Attribute>,
+}
+
+impl KeyAttributes {
+    pub fn new(mode: KeyMode) -> Self {
+        Self { mode, object_handle: 0, attr_count: 0, attr_size: 0, attributes: Vec::new() }
+    }
+
+    pub fn get(&self, mapping: TLVMapping) -> Option<&Attribute> {
+        self.attributes.iter().find(|attr| attr.mapping == mapping)
+    }
+
+    pub fn value(&self, mapping: TLVMapping) -> Option<&TLVValue> {
+        self.get(mapping).map(|attr| &attr.value)
+    }
+
+    // public keys are meant to be read, only private and secret key attributes can weaken anything
+    pub fn is_weakened_by(&self, attr: &Attribute) -> bool {
+        self.value(TLVMapping::OBJ_ATTR_CLASS) != Some(&TLVValue::ClassKey(ClassKey::Pubkey))
+            && attr.mapping.weakens(&attr.value)
+    }
+
+    /// The attributes whose values weaken the key, see `TLVMapping::weakens`.
+    pub fn weaknesses(&self) -> impl Iterator<Item = &Attribute> {
+        self.attributes.iter().filter(|attr| self.is_weakened_by(attr))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attestation {
+    pub header: AttestationHeader,
+    pub sections: Vec<KeyAttributes>,
+    pub signature: Vec<u8>,
+}
+
+impl Attestation {
+    pub fn section(&self, mode: KeyMode) -> Option<&KeyAttributes> {
+        self.sections.iter().find(|section| section.mode == mode)
+    }
+
+    /// Lists the attributes whose values differ between the two attestations, section by section
+    /// and in wire order. Attributes are matched on their TLV type.
+    pub fn diff(&self, other: &Attestation) -> Vec<AttributeDiff> {
+        let modes = union(self.sections.iter().chain(&other.sections).map(|s| s.mode));
+
+        let mut diffs = Vec::new();
+        for mode in modes {
+            let left = self.section(mode).map(|s| &s.attributes[..]).unwrap_or_default();
+            let right = other.section(mode).map(|s| &s.attributes[..]).unwrap_or_default();
+            let find = |attrs: &[Attribute], type_id| attrs.iter().find(|a| a.type_id == type_id).cloned();
+
+            for type_id in union(left.iter().chain(right).map(|a| a.type_id)) {
+                let (left, right) = (find(left, type_id), find(right, type_id));
+                if left.as_ref().map(|a| &a.raw) != right.as_ref().map(|a| &a.raw) {
+                    diffs.push(AttributeDiff { mode, left, right });
+                }
+            }
+        }
+        diffs
+    }
+
+    // a zero secondary offset means there is no private half, i.e. a symmetric key
+    pub fn mode(&self) -> Mode {
+        match self.header.secondkey_offset {
+            0 => Mode::Symmetric,
+            _ => Mode::Asymetric,
+        }
+    }
+}
+
+// distinct items, in order of first appearance
+fn union<T: PartialEq, I: Iterator<Item = T>>(items: I) -> Vec<T> {
+    let mut distinct = Vec::new();
+    for item in items {
+        if !distinct.contains(&item) {
+            distinct.push(item);
+        }
+    }
+    distinct
+}
+
+/// An attribute that is missing from one side, or present on both with different values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeDiff {
+    pub mode: KeyMode,
+    pub left: Option<Attribute>,
+    pub right: Option<Attribute>,
+}
+
+impl AttributeDiff {
+    pub fn mapping(&self) -> TLVMapping {
+        self.left.as_ref().or(self.right.as_ref()).map(|a| a.mapping).unwrap_or(TLVMapping::UNKNOWN)
+    }
+
+    /// `~` for a changed value, `-` for a removed and `+` for an added attribute, then the section,
+    /// the name in `style` and the values.
+    pub fn to_line(&self, style: NameStyle) -> String {
+        let name = self.mapping().label(style);
+        match (&self.left, &self.right) {
+            (Some(left), Some(right)) => {
+                format!("~ {:?} {}: {:?} -> {:?}", self.mode, name, left.value.to_str(), right.value.to_str())
+            }
+            (Some(left), None) => format!("- {:?} {}: {:?}", self.mode, name, left.value.to_str()),
+            (None, Some(right)) => format!("+ {:?} {}: {:?}", self.mode, name, right.value.to_str()),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+impl fmt::Display for AttributeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_line(NameStyle::Cavium))
+    }
+}
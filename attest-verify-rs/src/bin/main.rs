@@ -1,31 +1,241 @@
-use hsmattest::error::{self, ParseError};
-use hsmattest::state_transitions::register_functions;
+#[macro_use]
+mod output;
+
+use hsmattest::attestation::{Attestation, Attribute};
+use hsmattest::chain::{parse_certificates, subject_of, Certificate, ChainVerifier};
+use hsmattest::error::ParseError;
+use hsmattest::gcp::{match_key_id, KeyVersionName};
+use hsmattest::gzip::Decoder;
+use hsmattest::jwks::{match_attestation, Jwks, JwksMatch};
+use hsmattest::pkcs11::NameStyle;
+use hsmattest::policy::Policy;
+use hsmattest::pubkey::{self, from_hex, to_hex, KeyFormat, PublicKey};
+use hsmattest::tlv_mapping::TLVMapping;
+use hsmattest::verify::{verify_bundle, AttestationKey};
+use hsmattest::writer::Writer;
 use hsmattest::Machine;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use output::{write_stdout, Format, PrintObserver, Report};
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const BUF_SIZE: u32 = 1 << 16;
+
+// exit codes, the check failures (2 to 4) in rising order of severity
+const EXIT_OK: i32 = 0;
+const EXIT_ERROR: i32 = 1;
+const EXIT_POLICY_FAILURE: i32 = 2;
+const EXIT_KEY_MISMATCH: i32 = 3;
+const EXIT_SIGNATURE_INVALID: i32 = 4;
+const EXIT_DIFFERENT: i32 = 5;
+
+const USAGE: &str = "\
+Usage: hsmattest_bin <command> [options] <input>
+
+Commands:
+  parse <bundle> [owner-key]  print the attributes of a bundle, running any checks requested
+  verify <bundle>             check the bundle signature, with --key or --certs and trust anchors
+  check <bundle>              evaluate --policy, --public-key/--modulus or --gcp-key-name
+  kcv [public-key]            compute the KCV and EKCV of a public key (or --modulus/--exponent)
+  diff <bundle> <bundle>      list the attributes that differ between two bundles
+  inspect <bundle>            show the bundle layout: sizes, offsets and sections
+  export-pubkey <bundle>      write the attested public key, checking the signature first with --key/--certs
+  jwks <jwks.json> <bundle|dir>
+                              find the JWK of the attested key; for a directory, also list the keys
+                              no bundle in it attests
+  help                        show this message
+
+Without a command, `parse` is assumed. Use `-` as a path to read from stdin.
+
+Options:
+  --format text|json|yaml     output format (default text)
+  --names cavium|pkcs11       label attributes ObjAttrExtractable (default) or CKA_EXTRACTABLE
+  --key <pem|der>             owner public key the bundle is signed with
+  --certs <pem>               certificate chain(s) for the bundle signing key
+  --manufacturer-root <pem>   manufacturer trust anchors for --certs
+  --owner-root <pem>          owner trust anchors for --certs
+  --policy <toml|json>        attribute policy to evaluate
+  --public-key <pem|der>      public key to compare KCV, EKCV, modulus and exponent against
+  --modulus <hex>             bare RSA modulus, instead of --public-key
+  --exponent <int|0xhex>      RSA public exponent for --modulus (default 65537)
+  --gcp-key-name <name>       Cloud KMS key version whose KeyID should match ObjAttrId
+  --key-format pem|der|jwk|openssh
+                              encoding for export-pubkey (default pem)
+
+Exit codes:
+  0  success
+  1  usage, I/O or parse error (including malformed bundles and broken certificate chains)
+  2  a policy assertion failed
+  3  the public key or KeyID did not match the attestation
+  4  the bundle signature is invalid
+  5  diff found differences
+When several checks fail, all of them are reported and the exit code is that of the most severe:
+an invalid signature (4), then a key or KeyID mismatch (3), then a policy failure (2).
+";
+
 fn main() {
-    match run_parse() {
-        Ok(_) => println!("Done"),
-        Err(e) => println!("Error while parsing = '{}'", e),
+    let code = match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error while parsing = '{}'", e);
+            EXIT_ERROR
+        }
     };
+    std::process::exit(code);
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+enum Command {
+    #[default]
+    Parse,
+    Verify,
+    Check,
+    Kcv,
+    Diff,
+    Inspect,
+    ExportPubkey,
+    Jwks,
+    Help,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "parse" => Some(Command::Parse),
+            "verify" => Some(Command::Verify),
+            "check" => Some(Command::Check),
+            "kcv" => Some(Command::Kcv),
+            "diff" => Some(Command::Diff),
+            "inspect" => Some(Command::Inspect),
+            "export-pubkey" => Some(Command::ExportPubkey),
+            "jwks" => Some(Command::Jwks),
+            "help" | "-h" | "--help" => Some(Command::Help),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Args {
+    command: Command,
+    inputs: Vec<String>,
+    key: Option<String>,
+    certs: Option<String>,
+    manufacturer_roots: Option<String>,
+    owner_roots: Option<String>,
+    policy: Option<String>,
+    public_key: Option<String>,
+    modulus: Option<String>,
+    exponent: Option<String>,
+    gcp_key_name: Option<String>,
+    format: Format,
+    key_format: KeyFormat,
+    names: NameStyle,
+}
+
+impl Args {
+    fn input(&self, idx: usize) -> Result<&str, ParseError> {
+        self.inputs.get(idx).map(String::as_str).ok_or(ParseError::InvalidArg(idx + 1))
+    }
+}
+
+fn parse_args() -> Result<Args, ParseError> {
+    let mut args = Args::default();
+    let mut iter = std::env::args().skip(1).enumerate().peekable();
+
+    // older scripts call us with just a bundle, so the command is optional
+    if let Some(command) = iter.peek().and_then(|(_, arg)| Command::from_name(arg)) {
+        args.command = command;
+        iter.next();
+    }
+
+    while let Some((pos, arg)) = iter.next() {
+        let mut value = || iter.next().map(|(_, v)| v).ok_or(ParseError::InvalidArg(pos + 1));
+        match arg.as_str() {
+            "--key" => args.key = Some(value()?),
+            "--certs" => args.certs = Some(value()?),
+            "--manufacturer-root" => args.manufacturer_roots = Some(value()?),
+            "--owner-root" => args.owner_roots = Some(value()?),
+            "--policy" => args.policy = Some(value()?),
+            "--public-key" => args.public_key = Some(value()?),
+            "--modulus" => args.modulus = Some(value()?),
+            "--exponent" => args.exponent = Some(value()?),
+            "--gcp-key-name" => args.gcp_key_name = Some(value()?),
+            "--format" => args.format = Format::from_name(&value()?).ok_or(ParseError::InvalidArg(pos + 1))?,
+            "--names" => args.names = NameStyle::from_name(&value()?).ok_or(ParseError::InvalidArg(pos + 1))?,
+            "--key-format" => {
+                args.key_format = KeyFormat::from_name(&value()?).ok_or(ParseError::InvalidArg(pos + 1))?
+            }
+            "-h" | "--help" => args.command = Command::Help,
+            flag if flag.starts_with("--") => return Err(ParseError::InvalidArg(pos)),
+            _ => args.inputs.push(arg),
+        }
+    }
+
+    // `parse bundle.dat owner.pem` predates --key
+    if args.key.is_none() && matches!(args.command, Command::Parse | Command::Verify) && args.inputs.len() > 1 {
+        args.key = args.inputs.pop();
+    }
+    // either one names the key the bundle is signed with, neither wins over the other
+    if args.key.is_some() && args.certs.is_some() {
+        return Err(ParseError::ConflictingArgs("give the owner key or --certs, not both".into()));
+    }
+    Ok(args)
+}
+
+fn run() -> Result<i32, ParseError> {
+    let args = parse_args()?;
+    match args.command {
+        Command::Help => {
+            out!("{}", USAGE);
+            Ok(EXIT_OK)
+        }
+        Command::Parse | Command::Verify | Command::Check => run_bundle(&args),
+        Command::Kcv => run_kcv(&args),
+        Command::Diff => run_diff(&args),
+        Command::Inspect => run_inspect(&args),
+        Command::ExportPubkey => run_export_pubkey(&args),
+        Command::Jwks => run_jwks(&args),
+    }
 }
 
-fn run_parse() -> Result<(), error::ParseError> {
-    let mut machine = Machine::new().with_writer();
-    register_functions(&mut machine);
+// `-` reads from stdin
+fn open_input(path: &str) -> Result<Box<dyn Read>, ParseError> {
+    match path {
+        "-" => Ok(Box::new(io::stdin())),
+        path => Ok(Box::new(File::open(Path::new(path))?)),
+    }
+}
+
+fn read_input(path: &str) -> Result<Vec<u8>, ParseError> {
+    let mut contents = Vec::new();
+    open_input(path)?.read_to_end(&mut contents)?;
+    Ok(contents)
+}
 
-    let fname = std::env::args().skip(1).next().ok_or(ParseError::InvalidArg(0))?;
-    println!("Reading file = {}", fname);
-    let file = File::open(Path::new(&fname))?;
+struct Bundle {
+    attestation: Attestation,
+    // the inflated bundle bytes, as covered by the signature
+    bytes: Vec<u8>,
+    compressed: bool,
+}
 
-    let mut reader = BufReader::with_capacity(BUF_SIZE as _, file);
+fn read_bundle(path: &str, machine: &mut Machine) -> Result<Bundle, ParseError> {
+    let mut bytes = Vec::new();
+    let mut sink = |raw: &[u8]| {
+        machine.run_buf(raw);
+        bytes.extend_from_slice(raw);
+    };
+
+    // compressed bundles are inflated on the fly, anything else passes straight through
+    let mut decoder = Decoder::new();
+    let mut reader = BufReader::with_capacity(BUF_SIZE as _, open_input(path)?);
     loop {
         let length = {
             let buffer = reader.fill_buf()?;
-            machine.run_buf(buffer);
+            decoder.feed(buffer, &mut sink)?;
             buffer.len()
         };
 
@@ -33,6 +243,437 @@ fn run_parse() -> Result<(), error::ParseError> {
             break;
         }
         reader.consume(length);
+    }
+    decoder.finish(&mut sink)?;
+
+    let compressed = decoder.is_compressed();
+    Ok(Bundle { attestation: machine.finish()?, bytes, compressed })
+}
+
+fn read_certs(fname: Option<&String>) -> Result<Vec<Certificate>, ParseError> {
+    match fname {
+        Some(fname) => parse_certificates(&fs::read(Path::new(fname))?),
+        None => Ok(Vec::new()),
+    }
+}
+
+// the bundle key either comes straight from the command line, or from the leaf of the verified chains
+fn resolve_key(args: &Args, report: &mut Report) -> Result<Option<AttestationKey>, ParseError> {
+    if let Some(certs) = &args.certs {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let chains = ChainVerifier::new()
+            .with_manufacturer_roots(read_certs(args.manufacturer_roots.as_ref())?)
+            .with_owner_roots(read_certs(args.owner_roots.as_ref())?)
+            .at_time(now)
+            .verify(&read_certs(Some(certs))?)?;
+
+        if let Some(chain) = &chains.manufacturer {
+            report.pair("Manufacturer chain".into(), format!("ok (root '{}')", subject_of(chain.root())));
+        }
+        if let Some(chain) = &chains.owner {
+            report.pair("Owner chain".into(), format!("ok (root '{}')", subject_of(chain.root())));
+        }
+        return Ok(Some(chains.leaf_key().clone()));
+    }
+
+    match &args.key {
+        Some(key_name) => Ok(Some(AttestationKey::from_bytes(&fs::read(Path::new(key_name))?)?)),
+        None => Ok(None),
+    }
+}
+
+fn read_policy(fname: &str) -> Result<Policy, ParseError> {
+    let contents = fs::read_to_string(Path::new(fname))?;
+    if fname.ends_with(".json") {
+        Policy::from_json(&contents)
+    } else {
+        Policy::from_toml(&contents)
+    }
+}
+
+// the expected public key comes either from a PEM/DER file or a bare modulus (hex) and exponent
+fn read_public_key(fname: Option<&str>, args: &Args) -> Result<Option<PublicKey>, ParseError> {
+    if let Some(fname) = fname {
+        return PublicKey::from_bytes(&read_input(fname)?).map(Some);
+    }
+
+    let Some(modulus) = &args.modulus else {
+        return Ok(None);
+    };
+    let modulus = from_hex(modulus).ok_or(ParseError::InvalidPublicKey("modulus is not valid hex".into()))?;
+    let exponent = match args.exponent.as_deref().unwrap_or("65537") {
+        hex if hex.starts_with("0x") => from_hex(hex),
+        dec => dec.parse::<u32>().ok().map(|e| e.to_be_bytes().to_vec()),
+    }
+    .ok_or(ParseError::InvalidPublicKey("exponent is not a valid integer".into()))?;
+    Ok(Some(PublicKey::from_rsa_components(&modulus, &exponent)))
+}
+
+// parse, verify and check: one bundle, plus whichever checks were asked for
+fn run_bundle(args: &Args) -> Result<i32, ParseError> {
+    let fname = args.input(0)?;
+    match args.command {
+        Command::Verify if args.key.is_none() && args.certs.is_none() => {
+            return Err(ParseError::MissingArg("verify needs --key or --certs".into()));
+        }
+        Command::Check
+            if args.policy.is_none()
+                && args.public_key.is_none()
+                && args.modulus.is_none()
+                && args.gcp_key_name.is_none() =>
+        {
+            return Err(ParseError::MissingArg(
+                "check needs --policy, --public-key, --modulus or --gcp-key-name".into(),
+            ));
+        }
+        _ => {}
+    }
+
+    let mut report = Report::new(args.format);
+    let key = resolve_key(args, &mut report)?;
+    let policy = args.policy.as_deref().map(read_policy).transpose()?;
+    let public_key = read_public_key(args.public_key.as_deref(), args)?;
+    let key_name = args.gcp_key_name.as_deref().map(KeyVersionName::parse).transpose()?;
+
+    // the signature trailer is exactly as long as the signing key's modulus
+    let mut machine = match &key {
+        Some(key) => Machine::new_with_signature_len(key.signature_len()),
+        None => Machine::new(),
     };
-    Ok(())
+    if args.format == Format::Text && args.command == Command::Parse {
+        machine = machine.with_observer(PrintObserver::new(args.names));
+    }
+
+    report.note(format_args!("Reading file = {}", fname));
+    let bundle = read_bundle(fname, &mut machine)?;
+    let attestation = &bundle.attestation;
+
+    let mut code = EXIT_OK;
+    if let Some(key) = key {
+        let verdict = verify_bundle(&bundle.bytes, &key)?;
+        report.pair("Signature verification".into(), verdict.to_string());
+        if !verdict.is_valid() {
+            code = EXIT_SIGNATURE_INVALID;
+        }
+    }
+    code = code.max(run_checks(attestation, public_key, key_name, policy, args.names, &mut report)?);
+
+    match args.format {
+        Format::Text if code == EXIT_OK => outln!("Done"),
+        Format::Text => {}
+        format => {
+            let writer = report
+                .pairs
+                .into_iter()
+                .fold(Writer::new(attestation).with_names(args.names), |writer, (name, value)| {
+                    writer.with_pair(name, value)
+                });
+            format.emit(&writer.to_json());
+        }
+    }
+    Ok(code)
+}
+
+fn run_checks(
+    attestation: &Attestation,
+    public_key: Option<PublicKey>,
+    key_name: Option<KeyVersionName>,
+    policy: Option<Policy>,
+    names: NameStyle,
+    report: &mut Report,
+) -> Result<i32, ParseError> {
+    // every check runs, the most severe failure picks the exit code
+    let mut code = EXIT_OK;
+    if let Some(public_key) = public_key {
+        let checks = pubkey::compare(attestation, &public_key)?;
+        report.heading("PUBLIC KEY");
+        for check in &checks {
+            let name = format!("{} ({:?})", check.mapping.label(names), check.mode);
+            report.record(name, check.outcome.to_string(), check.to_line(names));
+        }
+        if checks.is_empty() {
+            report.note("No key check values or public components to compare against");
+            code = code.max(EXIT_KEY_MISMATCH);
+        } else if !checks.iter().all(|c| c.is_match()) {
+            report.note(format_args!(
+                "Public key mismatch on {} attribute(s)",
+                checks.iter().filter(|c| !c.is_match()).count()
+            ));
+            code = code.max(EXIT_KEY_MISMATCH);
+        }
+    }
+
+    if let Some(key_name) = key_name {
+        let matches = match_key_id(attestation, &key_name);
+        report.heading("KEY ID");
+        report.pair(key_name.to_string(), key_name.key_id());
+        for key_id in &matches {
+            report.record(format!("KeyID ({:?})", key_id.mode), key_id.outcome(), key_id);
+        }
+        if !matches.iter().any(|m| m.is_match()) {
+            report.note("No ObjAttrId matches the derived KeyID");
+            code = code.max(EXIT_KEY_MISMATCH);
+        }
+    }
+
+    if let Some(policy) = policy {
+        let policy_report = policy.evaluate(attestation);
+        report.heading("POLICY");
+        for result in &policy_report.results {
+            let name = format!("Policy {:?} {} {}", result.mode, result.mapping.label(names), result.assertion);
+            let value = if result.passed { "pass" } else { "fail" };
+            report.record(name, value.to_string(), result.to_line(names));
+        }
+        if !policy_report.passed() {
+            report.note(format_args!("Policy failed on {} attribute(s)", policy_report.failures().count()));
+            code = code.max(EXIT_POLICY_FAILURE);
+        }
+    }
+    Ok(code)
+}
+
+fn run_kcv(args: &Args) -> Result<i32, ParseError> {
+    let fname = args.inputs.first().map(String::as_str).or(args.public_key.as_deref());
+    let key = read_public_key(fname, args)?
+        .ok_or(ParseError::MissingArg("kcv needs a public key or --modulus".into()))?;
+    let kcv = to_hex(&key.kcv()?);
+    let ekcv = to_hex(&key.ekcv()?);
+
+    match args.format {
+        Format::Text => {
+            outln!("KCV = {}", kcv);
+            outln!("EKCV = {}", ekcv);
+        }
+        format => format.emit(&json!({ "kcv": kcv, "ekcv": ekcv })),
+    }
+    Ok(EXIT_OK)
+}
+
+fn run_diff(args: &Args) -> Result<i32, ParseError> {
+    let left = read_bundle(args.input(0)?, &mut Machine::new())?;
+    let right = read_bundle(args.input(1)?, &mut Machine::new())?;
+    let diffs = left.attestation.diff(&right.attestation);
+
+    match args.format {
+        Format::Text => {
+            for diff in &diffs {
+                outln!("{}", diff.to_line(args.names));
+            }
+            if diffs.is_empty() {
+                outln!("No differences");
+            }
+        }
+        format => {
+            let side = |attr: &Option<Attribute>| match attr {
+                Some(attr) => Value::String(attr.value.to_str()),
+                None => Value::Null,
+            };
+            let differences = diffs
+                .iter()
+                .map(|d| {
+                    json!({
+                        "mode": format!("{:?}", d.mode),
+                        "name": d.mapping().label(args.names),
+                        "left": side(&d.left),
+                        "right": side(&d.right),
+                    })
+                })
+                .collect::<Vec<_>>();
+            format.emit(&json!({ "identical": diffs.is_empty(), "differences": differences }));
+        }
+    }
+    Ok(if diffs.is_empty() { EXIT_OK } else { EXIT_DIFFERENT })
+}
+
+fn run_inspect(args: &Args) -> Result<i32, ParseError> {
+    let fname = args.input(0)?;
+    let bundle = read_bundle(fname, &mut Machine::new())?;
+    let attestation = &bundle.attestation;
+    let header = &attestation.header;
+
+    match args.format {
+        Format::Text => {
+            outln!("File = {}{}", fname, if bundle.compressed { " (gzip compressed)" } else { "" });
+            outln!("Bundle = {} bytes", bundle.bytes.len());
+            outln!("Response code = {:#010x}", header.response_code);
+            outln!("Request id = {:#010x}", header.request_id);
+            outln!("Total size = {}", header.total_size);
+            outln!("Response payload = {} bytes", header.response_payload.len());
+            outln!("Attribute buffer = {} bytes at offset {}", header.buff_size, header.attr_offset);
+            outln!("Attribute format = {:#010x}", header.attribute_format);
+            outln!("First key offset = {}", header.firstkey_offset);
+            outln!("Second key offset = {}", header.secondkey_offset);
+            outln!("Mode = {:?}", attestation.mode());
+            for section in &attestation.sections {
+                outln!(
+                    "Section {:?} = handle {:#010x}, {} attributes in {} bytes",
+                    section.mode,
+                    section.object_handle,
+                    section.attributes.len(),
+                    section.attr_size
+                );
+            }
+            outln!("Signature = {} bytes", attestation.signature.len());
+        }
+        format => {
+            let sections = attestation
+                .sections
+                .iter()
+                .map(|s| {
+                    json!({
+                        "mode": format!("{:?}", s.mode),
+                        "object_handle": s.object_handle,
+                        "attr_count": s.attr_count,
+                        "attr_size": s.attr_size,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let header = Writer::new(attestation).to_json()["header"].take();
+            format.emit(&json!({
+                "file": fname,
+                "compressed": bundle.compressed,
+                "bundle_len": bundle.bytes.len(),
+                "header": header,
+                "mode": format!("{:?}", attestation.mode()),
+                "sections": sections,
+                "signature_len": attestation.signature.len(),
+            }));
+        }
+    }
+    Ok(EXIT_OK)
+}
+
+fn run_export_pubkey(args: &Args) -> Result<i32, ParseError> {
+    // the chain results aren't part of the output, only the key is
+    let key = resolve_key(args, &mut Report::new(Format::Json))?;
+    let mut machine = match &key {
+        Some(key) => Machine::new_with_signature_len(key.signature_len()),
+        None => Machine::new(),
+    };
+    let bundle = read_bundle(args.input(0)?, &mut machine)?;
+    if let Some(key) = key {
+        let verdict = verify_bundle(&bundle.bytes, &key)?;
+        if !verdict.is_valid() {
+            eprintln!("Signature verification = {}, not exporting the key", verdict);
+            return Ok(EXIT_SIGNATURE_INVALID);
+        }
+    }
+
+    let attestation = &bundle.attestation;
+    let public_key = PublicKey::from_attestation(attestation)?;
+    let exported = match args.key_format {
+        // the key label makes a useful comment in authorized_keys
+        KeyFormat::OpenSsh => {
+            let label = attestation.sections.iter().find_map(|s| s.value(TLVMapping::OBJ_ATTR_LABEL));
+            let line = public_key.to_openssh(label.map(|label| label.to_str()).as_deref())?;
+            format!("{}\n", line).into_bytes()
+        }
+        KeyFormat::Jwk => format!("{:#}\n", public_key.to_jwk()).into_bytes(),
+        format => public_key.export(format)?,
+    };
+    write_stdout(&exported);
+    Ok(EXIT_OK)
+}
+
+// one bundle against the set; with a verdict when the owner key is known
+fn match_bundle(path: &str, jwks: &Jwks, key: Option<&AttestationKey>) -> Result<JwksMatch, ParseError> {
+    let mut machine = match key {
+        Some(key) => Machine::new_with_signature_len(key.signature_len()),
+        None => Machine::new(),
+    };
+    let bundle = read_bundle(path, &mut machine)?;
+    match_attestation(jwks, &bundle.attestation, &bundle.bytes, key)
+}
+
+fn jwks_exit_code(found: &JwksMatch) -> i32 {
+    match found.verdict {
+        Some(verdict) if !verdict.is_valid() => EXIT_SIGNATURE_INVALID,
+        _ if !found.is_match() => EXIT_KEY_MISMATCH,
+        _ => EXIT_OK,
+    }
+}
+
+fn jwks_json(found: &JwksMatch) -> Value {
+    json!({
+        "matched": found.is_match(),
+        "index": found.index,
+        "kid": found.kid,
+        "signature": found.verdict.map(|verdict| verdict.to_string()),
+    })
+}
+
+fn run_jwks(args: &Args) -> Result<i32, ParseError> {
+    let jwks = Jwks::from_json(&String::from_utf8_lossy(&read_input(args.input(0)?)?))?;
+    let path = args.input(1)?;
+    let key = resolve_key(args, &mut Report::new(args.format))?;
+
+    if !Path::new(path).is_dir() {
+        let found = match_bundle(path, &jwks, key.as_ref())?;
+        match args.format {
+            Format::Text => outln!("{}", found),
+            format => format.emit(&jwks_json(&found)),
+        }
+        return Ok(jwks_exit_code(&found));
+    }
+
+    // every file in the directory is taken as a bundle; the ones that don't parse are reported and skipped
+    let mut paths = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| path.is_file());
+    paths.sort();
+
+    // the worst outcome sets the exit code: invalid signature, then a key mismatch, then unreadable bundles
+    let mut code = EXIT_OK;
+    let mut matches = Vec::new();
+    let mut bundles = Vec::new();
+    for path in &paths {
+        let name = path.display().to_string();
+        match match_bundle(&name, &jwks, key.as_ref()) {
+            Ok(found) => {
+                code = code.max(jwks_exit_code(&found));
+                bundles.push(json!({ "file": name, "result": jwks_json(&found) }));
+                if args.format == Format::Text {
+                    outln!("{} = {}", name, found);
+                }
+                matches.push(found);
+            }
+            Err(e) => {
+                code = code.max(EXIT_ERROR);
+                bundles.push(json!({ "file": name, "error": e.to_string() }));
+                if args.format == Format::Text {
+                    outln!("{} = error, {}", name, e);
+                }
+            }
+        }
+    }
+
+    let unmatched = jwks.unmatched(&matches);
+    if !unmatched.is_empty() {
+        code = code.max(EXIT_KEY_MISMATCH);
+    }
+    match args.format {
+        Format::Text => {
+            for jwk in &unmatched {
+                match &jwk.key {
+                    Ok(_) => outln!("No attestation for {}", jwk),
+                    Err(e) => outln!("No attestation for {} ({})", jwk, e),
+                }
+            }
+            if unmatched.is_empty() {
+                outln!("Every key in the set is attested");
+            }
+        }
+        format => {
+            let unmatched = unmatched
+                .iter()
+                .map(|jwk| json!({ "index": jwk.index, "kid": jwk.kid }))
+                .collect::<Vec<_>>();
+            format.emit(&json!({ "bundles": bundles, "unmatched": unmatched }));
+        }
+    }
+    Ok(code)
 }
@@ -0,0 +1,134 @@
+use hsmattest::attestation::Attribute;
+use hsmattest::observer::AttestationObserver;
+use hsmattest::pkcs11::NameStyle;
+use hsmattest::pubkey::to_hex;
+use hsmattest::tlv_mapping::{ClassKey, TLVMapping, TLVValue};
+use hsmattest::writer::{explanation, to_yaml};
+use hsmattest::KeyMode;
+use serde_json::Value;
+use std::fmt::Display;
+use std::io::{self, ErrorKind, Write};
+
+/* Everything the CLI prints goes through `out!`/`outln!`. A reader that goes away before the output
+ * ends (`| head`) ends the run quietly, where `println!` would panic on the broken pipe.
+ * */
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::output::write_stdout(format!($($arg)*).as_bytes())
+    };
+}
+
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        $crate::output::write_stdout(format!("{}\n", format_args!($($arg)*)).as_bytes())
+    };
+}
+
+pub fn write_stdout(bytes: &[u8]) {
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(bytes).and_then(|_| stdout.flush()) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => std::process::exit(crate::EXIT_OK),
+        Err(e) => {
+            eprintln!("Error while writing = '{}'", e);
+            std::process::exit(crate::EXIT_ERROR);
+        }
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "yaml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+
+    // prints a structured document, text output is left to the caller
+    pub fn emit(&self, document: &Value) {
+        match self {
+            Format::Text => {}
+            Format::Json => outln!("{}", document),
+            Format::Yaml => out!("{}", to_yaml(document)),
+        }
+    }
+}
+
+// streams the attributes to stdout as they're parsed, in the same layout as `Writer::to_text`
+pub struct PrintObserver {
+    names: NameStyle,
+    // the class comes first in a section, it decides whether anything can weaken the key
+    public: bool,
+}
+
+impl PrintObserver {
+    pub fn new(names: NameStyle) -> Self {
+        Self { names, public: false }
+    }
+}
+
+impl AttestationObserver for PrintObserver {
+    fn on_key_section_start(&mut self, mode: KeyMode) {
+        self.public = false;
+        outln!("------------KEYMODE: {:?} -------------", mode);
+    }
+
+    fn on_attribute(&mut self, _mode: KeyMode, attribute: &Attribute) {
+        if attribute.mapping == TLVMapping::OBJ_ATTR_CLASS {
+            self.public = attribute.value == TLVValue::ClassKey(ClassKey::Pubkey);
+        }
+        let weakens = !self.public && attribute.mapping.weakens(&attribute.value);
+        outln!("{}", attribute.to_line(self.names));
+        outln!("{}", explanation(attribute, weakens));
+    }
+
+    fn on_signature(&mut self, signature: &[u8]) {
+        outln!("Attestation Signature \n{}", to_hex(signature));
+    }
+}
+
+// check results are printed as they come for text, and folded into the document otherwise
+pub struct Report {
+    pub format: Format,
+    pub pairs: Vec<(String, String)>,
+}
+
+impl Report {
+    pub fn new(format: Format) -> Self {
+        Self { format, pairs: Vec::new() }
+    }
+
+    pub fn heading(&self, title: &str) {
+        if self.format == Format::Text {
+            outln!("------------{} -------------", title);
+        }
+    }
+
+    pub fn note<D: Display>(&self, line: D) {
+        if self.format == Format::Text {
+            outln!("{}", line);
+        }
+    }
+
+    pub fn record<D: Display>(&mut self, name: String, value: String, line: D) {
+        match self.format {
+            Format::Text => outln!("{}", line),
+            _ => self.pairs.push((name, value)),
+        }
+    }
+
+    pub fn pair(&mut self, name: String, value: String) {
+        let line = format!("{} = {}", name, value);
+        self.record(name, value, line);
+    }
+}
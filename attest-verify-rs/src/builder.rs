@@ -0,0 +1,263 @@
+use crate::attestation::{Attestation, AttestationHeader, Attribute};
+use crate::error::ParseError;
+use crate::tlv_mapping::{TLVMapping, TLVValue};
+use crate::verify::AttestationKey;
+use crate::{ATTR_HEADER_LEN, SECTION_HEADER_LEN};
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::{Pkcs1v15Sign, RsaPrivateKey};
+use sha2::{Digest, Sha256};
+
+/* Synthetic bundles, laid out the way the HSM does it (see `AttestationHeader`), for fixtures and
+ * negative testing.
+ *
+ * Sizes and offsets are worked out from the sections unless they are overridden, and the bundle is
+ * either signed with an owner key (RSA PKCS#1 v1.5 over SHA-256, as checked by `verify_bundle`) or
+ * closed with a signature the caller supplies as is.
+ *
+ *   let bundle = AttestationBuilder::new()
+ *       .with_section(KeySection::new(0x10).with(TLVMapping::OBJ_ATTR_CLASS, TLVValue::ClassKey(ClassKey::Pubkey)))
+ *       .with_section(KeySection::new(0x11).with(TLVMapping::OBJ_ATTR_CLASS, TLVValue::ClassKey(ClassKey::Privkey)))
+ *       .with_signing_key(key)
+ *       .build()?;
+ * */
+
+/// The RSA private key a bundle is signed with, the counterpart of `AttestationKey`.
+#[derive(Debug, Clone)]
+pub struct SigningKey(RsaPrivateKey);
+
+impl SigningKey {
+    /// Accepts either a `PRIVATE KEY` (PKCS#8) or `RSA PRIVATE KEY` (PKCS#1) PEM.
+    pub fn from_pem(pem: &str) -> Result<Self, ParseError> {
+        let pem = pem.trim();
+        RsaPrivateKey::from_pkcs8_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+            .map(Self)
+            .map_err(|e| ParseError::InvalidPrivateKey(e.to_string()))
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, ParseError> {
+        RsaPrivateKey::from_pkcs8_der(der)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_der(der))
+            .map(Self)
+            .map_err(|e| ParseError::InvalidPrivateKey(e.to_string()))
+    }
+
+    /// Sniffs for a PEM armour and falls back to DER otherwise.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseError> {
+        match core::str::from_utf8(bytes) {
+            Ok(pem) if pem.contains("-----BEGIN") => Self::from_pem(pem),
+            _ => Self::from_der(bytes),
+        }
+    }
+
+    /// The key to verify the bundles signed with this one.
+    pub fn public_key(&self) -> AttestationKey {
+        AttestationKey::from(self.0.to_public_key())
+    }
+
+    pub fn signature_len(&self) -> usize {
+        self.public_key().signature_len()
+    }
+
+    pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>, ParseError> {
+        self.0
+            .sign(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(data))
+            .map_err(|e| ParseError::InvalidPrivateKey(e.to_string()))
+    }
+}
+
+impl From<RsaPrivateKey> for SigningKey {
+    fn from(value: RsaPrivateKey) -> Self {
+        Self(value)
+    }
+}
+
+/// One key section: the object handle followed by its attributes, in wire order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeySection {
+    pub object_handle: u32,
+    pub attributes: Vec<Attribute>,
+}
+
+impl KeySection {
+    pub fn new(object_handle: u32) -> Self {
+        Self { object_handle, attributes: Vec::new() }
+    }
+
+    /// Adds a typed attribute, written out with `TLVValue::to_bytes`.
+    pub fn with(self, mapping: TLVMapping, value: TLVValue) -> Self {
+        let type_id = mapping.to_int();
+        let raw = value.to_bytes();
+        self.with_attribute(Attribute { type_id, mapping, value, raw })
+    }
+
+    /// Adds an attribute from its raw value, for types we have no mapping for or values that
+    /// don't decode (e.g. a NUL terminated label).
+    pub fn with_raw(self, type_id: u32, raw: Vec<u8>) -> Self {
+        let mapping = TLVMapping::from_int(type_id);
+        let value = mapping.encode(&raw, raw.len() as u32);
+        self.with_attribute(Attribute { type_id, mapping, value, raw })
+    }
+
+    pub fn with_attribute(mut self, attribute: Attribute) -> Self {
+        self.attributes.push(attribute);
+        self
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut tlvs = Vec::new();
+        for attribute in &self.attributes {
+            tlvs.extend_from_slice(&attribute.type_id.to_be_bytes());
+            tlvs.extend_from_slice(&(attribute.raw.len() as u32).to_be_bytes());
+            tlvs.extend_from_slice(&attribute.raw);
+        }
+
+        let mut bytes = Vec::with_capacity(SECTION_HEADER_LEN as usize + tlvs.len());
+        bytes.extend_from_slice(&self.object_handle.to_be_bytes());
+        bytes.extend_from_slice(&(self.attributes.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&(tlvs.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&tlvs);
+        bytes
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Signer {
+    Key(Box<SigningKey>),
+    Signature(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AttestationBuilder {
+    response_code: u32,
+    request_id: u32,
+    response_payload: Vec<u8>,
+    attribute_format: u32,
+    sections: Vec<KeySection>,
+    signer: Option<Signer>,
+    // overrides for malformed bundles
+    total_size: Option<u32>,
+    key_offsets: Option<(u16, u16)>,
+}
+
+impl AttestationBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from a parsed bundle, keeping its signature. Unpadded bundles rebuild byte for byte,
+    /// so a real bundle can be tweaked and re-signed.
+    pub fn from_attestation(attestation: &Attestation) -> Self {
+        let mut builder = Self::new()
+            .with_header(&attestation.header)
+            .with_signature(attestation.signature.clone());
+        for section in &attestation.sections {
+            builder = builder.with_section(KeySection {
+                object_handle: section.object_handle,
+                attributes: section.attributes.clone(),
+            });
+        }
+        builder
+    }
+
+    /// Takes the response code, request id, response payload and attribute format from `header`.
+    /// Sizes and offsets are always worked out from the sections.
+    pub fn with_header(mut self, header: &AttestationHeader) -> Self {
+        self.response_code = header.response_code;
+        self.request_id = header.request_id;
+        self.response_payload = header.response_payload.clone();
+        self.attribute_format = header.attribute_format;
+        self
+    }
+
+    pub fn with_response_code(mut self, response_code: u32) -> Self {
+        self.response_code = response_code;
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: u32) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    pub fn with_response_payload(mut self, payload: Vec<u8>) -> Self {
+        self.response_payload = payload;
+        self
+    }
+
+    pub fn with_attribute_format(mut self, attribute_format: u32) -> Self {
+        self.attribute_format = attribute_format;
+        self
+    }
+
+    /// The first call adds the public (or only) key section, the second the private one.
+    pub fn with_section(mut self, section: KeySection) -> Self {
+        self.sections.push(section);
+        self
+    }
+
+    pub fn with_signing_key<K: Into<SigningKey>>(mut self, key: K) -> Self {
+        self.signer = Some(Signer::Key(Box::new(key.into())));
+        self
+    }
+
+    /// Appends `signature` verbatim instead of signing, its length is the signature length.
+    pub fn with_signature(mut self, signature: Vec<u8>) -> Self {
+        self.signer = Some(Signer::Signature(signature));
+        self
+    }
+
+    /// Writes `total_size` into the header in place of the real bundle length.
+    pub fn with_total_size(mut self, total_size: u32) -> Self {
+        self.total_size = Some(total_size);
+        self
+    }
+
+    /// Writes these key offsets into the attribute buffer in place of the real ones.
+    pub fn with_key_offsets(mut self, firstkey_offset: u16, secondkey_offset: u16) -> Self {
+        self.key_offsets = Some((firstkey_offset, secondkey_offset));
+        self
+    }
+
+    pub fn build(&self) -> Result<Vec<u8>, ParseError> {
+        if !(1..=2).contains(&self.sections.len()) {
+            return Err(ParseError::SectionCount(self.sections.len()));
+        }
+        let signature_len = match &self.signer {
+            Some(Signer::Key(key)) => key.signature_len(),
+            Some(Signer::Signature(signature)) => signature.len(),
+            None => return Err(ParseError::MissingArg("a signing key or signature".to_string())),
+        };
+
+        let sections = self.sections.iter().map(KeySection::to_bytes).collect::<Vec<_>>();
+        let (firstkey_offset, secondkey_offset) = self.key_offsets.unwrap_or_else(|| match &sections[..] {
+            [_] => (ATTR_HEADER_LEN as u16, 0),
+            [first, ..] => (ATTR_HEADER_LEN as u16, (ATTR_HEADER_LEN as usize + first.len()) as u16),
+            [] => (0, 0),
+        });
+
+        let mut attributes = Vec::new();
+        attributes.extend_from_slice(&self.attribute_format.to_be_bytes());
+        attributes.extend_from_slice(&firstkey_offset.to_be_bytes());
+        attributes.extend_from_slice(&secondkey_offset.to_be_bytes());
+        sections.iter().for_each(|section| attributes.extend_from_slice(section));
+
+        let total_size = 16 + self.response_payload.len() + attributes.len() + signature_len;
+        let mut bundle = Vec::with_capacity(total_size);
+        bundle.extend_from_slice(&self.response_code.to_be_bytes());
+        bundle.extend_from_slice(&self.request_id.to_be_bytes());
+        bundle.extend_from_slice(&self.total_size.unwrap_or(total_size as u32).to_be_bytes());
+        bundle.extend_from_slice(&(attributes.len() as u32).to_be_bytes());
+        bundle.extend_from_slice(&self.response_payload);
+        bundle.extend_from_slice(&attributes);
+
+        let signature = match &self.signer {
+            Some(Signer::Key(key)) => key.sign(&bundle)?,
+            Some(Signer::Signature(signature)) => signature.clone(),
+            None => Vec::new(),
+        };
+        bundle.extend_from_slice(&signature);
+        Ok(bundle)
+    }
+}
@@ -0,0 +1,304 @@
+use crate::error::ParseError;
+use crate::verify::AttestationKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use x509_cert::der::{Decode, Encode};
+use x509_cert::ext::pkix::{BasicConstraints, KeyUsage};
+use x509_cert::spki::ObjectIdentifier;
+
+pub use x509_cert::Certificate;
+
+/* Certificate chain building for the manufacturer (Cavium/Marvell) and owner (cloud provider) roots.
+ *
+ * Both chains terminate in a certificate for the same HSM key: the manufacturer vouches that the
+ * key lives inside genuine hardware and the owner vouches that the hardware sits in their fleet.
+ * The shared leaf key is what signs the attestation bundle.
+ *
+ * Bundles such as the `certificate_chain.pem` emitted by
+ * `gcloud kms keys versions get-certificate-chain` contain both chains (and sometimes the roots)
+ * in no particular order, so paths are built by issuer/subject lookup rather than position.
+ * Every certificate above the leaf, the trust anchor included, has to be a CA (basicConstraints
+ * cA with room in its pathLenConstraint) that may sign certificates (keyUsage keyCertSign).
+ * */
+
+const SHA256_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+const SHA384_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.12");
+const SHA512_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.13");
+
+/// Parses a PEM bundle of one or more certificates, or a single DER certificate.
+pub fn parse_certificates(bytes: &[u8]) -> Result<Vec<Certificate>, ParseError> {
+    let is_pem = core::str::from_utf8(bytes)
+        .map(|s| s.contains("-----BEGIN"))
+        .unwrap_or(false);
+
+    let certs = if is_pem {
+        // load_pem_chain is strict about anything around the armour, so only hand it the blocks
+        let pem = core::str::from_utf8(bytes).unwrap_or_default();
+        let mut certs = Vec::new();
+        for block in pem_blocks(pem) {
+            certs.extend(Certificate::load_pem_chain(block.as_bytes()).map_err(invalid)?);
+        }
+        certs
+    } else {
+        vec![Certificate::from_der(bytes).map_err(invalid)?]
+    };
+
+    if certs.is_empty() {
+        return Err(ParseError::InvalidCertificate("no certificates found".into()));
+    }
+    Ok(certs)
+}
+
+fn pem_blocks(pem: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Option<String> = None;
+    for line in pem.lines().map(str::trim) {
+        if line.starts_with("-----BEGIN CERTIFICATE") {
+            current = Some(String::new());
+        }
+        if let Some(block) = current.as_mut() {
+            block.push_str(line);
+            block.push('\n');
+        }
+        if line.starts_with("-----END CERTIFICATE") {
+            blocks.extend(current.take());
+        }
+    }
+    blocks
+}
+
+fn invalid(e: x509_cert::der::Error) -> ParseError {
+    ParseError::InvalidCertificate(e.to_string())
+}
+
+pub fn subject_of(cert: &Certificate) -> String {
+    cert.tbs_certificate.subject.to_string()
+}
+
+impl AttestationKey {
+    pub fn from_certificate(cert: &Certificate) -> Result<Self, ParseError> {
+        let spki = cert.tbs_certificate.subject_public_key_info.to_der().map_err(invalid)?;
+        RsaPublicKey::from_public_key_der(&spki)
+            .map(AttestationKey::from)
+            .map_err(|e| ParseError::InvalidPublicKey(e.to_string()))
+    }
+}
+
+/// A verified path, leaf first and trust anchor last.
+#[derive(Debug, Clone)]
+pub struct CertChain(Vec<Certificate>);
+
+impl CertChain {
+    pub fn leaf(&self) -> &Certificate {
+        &self.0[0]
+    }
+
+    pub fn root(&self) -> &Certificate {
+        &self.0[self.0.len() - 1]
+    }
+
+    pub fn certificates(&self) -> &[Certificate] {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VerifiedChains {
+    pub manufacturer: Option<CertChain>,
+    pub owner: Option<CertChain>,
+    leaf_key: AttestationKey,
+}
+
+impl VerifiedChains {
+    /// The key both chains vouch for, i.e. the key the attestation bundle is signed with.
+    pub fn leaf_key(&self) -> &AttestationKey {
+        &self.leaf_key
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChainVerifier {
+    manufacturer_roots: Vec<Certificate>,
+    owner_roots: Vec<Certificate>,
+    time: Option<u64>,
+}
+
+impl ChainVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_manufacturer_roots(mut self, roots: Vec<Certificate>) -> Self {
+        self.manufacturer_roots = roots;
+        self
+    }
+
+    pub fn with_owner_roots(mut self, roots: Vec<Certificate>) -> Self {
+        self.owner_roots = roots;
+        self
+    }
+
+    /// Validity periods are only checked once a validation time (unix seconds) is supplied, as
+    /// there is no clock to fall back on in the browser.
+    pub fn at_time(mut self, unix_secs: u64) -> Self {
+        self.time = Some(unix_secs);
+        self
+    }
+
+    /// Builds the manufacturer and owner chains out of `pool` and returns the shared leaf key.
+    pub fn verify(&self, pool: &[Certificate]) -> Result<VerifiedChains, ParseError> {
+        if self.manufacturer_roots.is_empty() && self.owner_roots.is_empty() {
+            return Err(ParseError::MissingTrustAnchors);
+        }
+
+        let manufacturer = self.build_all(pool, &self.manufacturer_roots)?;
+        let owner = self.build_all(pool, &self.owner_roots)?;
+
+        // pick the first leaf that both sides agree on, or the first leaf of whichever side was asked for
+        let (manufacturer, owner) = match (manufacturer, owner) {
+            (Some(mfr), Some(own)) => {
+                let pair = mfr.iter().find_map(|m| {
+                    own.iter()
+                        .find(|o| same_key(m.leaf(), o.leaf()))
+                        .map(|o| (m.clone(), o.clone()))
+                });
+                let (m, o) = pair.ok_or(ParseError::LeafKeyMismatch)?;
+                (Some(m), Some(o))
+            }
+            (Some(mfr), None) => (mfr.into_iter().next(), None),
+            (None, Some(own)) => (None, own.into_iter().next()),
+            (None, None) => unreachable!("at least one set of trust anchors is configured"),
+        };
+
+        let leaf = manufacturer.as_ref().or(owner.as_ref()).map(CertChain::leaf);
+        let leaf_key = AttestationKey::from_certificate(leaf.ok_or(ParseError::MissingTrustAnchors)?)?;
+        Ok(VerifiedChains { manufacturer, owner, leaf_key })
+    }
+
+    // every leaf in the pool that chains up to one of `roots`, or the first failure if none do.
+    fn build_all(&self, pool: &[Certificate], roots: &[Certificate]) -> Result<Option<Vec<CertChain>>, ParseError> {
+        if roots.is_empty() {
+            return Ok(None);
+        }
+
+        let mut chains = Vec::new();
+        let mut first_err = None;
+        for leaf in pool.iter().filter(|c| is_leaf(c, pool) && !roots.contains(c)) {
+            match self.build(leaf, pool, roots) {
+                Ok(chain) => chains.push(chain),
+                Err(e) => {
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+
+        match (chains.is_empty(), first_err) {
+            (false, _) => Ok(Some(chains)),
+            (true, Some(e)) => Err(e),
+            (true, None) => Err(ParseError::BrokenChain("no leaf certificate in bundle".into())),
+        }
+    }
+
+    fn build(&self, leaf: &Certificate, pool: &[Certificate], roots: &[Certificate]) -> Result<CertChain, ParseError> {
+        let mut path = vec![leaf.clone()];
+        loop {
+            let current = &path[path.len() - 1];
+            self.check_validity(current)?;
+
+            if let Some(root) = roots.iter().find(|r| issued_by(current, r)) {
+                self.check_validity(root)?;
+                check_issuer(root, path.len() - 1)?;
+                path.push(root.clone());
+                return Ok(CertChain(path));
+            }
+
+            let issuer = pool
+                .iter()
+                .filter(|c| !path.contains(c))
+                .find(|c| issued_by(current, c));
+
+            match issuer {
+                Some(issuer) => {
+                    check_issuer(issuer, path.len() - 1)?;
+                    path.push(issuer.clone());
+                }
+                None if is_self_issued(current) => return Err(ParseError::UntrustedRoot(subject_of(current))),
+                None => return Err(ParseError::BrokenChain(subject_of(current))),
+            }
+        }
+    }
+
+    fn check_validity(&self, cert: &Certificate) -> Result<(), ParseError> {
+        let Some(now) = self.time else {
+            return Ok(());
+        };
+        let validity = &cert.tbs_certificate.validity;
+        let not_before = validity.not_before.to_unix_duration().as_secs();
+        let not_after = validity.not_after.to_unix_duration().as_secs();
+
+        if now < not_before {
+            return Err(ParseError::CertificateNotYetValid { subject: subject_of(cert), not_before });
+        }
+        if now > not_after {
+            return Err(ParseError::CertificateExpired { subject: subject_of(cert), not_after });
+        }
+        Ok(())
+    }
+}
+
+fn same_key(a: &Certificate, b: &Certificate) -> bool {
+    a.tbs_certificate.subject_public_key_info == b.tbs_certificate.subject_public_key_info
+}
+
+fn is_self_issued(cert: &Certificate) -> bool {
+    cert.tbs_certificate.issuer == cert.tbs_certificate.subject
+}
+
+// a leaf doesn't issue anything else in the pool
+fn is_leaf(cert: &Certificate, pool: &[Certificate]) -> bool {
+    !pool.iter().any(|c| c != cert && issued_by(c, cert))
+}
+
+// `issuer` sits above `below` intermediate CAs on the path to the leaf
+fn check_issuer(issuer: &Certificate, below: usize) -> Result<(), ParseError> {
+    let refuse = |reason| ParseError::NotACertificateAuthority { subject: subject_of(issuer), reason };
+    let tbs = &issuer.tbs_certificate;
+
+    let constraints = tbs.get::<BasicConstraints>().map_err(invalid)?.map(|(_, ext)| ext);
+    match constraints {
+        Some(BasicConstraints { ca: true, path_len_constraint: Some(limit) }) if below > limit as usize => {
+            return Err(refuse(format!("pathLenConstraint {} with {} CAs below it", limit, below)));
+        }
+        Some(BasicConstraints { ca: true, .. }) => {}
+        _ => return Err(refuse("basicConstraints lacks cA".into())),
+    }
+
+    match tbs.get::<KeyUsage>().map_err(invalid)? {
+        Some((_, usage)) if usage.key_cert_sign() => Ok(()),
+        _ => Err(refuse("keyUsage lacks keyCertSign".into())),
+    }
+}
+
+fn issued_by(cert: &Certificate, issuer: &Certificate) -> bool {
+    cert.tbs_certificate.issuer == issuer.tbs_certificate.subject && check_signature(cert, issuer).is_ok()
+}
+
+/// Checks `cert`'s signature with the public key of `issuer`.
+pub fn check_signature(cert: &Certificate, issuer: &Certificate) -> Result<(), ParseError> {
+    let key = AttestationKey::from_certificate(issuer)?;
+    let tbs = cert.tbs_certificate.to_der().map_err(invalid)?;
+    let signature = cert.signature.raw_bytes();
+
+    let oid = cert.signature_algorithm.oid;
+    let (scheme, digest) = match oid {
+        SHA256_WITH_RSA => (Pkcs1v15Sign::new::<Sha256>(), Sha256::digest(&tbs).to_vec()),
+        SHA384_WITH_RSA => (Pkcs1v15Sign::new::<Sha384>(), Sha384::digest(&tbs).to_vec()),
+        SHA512_WITH_RSA => (Pkcs1v15Sign::new::<Sha512>(), Sha512::digest(&tbs).to_vec()),
+        _ => return Err(ParseError::UnsupportedAlgorithm(oid.to_string())),
+    };
+
+    key.public_key()
+        .verify(scheme, &digest, signature)
+        .map_err(|_| ParseError::BrokenChain(subject_of(cert)))
+}
@@ -1,11 +1,45 @@
+use crate::{KeyMode, State};
+use crate::probe::SIGNATURE_LENGTHS;
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::fmt;
+#[cfg(feature = "std")]
 use std::io;
 
 #[derive(Debug, Clone)]
 pub enum ParseError {
     FileNotFound(String),
     InvalidArg(usize),
+    MissingArg(String),
+    ConflictingArgs(String),
     IoError(String),
+    InvalidPublicKey(String),
+    InvalidPrivateKey(String),
+    BundleTooShort(usize),
+    InvalidCertificate(String),
+    UnsupportedAlgorithm(String),
+    CertificateExpired { subject: String, not_after: u64 },
+    CertificateNotYetValid { subject: String, not_before: u64 },
+    UntrustedRoot(String),
+    BrokenChain(String),
+    NotACertificateAuthority { subject: String, reason: String },
+    LeafKeyMismatch,
+    MissingTrustAnchors,
+    Decompression(String),
+    Truncated { state: State, offset: usize },
+    OffsetOutOfRange { offset: u64, limit: u64 },
+    AttributeCountMismatch { declared: u32, parsed: u32 },
+    SectionOutOfRange { mode: KeyMode, offset: u64, limit: u64 },
+    SectionOverlap { mode: KeyMode, offset: u64, previous_end: u64 },
+    TrailingData { offset: usize },
+    SectionCount(usize),
+    UnknownSignatureLength,
+    AmbiguousSignatureLength(Vec<usize>),
+    InvalidPolicy(String),
+    InvalidKeyName(String),
+    NoPublicKey,
+    UnsupportedKeyFormat(String),
+    InvalidJwks(String),
 }
 
 impl fmt::Display for ParseError {
@@ -16,15 +50,86 @@ impl fmt::Display for ParseError {
             InvalidArg(pos) => write!(
                 f,
                 "Invalid arg at position {}. Please provide a filename \
-            \nFor example. ./hsmattest attestation.dat",
+            \nFor example. ./hsmattest parse attestation.dat [owner-key.pem] \
+            \n    or ./hsmattest verify attestation.dat --certs certificate_chain.pem \
+            --manufacturer-root cavium.pem --owner-root global_1498867200.pem \
+            \n    or ./hsmattest check attestation.dat --public-key key.pem \
+            \nSee ./hsmattest help for all commands and options",
                 pos
             ),
+            MissingArg(e) => write!(f, "Missing argument, {}", e),
+            ConflictingArgs(e) => write!(f, "Conflicting arguments, {}", e),
             IoError(e) => write!(f, "IoError = {}", e),
+            InvalidPublicKey(e) => write!(f, "Invalid public key = {}", e),
+            InvalidPrivateKey(e) => write!(f, "Invalid private key = {}", e),
+            BundleTooShort(len) => write!(f, "Bundle of {} bytes is too short to carry a signature", len),
+            InvalidCertificate(e) => write!(f, "Invalid certificate = {}", e),
+            UnsupportedAlgorithm(oid) => write!(f, "Unsupported signature algorithm {}", oid),
+            CertificateExpired { subject, not_after } => {
+                write!(f, "Certificate '{}' expired at {} (unix time)", subject, not_after)
+            }
+            CertificateNotYetValid { subject, not_before } => {
+                write!(f, "Certificate '{}' is not valid before {} (unix time)", subject, not_before)
+            }
+            UntrustedRoot(subject) => write!(f, "Chain ends in untrusted root '{}'", subject),
+            BrokenChain(subject) => write!(f, "No valid issuer found for certificate '{}'", subject),
+            NotACertificateAuthority { subject, reason } => {
+                write!(f, "Certificate '{}' may not issue certificates: {}", subject, reason)
+            }
+            LeafKeyMismatch => write!(f, "Manufacturer and owner chains do not certify the same key"),
+            MissingTrustAnchors => write!(f, "No trust anchors were configured"),
+            Decompression(e) => write!(f, "Unable to inflate compressed bundle = {}", e),
+            Truncated { state, offset } => {
+                write!(f, "Bundle truncated in state {:?} after {} bytes", state, offset)
+            }
+            OffsetOutOfRange { offset, limit } => {
+                write!(f, "Offset {} is out of range, limit is {}", offset, limit)
+            }
+            AttributeCountMismatch { declared, parsed } => write!(
+                f,
+                "Key section declares {} attributes which doesn't match the attribute buffer ({} parsed)",
+                declared, parsed
+            ),
+            SectionOutOfRange { mode, offset, limit } => write!(
+                f,
+                "{:?} key section runs to offset {}, past the end of the attribute buffer at {}",
+                mode, offset, limit
+            ),
+            SectionOverlap { mode, offset, previous_end } => write!(
+                f,
+                "{:?} key section starts at offset {} before the preceding data ends at {}",
+                mode, offset, previous_end
+            ),
+            TrailingData { offset } => write!(f, "Unexpected data after the signature at offset {}", offset),
+            SectionCount(count) => write!(f, "A bundle carries one or two key sections, not {}", count),
+            UnknownSignatureLength => write!(
+                f,
+                "No signature length of {:?} bytes locates a valid attribute buffer",
+                SIGNATURE_LENGTHS
+            ),
+            AmbiguousSignatureLength(lens) => write!(
+                f,
+                "Signature lengths of {:?} bytes all locate a valid attribute buffer, \
+            pin the length with the owner key",
+                lens
+            ),
+            InvalidPolicy(e) => write!(f, "Invalid policy = {}", e),
+            InvalidKeyName(name) => write!(
+                f,
+                "Invalid key name '{}', expected \
+            projects/../locations/../keyRings/../cryptoKeys/../cryptoKeyVersions/..",
+                name
+            ),
+            NoPublicKey => write!(f, "The bundle carries no RSA modulus and exponent or EC curve and point"),
+            UnsupportedKeyFormat(e) => write!(f, "Unsupported key format = {}", e),
+            InvalidJwks(e) => write!(f, "Invalid JWKS = {}", e),
         }
     }
 }
+#[cfg(feature = "std")]
 impl std::error::Error for ParseError {}
 
+#[cfg(feature = "std")]
 impl From<io::Error> for ParseError {
     fn from(value: io::Error) -> Self {
         ParseError::IoError(value.to_string())
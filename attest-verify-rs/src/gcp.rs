@@ -0,0 +1,140 @@
+use crate::attestation::Attestation;
+use crate::error::ParseError;
+use crate::pubkey::to_hex;
+use crate::tlv_mapping::{TLVMapping, TLVValue};
+use crate::KeyMode;
+use core::fmt;
+use sha2::{Digest, Sha256};
+
+/* Cloud KMS key identity.
+ *
+ * Keys generated by Cloud HSM carry a KeyID in `ObjAttrId`: two sha-256 hex digests back to back,
+ * the second of which is the digest of the full key version resource name, i.e.
+ *   projects/{project}/locations/{location}/keyRings/{key_ring}/cryptoKeys/{key}/cryptoKeyVersions/{version}
+ * Either half is accepted as a match so callers can tell which one lined up.
+ * */
+
+const COLLECTIONS: [&str; 5] = ["projects", "locations", "keyRings", "cryptoKeys", "cryptoKeyVersions"];
+const DIGEST_HEX_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyVersionName {
+    pub project: String,
+    pub location: String,
+    pub key_ring: String,
+    pub crypto_key: String,
+    pub version: String,
+}
+
+impl KeyVersionName {
+    pub fn new<S: Into<String>>(project: S, location: S, key_ring: S, crypto_key: S, version: S) -> Self {
+        Self {
+            project: project.into(),
+            location: location.into(),
+            key_ring: key_ring.into(),
+            crypto_key: crypto_key.into(),
+            version: version.into(),
+        }
+    }
+
+    /// Parses a full resource name, with or without a leading `//cloudkms.googleapis.com/`.
+    pub fn parse(name: &str) -> Result<Self, ParseError> {
+        let invalid = || ParseError::InvalidKeyName(name.to_string());
+        let trimmed = name.trim().trim_start_matches("//cloudkms.googleapis.com/").trim_matches('/');
+
+        let parts = trimmed.split('/').collect::<Vec<_>>();
+        if parts.len() != COLLECTIONS.len() * 2 {
+            return Err(invalid());
+        }
+        let mut ids = Vec::with_capacity(COLLECTIONS.len());
+        for (pair, collection) in parts.chunks(2).zip(COLLECTIONS) {
+            if pair[0] != collection || pair[1].is_empty() {
+                return Err(invalid());
+            }
+            ids.push(pair[1]);
+        }
+        Ok(Self::new(ids[0], ids[1], ids[2], ids[3], ids[4]))
+    }
+
+    /// The hex sha-256 of the resource name, as computed by `openssl dgst -sha256 -hex`.
+    pub fn key_id(&self) -> String {
+        to_hex(&Sha256::digest(self.to_string()))
+    }
+}
+
+impl fmt::Display for KeyVersionName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "projects/{}/locations/{}/keyRings/{}/cryptoKeys/{}/cryptoKeyVersions/{}",
+            self.project, self.location, self.key_ring, self.crypto_key, self.version
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IdHalf {
+    First,
+    Second,
+}
+
+/// Splits an `ObjAttrId` value into its two 64 character digests.
+pub fn split_object_id(id: &str) -> Option<(&str, &str)> {
+    if id.len() != DIGEST_HEX_LEN * 2 || !id.is_ascii() {
+        return None;
+    }
+    Some(id.split_at(DIGEST_HEX_LEN))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyIdMatch {
+    pub mode: KeyMode,
+    pub key_id: String,
+    pub object_id: String,
+    pub matched: Option<IdHalf>,
+}
+
+impl KeyIdMatch {
+    pub fn is_match(&self) -> bool {
+        self.matched.is_some()
+    }
+
+    pub fn outcome(&self) -> String {
+        match self.matched {
+            Some(IdHalf::First) => "match (first half)".to_string(),
+            Some(IdHalf::Second) => "match (second half)".to_string(),
+            None => format!("mismatch (expected {}, attested {})", self.key_id, self.object_id),
+        }
+    }
+}
+
+impl fmt::Display for KeyIdMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeyID ({:?}) = {}", self.mode, self.outcome())
+    }
+}
+
+/// Checks the derived KeyID against the `ObjAttrId` of every section that carries one.
+pub fn match_key_id(attestation: &Attestation, name: &KeyVersionName) -> Vec<KeyIdMatch> {
+    let key_id = name.key_id();
+    attestation
+        .sections
+        .iter()
+        .filter_map(|section| match section.value(TLVMapping::OBJ_ATTR_ID) {
+            Some(TLVValue::HexStr128(id)) => Some((section.mode, id.0.as_str())),
+            _ => None,
+        })
+        .map(|(mode, object_id)| {
+            let matched = split_object_id(object_id).and_then(|(first, second)| {
+                if first.eq_ignore_ascii_case(&key_id) {
+                    Some(IdHalf::First)
+                } else if second.eq_ignore_ascii_case(&key_id) {
+                    Some(IdHalf::Second)
+                } else {
+                    None
+                }
+            });
+            KeyIdMatch { mode, key_id: key_id.clone(), object_id: object_id.to_string(), matched }
+        })
+        .collect()
+}
@@ -0,0 +1,279 @@
+use crate::error::ParseError;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use miniz_oxide::inflate::stream::{inflate, InflateState};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+
+/* Transparent inflation of CAVIUM_V2_COMPRESSED (`*.compressed.dat`) attestation bundles.
+ *
+ * The cloud consoles hand out the bundle wrapped in a gzip member (RFC 1952). The first two bytes
+ * are sniffed for the gzip magic: uncompressed bundles are passed through untouched, compressed
+ * ones are inflated as the bytes arrive so the state machine never has to see a complete buffer.
+ * The header and trailer are walked byte-at-a-time just like the attestation itself, and the
+ * CRC32/ISIZE trailer is checked once the deflate stream ends.
+ * */
+
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+const OUT_BUF_SIZE: usize = 1 << 15;
+
+const FHCRC: u8 = 1 << 1;
+const FEXTRA: u8 = 1 << 2;
+const FNAME: u8 = 1 << 3;
+const FCOMMENT: u8 = 1 << 4;
+
+/// Sniffs the first bytes of a bundle and inflates it when it is gzip compressed.
+#[derive(Default)]
+pub struct Decoder {
+    state: DecoderState,
+}
+
+#[derive(Default)]
+enum DecoderState {
+    #[default]
+    Sniffing,
+    // holding back the first byte when it matches the magic but the second hasn't arrived yet
+    Partial(u8),
+    Plain,
+    Gzip(Box<GzipStream>),
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(self.state, DecoderState::Gzip(_))
+    }
+
+    /// Pushes `input` through the decoder, handing the raw bundle bytes to `sink` as they appear.
+    pub fn feed<F: FnMut(&[u8])>(&mut self, input: &[u8], mut sink: F) -> Result<(), ParseError> {
+        while !input.is_empty() {
+            match &mut self.state {
+                DecoderState::Sniffing => {
+                    if input.len() == 1 && input[0] == GZIP_MAGIC[0] {
+                        self.state = DecoderState::Partial(input[0]);
+                        return Ok(());
+                    }
+                    self.state = if input.starts_with(&GZIP_MAGIC) {
+                        DecoderState::Gzip(Box::default())
+                    } else {
+                        DecoderState::Plain
+                    };
+                }
+                DecoderState::Partial(first) => {
+                    let first = *first;
+                    if input[0] == GZIP_MAGIC[1] {
+                        let mut gzip = Box::<GzipStream>::default();
+                        gzip.feed(&[first], &mut sink)?;
+                        self.state = DecoderState::Gzip(gzip);
+                    } else {
+                        sink(&[first]);
+                        self.state = DecoderState::Plain;
+                    }
+                }
+                DecoderState::Plain => {
+                    sink(input);
+                    return Ok(());
+                }
+                DecoderState::Gzip(gzip) => return gzip.feed(input, &mut sink),
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes anything held back and checks a compressed stream was complete.
+    pub fn finish<F: FnMut(&[u8])>(&mut self, mut sink: F) -> Result<(), ParseError> {
+        match &self.state {
+            DecoderState::Partial(first) => {
+                sink(&[*first]);
+                self.state = DecoderState::Plain;
+                Ok(())
+            }
+            DecoderState::Gzip(gzip) if gzip.section != Section::Done => {
+                Err(ParseError::Decompression("gzip stream ended early".into()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Section {
+    Header,
+    Extra,
+    Name,
+    Comment,
+    HeaderCrc,
+    Deflate,
+    Trailer,
+    Done,
+}
+
+struct GzipStream {
+    section: Section,
+    flags: u8,
+    // bytes seen in the current header section
+    counter: usize,
+    extra_len: usize,
+    inflater: Box<InflateState>,
+    out: Vec<u8>,
+    crc: u32,
+    size: u32,
+    trailer: [u8; 8],
+}
+
+impl Default for GzipStream {
+    fn default() -> Self {
+        Self {
+            section: Section::Header,
+            flags: 0,
+            counter: 0,
+            extra_len: 0,
+            inflater: InflateState::new_boxed(DataFormat::Raw),
+            out: vec![0; OUT_BUF_SIZE],
+            crc: !0,
+            size: 0,
+            trailer: [0; 8],
+        }
+    }
+}
+
+impl GzipStream {
+    fn feed<F: FnMut(&[u8])>(&mut self, mut input: &[u8], sink: &mut F) -> Result<(), ParseError> {
+        while !input.is_empty() {
+            if self.section == Section::Deflate {
+                input = self.inflate(input, sink)?;
+                continue;
+            }
+            self.header_byte(input[0])?;
+            input = &input[1..];
+        }
+        Ok(())
+    }
+
+    fn header_byte(&mut self, c: u8) -> Result<(), ParseError> {
+        self.counter += 1;
+        match self.section {
+            Section::Header => {
+                match self.counter {
+                    1 | 2 if c != GZIP_MAGIC[self.counter - 1] => return Err(corrupt("bad magic")),
+                    3 if c != 8 => return Err(corrupt("unsupported compression method")),
+                    4 => self.flags = c,
+                    10 => self.next_header_section(Section::Header),
+                    _ => {}
+                }
+            }
+            Section::Extra => {
+                // two bytes of little-endian length, then that many bytes of payload
+                match self.counter {
+                    1 => self.extra_len = c as usize,
+                    2 => self.extra_len |= (c as usize) << 8,
+                    _ => {}
+                }
+                if self.counter >= 2 && self.counter == self.extra_len + 2 {
+                    self.next_header_section(Section::Extra);
+                }
+            }
+            Section::Name | Section::Comment if c == 0 => self.next_header_section(self.section),
+            Section::Name | Section::Comment => {}
+            Section::HeaderCrc if self.counter == 2 => self.next_header_section(Section::HeaderCrc),
+            Section::HeaderCrc => {}
+            Section::Trailer => {
+                self.trailer[self.counter - 1] = c;
+                if self.counter == self.trailer.len() {
+                    self.check_trailer()?;
+                    self.section = Section::Done;
+                }
+            }
+            Section::Done => return Err(corrupt("trailing data after gzip member")),
+            Section::Deflate => unreachable!("deflate data is handled by the inflater"),
+        }
+        Ok(())
+    }
+
+    // the optional header fields always appear in this order, each only when its flag is set
+    fn next_header_section(&mut self, from: Section) {
+        let order = [
+            (Section::Extra, FEXTRA),
+            (Section::Name, FNAME),
+            (Section::Comment, FCOMMENT),
+            (Section::HeaderCrc, FHCRC),
+        ];
+        let start = order.iter().position(|(s, _)| *s == from).map_or(0, |p| p + 1);
+        self.section = order[start..]
+            .iter()
+            .find(|(_, flag)| self.flags & flag != 0)
+            .map_or(Section::Deflate, |(s, _)| *s);
+        self.counter = 0;
+    }
+
+    fn inflate<'a, F: FnMut(&[u8])>(&mut self, mut input: &'a [u8], sink: &mut F) -> Result<&'a [u8], ParseError> {
+        loop {
+            let res = inflate(&mut self.inflater, input, &mut self.out, MZFlush::None);
+            input = &input[res.bytes_consumed..];
+
+            let written = &self.out[..res.bytes_written];
+            self.crc = crc32_update(self.crc, written);
+            self.size = self.size.wrapping_add(written.len() as u32);
+            sink(written);
+
+            match res.status {
+                Ok(MZStatus::StreamEnd) => {
+                    self.section = Section::Trailer;
+                    self.counter = 0;
+                    return Ok(input);
+                }
+                // waiting on more input
+                Err(MZError::Buf) => return Ok(input),
+                Err(e) => return Err(ParseError::Decompression(format!("{:?}", e))),
+                Ok(_) if res.bytes_consumed == 0 && res.bytes_written == 0 => return Ok(input),
+                Ok(_) => {}
+            }
+        }
+    }
+
+    fn check_trailer(&self) -> Result<(), ParseError> {
+        let crc = u32::from_le_bytes([self.trailer[0], self.trailer[1], self.trailer[2], self.trailer[3]]);
+        let size = u32::from_le_bytes([self.trailer[4], self.trailer[5], self.trailer[6], self.trailer[7]]);
+
+        if crc != !self.crc {
+            return Err(corrupt("crc32 mismatch"));
+        }
+        if size != self.size {
+            return Err(corrupt("length mismatch"));
+        }
+        Ok(())
+    }
+}
+
+fn corrupt(reason: &str) -> ParseError {
+    ParseError::Decompression(reason.into())
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(crc, |crc, b| CRC32_TABLE[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8))
+}
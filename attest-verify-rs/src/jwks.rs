@@ -0,0 +1,123 @@
+use crate::attestation::Attestation;
+use crate::error::ParseError;
+use crate::pubkey::PublicKey;
+use crate::verify::{verify_bundle, AttestationKey, SignatureVerdict};
+use core::fmt;
+use serde_json::Value;
+
+/* JWKS matching.
+ *
+ * A service that publishes its keys at `/.well-known/jwks.json` can be tied back to the HSM: the
+ * JWK whose modulus and exponent (or curve and point) equal the public key rebuilt from an
+ * attestation is the attested key. Keys of other types, or that don't decode, never match.
+ * In batch, every key of the set is held against a number of attestations; a key is accounted for
+ * once an attestation matches it and its signature isn't invalid.
+ * */
+
+#[derive(Debug, Clone)]
+pub struct Jwk {
+    // position in the `keys` array, for keys without a `kid`
+    pub index: usize,
+    pub kid: Option<String>,
+    pub key: Result<PublicKey, ParseError>,
+}
+
+impl Jwk {
+    pub fn from_json(index: usize, jwk: &Value) -> Self {
+        let kid = jwk["kid"].as_str().map(str::to_string);
+        Jwk { index, kid, key: PublicKey::from_jwk(jwk) }
+    }
+}
+
+impl fmt::Display for Jwk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kid {
+            Some(kid) => write!(f, "kid '{}'", kid),
+            None => write!(f, "key #{}", self.index),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+impl Jwks {
+    pub fn from_json(json: &str) -> Result<Self, ParseError> {
+        let value: Value = serde_json::from_str(json).map_err(|e| ParseError::InvalidJwks(e.to_string()))?;
+        let keys = value["keys"]
+            .as_array()
+            .ok_or_else(|| ParseError::InvalidJwks("no 'keys' array".into()))?;
+        Ok(Jwks { keys: keys.iter().enumerate().map(|(index, jwk)| Jwk::from_json(index, jwk)).collect() })
+    }
+
+    pub fn find(&self, key: &PublicKey) -> Option<&Jwk> {
+        self.keys.iter().find(|jwk| jwk.key.as_ref().is_ok_and(|jwk_key| jwk_key == key))
+    }
+
+    /// The keys none of `matches` accounts for, in the order of the set.
+    pub fn unmatched<'a>(&'a self, matches: &[JwksMatch]) -> Vec<&'a Jwk> {
+        self.keys
+            .iter()
+            .filter(|jwk| !matches.iter().any(|m| m.accounts_for(jwk.index)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JwksMatch {
+    // the attested key
+    pub key: PublicKey,
+    pub index: Option<usize>,
+    pub kid: Option<String>,
+    // only with an owner key to check the bundle signature against
+    pub verdict: Option<SignatureVerdict>,
+}
+
+impl JwksMatch {
+    pub fn is_match(&self) -> bool {
+        self.index.is_some()
+    }
+
+    fn accounts_for(&self, index: usize) -> bool {
+        self.index == Some(index) && self.verdict != Some(SignatureVerdict::Invalid)
+    }
+
+    pub fn outcome(&self) -> String {
+        match (self.index, &self.kid) {
+            (Some(_), Some(kid)) => format!("match (kid '{}')", kid),
+            (Some(index), None) => format!("match (key #{}, no kid)", index),
+            (None, _) => "no key in the set matches".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for JwksMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "JWK = {}", self.outcome())?;
+        match self.verdict {
+            Some(verdict) => write!(f, ", signature {}", verdict),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Finds the JWK of the attested key. With `owner`, the signature over `bundle` (the inflated bytes
+/// `attestation` was parsed from) is checked too.
+pub fn match_attestation(
+    jwks: &Jwks,
+    attestation: &Attestation,
+    bundle: &[u8],
+    owner: Option<&AttestationKey>,
+) -> Result<JwksMatch, ParseError> {
+    let key = PublicKey::from_attestation(attestation)?;
+    let jwk = jwks.find(&key);
+    let verdict = owner.map(|owner| verify_bundle(bundle, owner)).transpose()?;
+    Ok(JwksMatch {
+        index: jwk.map(|jwk| jwk.index),
+        kid: jwk.and_then(|jwk| jwk.kid.clone()),
+        key,
+        verdict,
+    })
+}
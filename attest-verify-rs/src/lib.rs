@@ -1,42 +1,96 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 #[macro_use]
 pub mod macros;
+pub mod attestation;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod chain;
 pub mod error;
 pub mod function;
+#[cfg(feature = "std")]
+pub mod gcp;
+pub mod gzip;
+#[cfg(feature = "std")]
+pub mod jwks;
+pub mod observer;
+pub mod pkcs11;
+#[cfg(feature = "std")]
+pub mod policy;
+pub mod probe;
+#[cfg(feature = "std")]
+pub mod pubkey;
 pub mod tlv_mapping;
 pub mod state_transitions;
+#[cfg(feature = "std")]
+pub mod verify;
+#[cfg(feature = "std")]
 pub mod writer;
 
-use function::{build_unboxed_handlers, Callable, Func, FuncMap};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use attestation::{Attestation, AttestationHeader, Attribute, KeyAttributes};
+use error::ParseError;
+use function::{Callable, Func};
 use num_enum::FromPrimitive;
-use writer::Writer;
+use observer::{AttestationObserver, Observers};
+use probe::{SignatureProbe, SIGNATURE_LENGTHS};
 
-pub type FuncResult = Vec<Vec<Func<fn(&mut Machine) -> Option<State>>>>;
+// format (4) and the two key offsets (2 + 2) at the start of the attribute buffer
+pub const ATTR_HEADER_LEN: u32 = 8;
+// object handle (4), attribute count (4) and attribute size (4) at the start of each key section
+pub const SECTION_HEADER_LEN: u32 = 12;
+
+// handlers by current and proposed state, see `transitions!`
+pub type Handlers = [[Func<fn(&mut Machine) -> Option<State>>; STATE_VARIANTS]; STATE_VARIANTS];
 
 #[derive(Debug)]
 pub struct Machine {
     stack: Vec<u8>,
     state_stack: Vec<State>,
-    state_machine: Vec<u8>,
-    func_table: FuncResult,
     state: State,
     prev: State,
     counter: u32,
     pub be_int: u32,
     byte: u8,
     index: usize,
+    pub response_code: u32,
+    pub request_id: u32,
     pub total_size: u32,
     pub buff_size: u32,
     pub attr_offset: u32,
+    response_payload: Vec<u8>,
+    pub attribute_format: u32,
     pub firstkey_offset: u32,
     pub secondkey_offset: u32,
     pub attrs_processed: u32,
     pub attr_count: u32,
+    pub object_handle: u32,
+    pub attr_size: u32,
+    // where `SkipToSection` hands over to the next state
+    skip_to: usize,
     mode: Mode,
     pub tlv_type: u32,
     pub tlv_len: u32,
     pub signature_len: usize,
     pub key_mode: KeyMode,
-    pub writer: Option<Writer>,
+    attestation: Attestation,
+    error: Option<ParseError>,
+    observers: Observers,
+    // set until the caller pins the signature length
+    detect_signature: bool,
+    probe: Option<SignatureProbe>,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Machine {
@@ -46,94 +100,353 @@ impl Machine {
             prev: State::Initial,
             stack: Vec::new(),
             state_stack: Vec::new(),
-            state_machine: make_state(),
-            func_table: build_unboxed_handlers(),
             counter: 0,
             byte: 0,
             be_int: 0,
             index: 0,
+            response_code: 0,
+            request_id: 0,
             total_size: 0,
             buff_size: 0,
             attr_offset: 0,
+            response_payload: Vec::new(),
+            attribute_format: 0,
             firstkey_offset: 0,
             secondkey_offset: 0,
             attrs_processed: 0,
             attr_count: 0,
+            object_handle: 0,
+            attr_size: 0,
+            skip_to: 0,
             mode: Mode::default(),
             tlv_type: 0,
             tlv_len: 0,
-            signature_len: 256,
+            signature_len: SIGNATURE_LENGTHS[0],
             key_mode: KeyMode::default(),
-            writer: None,
+            attestation: Attestation::default(),
+            error: None,
+            observers: Observers::default(),
+            detect_signature: true,
+            probe: None,
         }
     }
 
+    /// Parses with a known signature length, e.g. the modulus size of the owner key, instead of
+    /// detecting it from the bundle.
     pub fn new_with_signature_len(len: usize) -> Self {
         let mut machine = Self::new();
         machine.set_signature_len(len);
         machine
     }
 
-    pub fn with_writer(mut self) -> Self {
-        let writer = Writer::new();
-        self.writer = Some(writer);
+    /// Gets the machine ready for the next bundle, keeping its observers, a signature length given
+    /// to `new_with_signature_len` and the capacity of its buffers.
+    pub fn reset(&mut self) {
+        let pinned = (!self.detect_signature).then_some(self.signature_len);
+        let mut fresh = Self {
+            stack: core::mem::take(&mut self.stack),
+            state_stack: core::mem::take(&mut self.state_stack),
+            response_payload: core::mem::take(&mut self.response_payload),
+            observers: core::mem::take(&mut self.observers),
+            ..Self::new()
+        };
+        fresh.stack.clear();
+        fresh.state_stack.clear();
+        fresh.response_payload.clear();
+        if let Some(len) = pinned {
+            fresh.set_signature_len(len);
+        }
+        *self = fresh;
+    }
+
+    /// Registers an observer to be told about each header, section, attribute and signature as it
+    /// is parsed.
+    pub fn with_observer<O: AttestationObserver + 'static>(mut self, observer: O) -> Self {
+        self.add_observer(Box::new(observer));
         self
     }
 
+    pub fn add_observer(&mut self, observer: Box<dyn AttestationObserver>) {
+        self.observers.push(observer);
+    }
+
     pub fn run_buf(&mut self, buff: &[u8]) {
         for c in buff {
             self.parse(*c);
         }
     }
 
-    pub fn to_json_bytes(&mut self) -> Option<Vec<u8>> {
-        if let Some(writer) = self.writer.take() {
-            Some(writer.to_json_bytes())
-        } else {
-            None
+    /// Hands back the parsed result, leaving the machine with an empty one. Fails if the input
+    /// was malformed or ended before the signature was read in full.
+    pub fn finish(&mut self) -> Result<Attestation, ParseError> {
+        // input ran out while we were still looking for the attribute buffer
+        if self.probe.is_some() {
+            self.resolve_probe();
+        }
+        if let Some(err) = self.error.clone() {
+            return Err(err);
         }
+        if self.state != State::Done {
+            return Err(ParseError::Truncated { state: self.state, offset: self.index });
+        }
+
+        let mut attestation = core::mem::take(&mut self.attestation);
+        attestation.header = self.header();
+        Ok(attestation)
     }
 
     pub fn parse(&mut self, c: u8) {
+        // once the input is known to be malformed there's nothing more to learn from it
+        if self.error.is_some() {
+            return;
+        }
+
+        if let Some(probe) = self.probe.as_mut() {
+            // whatever comes before the lowest candidate offset can only be response payload
+            if self.index < probe.start() {
+                self.response_payload.push(c);
+            }
+            let decided = probe.push(self.index, c);
+            self.index += 1;
+            if decided {
+                self.resolve_probe();
+            }
+            return;
+        }
+        self.step(c);
+    }
+
+    // settles on a signature length and replays the held back bytes from the attribute offset
+    fn resolve_probe(&mut self) {
+        let Some(probe) = self.probe.take() else {
+            return;
+        };
+        // input that ran out before every candidate could be judged
+        if !probe.is_complete() {
+            self.fail(ParseError::Truncated { state: self.state, offset: self.index });
+            return;
+        }
+        let (signature_len, attr_offset) = match probe.decide() {
+            Ok(decided) => decided,
+            Err(e) => {
+                self.fail(e);
+                return;
+            }
+        };
+        self.signature_len = signature_len;
+        self.attr_offset = attr_offset as u32;
+        self.index = probe.start();
+        for c in probe.into_bytes() {
+            if self.error.is_some() {
+                return;
+            }
+            self.step(c);
+        }
+    }
+
+    fn step(&mut self, c: u8) {
         let current_state = self.state;
         self.byte = c;
 
-        let proposed_state = self.state_machine[current_state as usize * 256 + c as usize];
+        // nothing ahead of the signature may spill over into it
+        if self.in_attributes() && self.index >= self.signature_offset() {
+            let err = match current_state {
+                State::TLVType | State::TLVLen | State::TLVValue => ParseError::AttributeCountMismatch {
+                    declared: self.attr_count,
+                    parsed: self.attrs_processed,
+                },
+                _ => ParseError::OffsetOutOfRange {
+                    offset: self.index as u64,
+                    limit: self.signature_offset() as u64,
+                },
+            };
+            self.fail(err);
+            return;
+        }
+
+        let proposed_state = STATE_TABLE[current_state as usize * 256 + c as usize];
         let new_state = self
             .run_funcs(current_state, proposed_state.into())
-            .unwrap_or_else(|| current_state);
+            .unwrap_or(current_state);
 
         // if we've manually overidden the state then reset the counters
-        if proposed_state != new_state as _ {
+        if proposed_state != new_state as u8 {
             self.reset_count();
         };
 
-        self.state = new_state.into();
+        self.state = new_state;
         self.prev = current_state;
         self.index += 1;
     }
 
     pub fn run_funcs(&mut self, current: State, new_state: State) -> Option<State> {
-        let func = self.func_table[current as usize][new_state as usize];
-        let res = func.apply(self);
-        res
+        let func = state_transitions::TRANSITIONS[current as usize][new_state as usize];
+        func.apply(self)
     }
 }
 
 impl Machine {
-    pub fn write_tlv(&mut self, attr_name: String, attr_value: String, mode: KeyMode) {
-        if let Some(writer) = self.writer.as_mut() {
-            writer.push(attr_name, attr_value, mode);
+    pub fn header(&self) -> AttestationHeader {
+        AttestationHeader {
+            response_code: self.response_code,
+            request_id: self.request_id,
+            total_size: self.total_size,
+            buff_size: self.buff_size,
+            response_payload: self.response_payload.clone(),
+            attr_offset: self.attr_offset,
+            attribute_format: self.attribute_format,
+            firstkey_offset: self.firstkey_offset,
+            secondkey_offset: self.secondkey_offset,
+            signature_len: self.signature_len as u32,
         }
     }
 
-    pub fn map_func(&mut self, mapper: FuncMap, func: Func<fn(&mut Machine) -> Option<State>>) {
-        let FuncMap(from_iter, to) = mapper;
-        for from in from_iter {
-            self.func_table[from as usize][to as usize] = func;
+    pub fn emit_header(&mut self) {
+        let header = self.header();
+        self.observers.notify(|o| o.on_header(&header));
+    }
+
+    pub fn start_section(&mut self) {
+        let mode = self.key_mode;
+        self.observers.notify(|o| o.on_key_section_start(mode));
+        self.attestation.sections.push(KeyAttributes {
+            object_handle: self.object_handle,
+            attr_count: self.attr_count,
+            attr_size: self.attr_size,
+            ..KeyAttributes::new(mode)
+        });
+    }
+
+    pub fn push_attribute(&mut self, attribute: Attribute) {
+        if self.attestation.sections.is_empty() {
+            self.start_section();
+        }
+        let mode = self.key_mode;
+        self.observers.notify(|o| o.on_attribute(mode, &attribute));
+        if let Some(section) = self.attestation.sections.last_mut() {
+            section.attributes.push(attribute);
         }
     }
 
+    pub fn push_payload(&mut self, byte: u8) {
+        self.response_payload.push(byte);
+    }
+
+    pub fn set_signature(&mut self, signature: Vec<u8>) {
+        self.observers.notify(|o| o.on_signature(&signature));
+        self.attestation.signature = signature;
+    }
+
+    pub fn fail(&mut self, err: ParseError) -> Option<State> {
+        if self.error.is_none() {
+            self.observers.notify(|o| o.on_error(&err));
+            self.error = Some(err);
+        }
+        None
+    }
+
+    pub fn error(&self) -> Option<&ParseError> {
+        self.error.as_ref()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    // works out where the attribute buffer starts: it sits right in front of the signature
+    pub fn locate_attributes(&mut self) -> Result<(), ParseError> {
+        if self.detect_signature {
+            self.probe = SignatureProbe::new(self.total_size, self.buff_size, self.index);
+            if self.probe.is_some() {
+                return Ok(());
+            }
+        }
+
+        let trailer = self.buff_size as u64 + self.signature_len as u64;
+        let attr_offset = (self.total_size as u64)
+            .checked_sub(trailer)
+            .filter(|offset| *offset > self.index as u64)
+            .ok_or(ParseError::OffsetOutOfRange { offset: trailer, limit: self.total_size as u64 })?;
+        self.attr_offset = attr_offset as u32;
+        Ok(())
+    }
+
+    /* Both key offsets are relative to the start of the attribute buffer. The public (or only)
+     * section runs up to the private one, and the last section up to the signature. Whatever lies
+     * between the end of a section's attributes and the next boundary is padding.
+     * */
+    pub fn section_bounds(&self, mode: KeyMode) -> (usize, usize) {
+        let base = self.attr_offset as usize;
+        match (mode, self.secondkey_offset) {
+            (KeyMode::Primary, 0) => (base + self.firstkey_offset as usize, self.signature_offset()),
+            (KeyMode::Primary, second) => (base + self.firstkey_offset as usize, base + second as usize),
+            (KeyMode::Secondary, second) => (base + second as usize, self.signature_offset()),
+        }
+    }
+
+    // the key offsets have to leave room for the headers in front of them, and stay in the buffer
+    pub fn check_sections(&self) -> Result<(), ParseError> {
+        let abs = |offset: u32| self.attr_offset as u64 + offset as u64;
+        let first = self.firstkey_offset;
+        let second = self.secondkey_offset;
+
+        if first < ATTR_HEADER_LEN {
+            return Err(ParseError::SectionOverlap {
+                mode: KeyMode::Primary,
+                offset: abs(first),
+                previous_end: abs(ATTR_HEADER_LEN),
+            });
+        }
+        let mut sections = vec![(KeyMode::Primary, first)];
+        if second != 0 {
+            if second < first + SECTION_HEADER_LEN {
+                return Err(ParseError::SectionOverlap {
+                    mode: KeyMode::Secondary,
+                    offset: abs(second),
+                    previous_end: abs(first + SECTION_HEADER_LEN),
+                });
+            }
+            sections.push((KeyMode::Secondary, second));
+        }
+        for (mode, offset) in sections {
+            if offset as u64 + SECTION_HEADER_LEN as u64 > self.buff_size as u64 {
+                return Err(ParseError::SectionOutOfRange {
+                    mode,
+                    offset: abs(offset + SECTION_HEADER_LEN),
+                    limit: self.signature_offset() as u64,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // skips padding up to `offset`, then carries on in `resume`
+    pub fn skip_to(&mut self, offset: usize, resume: State) -> State {
+        self.skip_to = offset;
+        self.push_state(resume);
+        State::SkipToSection
+    }
+
+    // where the attributes of the current section end, going by its header
+    pub fn attributes_end(&self) -> usize {
+        let (start, _) = self.section_bounds(self.key_mode);
+        // saturating, the declared size can overflow a 32 bit usize (wasm)
+        start.saturating_add(SECTION_HEADER_LEN as usize + self.attr_size as usize)
+    }
+
+    pub fn skip_target(&self) -> usize {
+        self.skip_to
+    }
+
+    pub fn signature_offset(&self) -> usize {
+        self.attr_offset as usize + self.buff_size as usize
+    }
+
+    fn in_attributes(&self) -> bool {
+        let state = self.state as u8;
+        state > State::SkipToOffset as u8 && state < State::Signature as u8
+    }
+
     pub fn current_byte(&self) -> u8 {
         self.byte
     }
@@ -156,7 +469,7 @@ impl Machine {
 
     // only come through set state to ensure we've got an accurate representation of previous
     pub fn set_state(&mut self, new_state: State) {
-        self.prev = self.state.clone();
+        self.prev = self.state;
         self.state = new_state;
     }
 
@@ -203,6 +516,7 @@ impl Machine {
     }
 
     pub fn set_signature_len(&mut self, len: usize) {
+        self.detect_signature = false;
         self.signature_len = len;
     }
 
@@ -232,21 +546,29 @@ enum_builder! (
         TLVLen,
         TLVValue,
         SecondaryKey,
+        SkipToSection,
         Signature,
+        Done,
     }
 );
 
 const STATE_VARIANTS: usize = State::attr_count();
 
+// the state proposed for each state and input byte, built once at compile time
+static STATE_TABLE: [u8; 256 * STATE_VARIANTS] = make_state();
+
 // potentially we could have another machine that tracks the count for each state and increments
 // where appropriate.
-fn make_state() -> Vec<u8> {
-    let mut sm = vec![0u8; 256 * STATE_VARIANTS];
-    for enum_state in 0..State::attr_count() {
-        let state = State::from_primitive(enum_state as _);
-        for c in 0usize..=255 {
-            sm[state as usize * 256 + c] = enum_state as _;
+const fn make_state() -> [u8; 256 * STATE_VARIANTS] {
+    let mut sm = [0u8; 256 * STATE_VARIANTS];
+    let mut state = 0;
+    while state < STATE_VARIANTS {
+        let mut c = 0;
+        while c < 256 {
+            sm[state * 256 + c] = state as u8;
+            c += 1;
         }
+        state += 1;
     }
     sm
 }
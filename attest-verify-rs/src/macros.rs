@@ -19,12 +19,14 @@ macro_rules! enum_builder {
 }
 
 macro_rules! tlv_mapping {
-    ($(#[$comment:meta])* $enum_vis:vis enum $name:ident { $( $($attr_comments:meta)?  $id:expr => $attr_name:ident = $typ:ident,)*} ) => {
+    ($(#[$comment:meta])* $enum_vis:vis enum $name:ident {
+        $( $(#[doc = $doc:literal])* $id:literal => $attr_name:ident = $typ:ident [$relevance:ident $(, weak if $weak:pat)?] $(as $cka:ident)?, )*
+    } ) => {
             $($comment)*
             #[allow(non_camel_case_types)]
-            #[derive(Copy, Clone)]
+            #[derive(Copy, Clone, PartialEq, Eq)]
             $enum_vis enum $name {
-                $($attr_name),*
+                $( $(#[doc = $doc])* $attr_name ),*
                 ,UNKNOWN
             }
 
@@ -37,6 +39,14 @@ macro_rules! tlv_mapping {
                 }
             }
 
+            // the TLV type written on the wire, attributes without a mapping go out as OBJ_UNKNOWN
+            pub fn to_int(&self) -> u32 {
+                match *self {
+                    $( Self::$attr_name => $id ),*,
+                    Self::UNKNOWN => 0x80000000,
+                }
+            }
+
             pub fn encode(&self, bytes: &[u8], len: u32) -> TLVValue {
                 match *self {
                     $(Self::$attr_name => crate::tlv_mapping::$typ::encode(bytes, len)
@@ -45,11 +55,71 @@ macro_rules! tlv_mapping {
                         .map(crate::tlv_mapping::TLVValue::Bytes),
                 }.unwrap_or_else(|| TLVValue::RawBytes(RawBytes(bytes.to_vec())))
             }
+
+            /// What the attribute says about the key, from its doc comment in the table.
+            pub fn description(&self) -> &'static str {
+                match *self {
+                    $( Self::$attr_name => concat!($($doc),*).trim(), )*
+                    Self::UNKNOWN => "Attribute type without a mapping.",
+                }
+            }
+
+            pub fn security_relevance(&self) -> crate::tlv_mapping::SecurityRelevance {
+                match *self {
+                    $( Self::$attr_name => crate::tlv_mapping::SecurityRelevance::$relevance, )*
+                    Self::UNKNOWN => crate::tlv_mapping::SecurityRelevance::Info,
+                }
+            }
+
+            /// Whether `value` weakens the protection of a private or secret key, e.g. an extractable
+            /// key. Values that don't decode never count.
+            pub fn weakens(&self, value: &TLVValue) -> bool {
+                match *self {
+                    $( Self::$attr_name => false $(|| matches!(value, TLVValue::$typ($weak)))?, )*
+                    Self::UNKNOWN => false,
+                }
+            }
+
+            /// The PKCS#11 attribute this one corresponds to, if there is one.
+            pub fn pkcs11(&self) -> Option<crate::pkcs11::Pkcs11Attribute> {
+                match *self {
+                    $( Self::$attr_name => None $(.or(Some(crate::pkcs11::Pkcs11Attribute {
+                        id: crate::pkcs11::$cka,
+                        name: stringify!($cka),
+                    })))?, )*
+                    Self::UNKNOWN => None,
+                }
+            }
+
+            pub fn from_pkcs11(id: u32) -> Option<Self> {
+                match id {
+                    $( $(crate::pkcs11::$cka => Some(Self::$attr_name),)? )*
+                    _ => None,
+                }
+            }
+
+            pub fn from_pkcs11_name(name: &str) -> Option<Self> {
+                match name {
+                    $( $(stringify!($cka) => Some(Self::$attr_name),)? )*
+                    _ => None,
+                }
+            }
         }
 
         paste! {
-            impl std::fmt::Display for $name {
-                fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+            impl $name {
+                /// Looks up a mapping by its display name (`ObjAttrClass`), its Cavium name (`OBJ_ATTR_CLASS`)
+                /// or its PKCS#11 name (`CKA_CLASS`).
+                pub fn from_name(name: &str) -> Option<Self> {
+                    match name {
+                        $( stringify!([< $attr_name:camel >]) | stringify!($attr_name) => Some(Self::$attr_name), )*
+                        _ => Self::from_pkcs11_name(name),
+                    }
+                }
+            }
+
+            impl core::fmt::Display for $name {
+                fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
                     match *self {
                         $( Self::$attr_name => {
                             write!(f, "{}", stringify!( [< $attr_name:camel >]))
@@ -59,11 +129,11 @@ macro_rules! tlv_mapping {
                 }
             }
 
-            impl std::fmt::Debug for $name {
-                fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+            impl core::fmt::Debug for $name {
+                fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), core::fmt::Error> {
                     match *self {
                         $( Self::$attr_name => {
-                            write!(f, "{} (ID: {})", stringify!( [< $attr_name:camel >]), $id)
+                            write!(f, "{} (ID: {})", stringify!( [< $attr_name:camel >]), $id as u32)
                         }),*,
                         Self::UNKNOWN => write!(f, "{} ", "Unknown"),
                     }
@@ -72,3 +142,18 @@ macro_rules! tlv_mapping {
         }
     };
 }
+
+/* Builds the handler table at compile time, see `state_transitions`. Each entry maps a transition
+ * from one or more states into another to a handler. Handlers don't capture anything, so they
+ * coerce to plain fn pointers and the whole table can sit in a static.
+ * */
+macro_rules! transitions {
+    ($(#[$comment:meta])* $vis:vis static $name:ident = { $( $($from:ident)|+ => $to:ident : $func:expr, )* } ) => {
+        $(#[$comment])*
+        $vis static $name: crate::Handlers = {
+            let mut table: crate::Handlers = [[crate::function::Func::Unit(()); crate::STATE_VARIANTS]; crate::STATE_VARIANTS];
+            $( $( table[crate::State::$from as usize][crate::State::$to as usize] = crate::function::Func::Fun($func); )+ )*
+            table
+        };
+    };
+}
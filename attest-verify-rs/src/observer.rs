@@ -0,0 +1,47 @@
+use crate::attestation::{AttestationHeader, Attribute};
+use crate::error::ParseError;
+use crate::KeyMode;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+
+/* Parse events, delivered while the bundle streams through the `Machine`.
+ *
+ * Observers see every value as soon as its last byte arrives, so callers that want progressive
+ * output (the CLI) don't have to wait for `Machine::finish`. Every method has an empty default,
+ * implement only the ones you care about.
+ * */
+pub trait AttestationObserver {
+    // the fixed header fields, once both key offsets have been read
+    fn on_header(&mut self, _header: &AttestationHeader) {}
+
+    fn on_key_section_start(&mut self, _mode: KeyMode) {}
+
+    fn on_attribute(&mut self, _mode: KeyMode, _attribute: &Attribute) {}
+
+    fn on_signature(&mut self, _signature: &[u8]) {}
+
+    // only the first error is reported, parsing stops there
+    fn on_error(&mut self, _error: &ParseError) {}
+}
+
+#[derive(Default)]
+pub struct Observers(Vec<Box<dyn AttestationObserver>>);
+
+impl Observers {
+    pub fn push(&mut self, observer: Box<dyn AttestationObserver>) {
+        self.0.push(observer);
+    }
+
+    pub fn notify<F: FnMut(&mut dyn AttestationObserver)>(&mut self, mut event: F) {
+        for observer in self.0.iter_mut() {
+            event(observer.as_mut());
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
@@ -0,0 +1,95 @@
+use crate::tlv_mapping::TLVMapping;
+use alloc::string::{String, ToString};
+use core::fmt;
+
+/* PKCS#11 attribute types (CKA_*, PKCS#11 v3.0 section 4) for the Cavium attributes that have one.
+ *
+ * Most Cavium attribute types are the PKCS#11 value itself; the key check value is the exception,
+ * the HSM writes it as 0x0173 where PKCS#11 has CKA_CHECK_VALUE at 0x0090. Attributes without a
+ * PKCS#11 counterpart (split keys, the extended check value, the mechanism flags) keep their Cavium
+ * names. The mapping itself is the `as CKA_*` column of the `tlv_mapping!` table.
+ * */
+
+pub const CKA_CLASS: u32 = 0x0000;
+pub const CKA_TOKEN: u32 = 0x0001;
+pub const CKA_PRIVATE: u32 = 0x0002;
+pub const CKA_LABEL: u32 = 0x0003;
+pub const CKA_TRUSTED: u32 = 0x0086;
+pub const CKA_CHECK_VALUE: u32 = 0x0090;
+pub const CKA_KEY_TYPE: u32 = 0x0100;
+pub const CKA_ID: u32 = 0x0102;
+pub const CKA_SENSITIVE: u32 = 0x0103;
+pub const CKA_ENCRYPT: u32 = 0x0104;
+pub const CKA_DECRYPT: u32 = 0x0105;
+pub const CKA_WRAP: u32 = 0x0106;
+pub const CKA_UNWRAP: u32 = 0x0107;
+pub const CKA_SIGN: u32 = 0x0108;
+pub const CKA_VERIFY: u32 = 0x010A;
+pub const CKA_DERIVE: u32 = 0x010C;
+pub const CKA_MODULUS: u32 = 0x0120;
+pub const CKA_MODULUS_BITS: u32 = 0x0121;
+pub const CKA_PUBLIC_EXPONENT: u32 = 0x0122;
+pub const CKA_VALUE_LEN: u32 = 0x0161;
+pub const CKA_EXTRACTABLE: u32 = 0x0162;
+pub const CKA_LOCAL: u32 = 0x0163;
+pub const CKA_NEVER_EXTRACTABLE: u32 = 0x0164;
+pub const CKA_ALWAYS_SENSITIVE: u32 = 0x0165;
+pub const CKA_EC_PARAMS: u32 = 0x0180;
+pub const CKA_EC_POINT: u32 = 0x0181;
+pub const CKA_WRAP_WITH_TRUSTED: u32 = 0x0210;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Pkcs11Attribute {
+    pub id: u32,
+    pub name: &'static str,
+}
+
+impl Pkcs11Attribute {
+    pub fn from_name(name: &str) -> Option<Self> {
+        TLVMapping::from_pkcs11_name(name).and_then(|mapping| mapping.pkcs11())
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        TLVMapping::from_pkcs11(id).and_then(|mapping| mapping.pkcs11())
+    }
+
+    /// The Cavium attribute this one is attested as.
+    pub fn mapping(&self) -> TLVMapping {
+        TLVMapping::from_pkcs11(self.id).unwrap_or(TLVMapping::UNKNOWN)
+    }
+}
+
+impl fmt::Display for Pkcs11Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:#06x})", self.name, self.id)
+    }
+}
+
+/// Which names attributes are labelled with in the output.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum NameStyle {
+    // ObjAttrExtractable
+    #[default]
+    Cavium,
+    // CKA_EXTRACTABLE, falling back to the Cavium name
+    Pkcs11,
+}
+
+impl NameStyle {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cavium" => Some(NameStyle::Cavium),
+            "pkcs11" => Some(NameStyle::Pkcs11),
+            _ => None,
+        }
+    }
+}
+
+impl TLVMapping {
+    pub fn label(&self, style: NameStyle) -> String {
+        match (style, self.pkcs11()) {
+            (NameStyle::Pkcs11, Some(attribute)) => attribute.name.to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use crate::attestation::Attestation;
+use crate::error::ParseError;
+use crate::pkcs11::NameStyle;
+use crate::tlv_mapping::{TLVMapping, TLVValue};
+use crate::KeyMode;
+use core::fmt;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/* Declarative expectations over the parsed key attributes.
+ *
+ * A policy holds one table of assertions per key section, keyed by attribute name. In TOML:
+ *
+ *   [secondary]
+ *   ObjAttrClass = { equals = "private-key" }
+ *   ObjAttrLocal = { equals = true }
+ *   ObjAttrExtractable = { equals = false }
+ *   ObjAttrKeyType = { one_of = ["rsa", "ec"] }
+ *   ObjAttrLabel = { present = true }
+ *   ObjAttrId = { hex_prefix = "7b507b8a" }
+ *
+ * Attributes may also go by their Cavium (`OBJ_ATTR_LOCAL`) or PKCS#11 (`CKA_LOCAL`) names.
+ * The same layout works as JSON. Symmetric keys only carry a `primary` section, so a policy with
+ * `secondary` assertions will fail against them.
+ * */
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Expected {
+    Bool(bool),
+    Int(u32),
+    Str(String),
+}
+
+impl Expected {
+    fn matches(&self, value: &TLVValue) -> bool {
+        match self {
+            Expected::Bool(b) => value.as_bool() == Some(*b),
+            Expected::Int(i) => value.as_int() == Some(*i),
+            Expected::Str(s) => value.to_str() == *s,
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Bool(b) => write!(f, "{}", b),
+            Expected::Int(i) => write!(f, "{}", i),
+            Expected::Str(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Assertion {
+    Equals(Expected),
+    OneOf(Vec<Expected>),
+    Present(bool),
+    HexPrefix(String),
+}
+
+impl Assertion {
+    fn check(&self, value: Option<&TLVValue>) -> bool {
+        match (self, value) {
+            (Assertion::Present(present), value) => value.is_some() == *present,
+            (_, None) => false,
+            (Assertion::Equals(expected), Some(value)) => expected.matches(value),
+            (Assertion::OneOf(options), Some(value)) => options.iter().any(|e| e.matches(value)),
+            (Assertion::HexPrefix(prefix), Some(value)) => {
+                value.to_str().to_ascii_lowercase().starts_with(&prefix.to_ascii_lowercase())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Assertion::Equals(expected) => write!(f, "equals {}", expected),
+            Assertion::OneOf(options) => {
+                let options = options.iter().map(|o| o.to_string()).collect::<Vec<_>>();
+                write!(f, "one of [{}]", options.join(", "))
+            }
+            Assertion::Present(true) => write!(f, "present"),
+            Assertion::Present(false) => write!(f, "absent"),
+            Assertion::HexPrefix(prefix) => write!(f, "starts with {}", prefix),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    #[serde(default)]
+    primary: BTreeMap<String, Assertion>,
+    #[serde(default)]
+    secondary: BTreeMap<String, Assertion>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    assertions: Vec<(KeyMode, TLVMapping, Assertion)>,
+}
+
+impl Policy {
+    pub fn from_toml(contents: &str) -> Result<Self, ParseError> {
+        let file = toml::from_str(contents).map_err(|e| ParseError::InvalidPolicy(e.to_string()))?;
+        Self::from_file(file)
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, ParseError> {
+        let file = serde_json::from_str(contents).map_err(|e| ParseError::InvalidPolicy(e.to_string()))?;
+        Self::from_file(file)
+    }
+
+    fn from_file(file: PolicyFile) -> Result<Self, ParseError> {
+        let sections = [(KeyMode::Primary, file.primary), (KeyMode::Secondary, file.secondary)];
+
+        let mut assertions = Vec::new();
+        for (mode, section) in sections {
+            for (name, assertion) in section {
+                let mapping = TLVMapping::from_name(&name)
+                    .ok_or_else(|| ParseError::InvalidPolicy(format!("unknown attribute '{}'", name)))?;
+                assertions.push((mode, mapping, assertion));
+            }
+        }
+        Ok(Self { assertions })
+    }
+
+    pub fn evaluate(&self, attestation: &Attestation) -> PolicyReport {
+        let results = self
+            .assertions
+            .iter()
+            .map(|(mode, mapping, assertion)| {
+                let value = attestation.section(*mode).and_then(|s| s.value(*mapping));
+                AssertionResult {
+                    mode: *mode,
+                    mapping: *mapping,
+                    assertion: assertion.clone(),
+                    actual: value.map(TLVValue::to_str),
+                    passed: assertion.check(value),
+                }
+            })
+            .collect();
+        PolicyReport { results }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AssertionResult {
+    pub mode: KeyMode,
+    pub mapping: TLVMapping,
+    pub assertion: Assertion,
+    pub actual: Option<String>,
+    pub passed: bool,
+}
+
+impl AssertionResult {
+    /// PASS or FAIL, the section, the attribute named in `style`, the assertion and the attested
+    /// value; `Display` uses the Cavium names.
+    pub fn to_line(&self, style: NameStyle) -> String {
+        format!(
+            "{} {:?} {} {} (actual: {})",
+            if self.passed { "PASS" } else { "FAIL" },
+            self.mode,
+            self.mapping.label(style),
+            self.assertion,
+            self.actual.as_deref().unwrap_or("absent"),
+        )
+    }
+}
+
+impl fmt::Display for AssertionResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_line(NameStyle::Cavium))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PolicyReport {
+    pub results: Vec<AssertionResult>,
+}
+
+impl PolicyReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &AssertionResult> {
+        self.results.iter().filter(|r| !r.passed)
+    }
+}
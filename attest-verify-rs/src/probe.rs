@@ -0,0 +1,141 @@
+/* Signature length detection.
+ *
+ * The attribute buffer sits right in front of the signature, so its offset depends on how long the
+ * signature is, i.e. on the owner key size, which the bundle doesn't record anywhere. Once
+ * `total_size` and `buff_size` are known every supported length gives a candidate offset, and the
+ * bytes at each candidate are checked for something that looks like the start of the attribute
+ * buffer:
+ *   - format (4), first and second key offsets (2 + 2)
+ *   - object handle (4), attribute count (4) and attribute size (4) of the first section, at the
+ *     first key offset, past any padding
+ * Everything from the lowest candidate onwards is held back until each candidate has been read as
+ * far as its first section header, then replayed through the machine from the one offset that holds
+ * up. If none or several do the bundle is rejected rather than guessed at; the owner key pins the
+ * length (`Machine::new_with_signature_len`). Nothing before the attribute buffer is parsed, so
+ * observers never see a byte twice.
+ * */
+
+use crate::error::ParseError;
+use crate::{ATTR_HEADER_LEN, SECTION_HEADER_LEN};
+use alloc::vec::Vec;
+
+pub const SIGNATURE_LENGTHS: [usize; 3] = [256, 384, 512];
+
+#[derive(Debug, Clone)]
+pub struct SignatureProbe {
+    start: usize,
+    buff_size: u64,
+    // (signature length, attribute offset)
+    candidates: Vec<(usize, usize)>,
+    bytes: Vec<u8>,
+}
+
+impl SignatureProbe {
+    /// Returns `None` when the bundle is too small for any of the supported lengths.
+    pub fn new(total_size: u32, buff_size: u32, index: usize) -> Option<Self> {
+        let candidates = SIGNATURE_LENGTHS
+            .iter()
+            .filter_map(|len| {
+                (total_size as u64)
+                    .checked_sub(buff_size as u64 + *len as u64)
+                    .filter(|offset| *offset > index as u64)
+                    .map(|offset| (*len, offset as usize))
+            })
+            .collect::<Vec<_>>();
+
+        let start = candidates.iter().map(|(_, offset)| *offset).min()?;
+        Some(Self { start, buff_size: buff_size as u64, candidates, bytes: Vec::new() })
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Takes the byte at `index`, returning true once every candidate can be judged.
+    pub fn push(&mut self, index: usize, byte: u8) -> bool {
+        if index >= self.start {
+            self.bytes.push(byte);
+        }
+        self.is_complete()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        let buffered = self.start + self.bytes.len();
+        self.candidates.iter().all(|(_, offset)| self.needed(*offset) <= buffered)
+    }
+
+    /// The (signature length, attribute offset) of the one candidate that holds up. Fails when
+    /// none or several of them do, the bundle can't be told apart from another layout then.
+    pub fn decide(&self) -> Result<(usize, usize), ParseError> {
+        let plausible = self
+            .candidates
+            .iter()
+            .filter(|(_, offset)| self.plausible(*offset))
+            .copied()
+            .collect::<Vec<_>>();
+        match plausible[..] {
+            [chosen] => Ok(chosen),
+            [] => Err(ParseError::UnknownSignatureLength),
+            _ => Err(ParseError::AmbiguousSignatureLength(plausible.iter().map(|(len, _)| *len).collect())),
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    // the big endian integer in the `len` bytes at `offset` in the bundle, once they have been read
+    fn read(&self, offset: usize, len: usize) -> Option<u64> {
+        let bytes = self.bytes.get(offset - self.start..offset - self.start + len)?;
+        Some(bytes.iter().fold(0u64, |acc, b| acc << 8 | *b as u64))
+    }
+
+    fn key_offsets(&self, offset: usize) -> Option<(u64, u64)> {
+        Some((self.read(offset + 4, 2)?, self.read(offset + 6, 2)?))
+    }
+
+    // the key offsets have to leave room for the section headers, and stay in the buffer
+    fn offsets_fit(&self, firstkey_offset: u64, secondkey_offset: u64) -> bool {
+        firstkey_offset >= ATTR_HEADER_LEN as u64
+            && firstkey_offset + SECTION_HEADER_LEN as u64 <= self.buff_size
+            && match secondkey_offset {
+                0 => true,
+                offset => offset >= firstkey_offset + SECTION_HEADER_LEN as u64 && offset < self.buff_size,
+            }
+    }
+
+    // how far the candidate at `offset` has to be read: the header of the attribute buffer, and
+    // then the first section header if the key offsets fit
+    fn needed(&self, offset: usize) -> usize {
+        match self.key_offsets(offset) {
+            Some((first, second)) if self.offsets_fit(first, second) => {
+                offset + first as usize + SECTION_HEADER_LEN as usize
+            }
+            _ => offset + ATTR_HEADER_LEN as usize,
+        }
+    }
+
+    fn plausible(&self, offset: usize) -> bool {
+        let Some((firstkey_offset, secondkey_offset)) = self.key_offsets(offset) else {
+            return false;
+        };
+        if !self.offsets_fit(firstkey_offset, secondkey_offset) {
+            return false;
+        }
+        let section = offset + firstkey_offset as usize;
+        let (attr_count, attr_size) = (self.read(section + 4, 4), self.read(section + 8, 4));
+        let (Some(attr_count), Some(attr_size)) = (attr_count, attr_size) else {
+            return false;
+        };
+
+        // every attribute takes at least 8 bytes of type and length, and the first section has
+        // to end before the second one (or the signature) starts
+        let section_end = firstkey_offset + SECTION_HEADER_LEN as u64 + attr_size;
+        attr_count > 0
+            && attr_count * 8 <= attr_size
+            && match secondkey_offset {
+                0 => section_end <= self.buff_size,
+                offset => section_end <= offset,
+            }
+    }
+}
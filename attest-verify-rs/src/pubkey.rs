@@ -0,0 +1,455 @@
+use crate::attestation::{Attestation, KeyAttributes};
+use crate::error::ParseError;
+use crate::pkcs11::NameStyle;
+use crate::tlv_mapping::{ClassKey, Curve, EcPoint, EncodeTLV, TLVMapping, TLVValue};
+// the hex helpers live with the TLV values, which need them without std
+pub use crate::tlv_mapping::{from_hex, to_hex};
+use crate::KeyMode;
+use base64ct::{Base64, Base64UrlUnpadded, Encoding};
+use core::fmt;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::{DecodePublicKey, EncodePublicKey};
+use rsa::traits::PublicKeyParts;
+use rsa::{BigUint, RsaPublicKey};
+use sha1::Sha1;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use x509_cert::der::asn1::BitString;
+use x509_cert::der::pem::LineEnding;
+use x509_cert::der::{self, pem, Any, Decode, Encode};
+use x509_cert::spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SubjectPublicKeyInfoOwned};
+
+/* Key check values and public key comparison.
+ *
+ * Both check values are digests of the DER encoded SubjectPublicKeyInfo, i.e. the output of
+ * `openssl rsa -pubin -outform der` (or `openssl ec -pubin -outform der`):
+ *   - KCV is the first three bytes of its SHA-1
+ *   - EKCV is its full SHA-256
+ * The public-key section additionally carries the modulus and exponent of an RSA key, or the curve
+ * and point of an EC key, which are compared byte-for-byte (ignoring leading zeros of RSA values).
+ *
+ * The same components rebuild the attested key, which exports as a PEM or DER SubjectPublicKeyInfo,
+ * a JWK (RFC 7517, with its RFC 7638 thumbprint as the `kid`) or an OpenSSH public key line.
+ * */
+
+pub const KCV_LEN: usize = 3;
+
+// id-ecPublicKey, RFC 5480
+const EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    #[default]
+    Pem,
+    Der,
+    Jwk,
+    OpenSsh,
+}
+
+impl KeyFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pem" => Some(KeyFormat::Pem),
+            "der" => Some(KeyFormat::Der),
+            "jwk" => Some(KeyFormat::Jwk),
+            "openssh" | "ssh" => Some(KeyFormat::OpenSsh),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicKey {
+    Rsa { modulus: Vec<u8>, exponent: Vec<u8> },
+    Ec { curve: Curve, point: EcPoint },
+}
+
+impl PublicKey {
+    pub fn from_rsa_components(modulus: &[u8], exponent: &[u8]) -> Self {
+        PublicKey::Rsa {
+            modulus: strip_zeros(modulus).to_vec(),
+            exponent: strip_zeros(exponent).to_vec(),
+        }
+    }
+
+    /// Takes the uncompressed point, `0x04 || X || Y`, which has to lie on `curve`.
+    pub fn from_ec_point(curve: Curve, point: &[u8]) -> Option<Self> {
+        let point = EcPoint::from_uncompressed(point)
+            .filter(|point| point.x.len() == curve.field_len() && is_on_curve(curve, point))?;
+        Some(PublicKey::Ec { curve, point })
+    }
+
+    /// Accepts a `PUBLIC KEY` (SubjectPublicKeyInfo, RSA or EC) or `RSA PUBLIC KEY` (PKCS#1) PEM.
+    pub fn from_pem(pem: &str) -> Result<Self, ParseError> {
+        let (_, der) =
+            pem::decode_vec(pem.trim().as_bytes()).map_err(|e| ParseError::InvalidPublicKey(e.to_string()))?;
+        Self::from_der(&der)
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, ParseError> {
+        RsaPublicKey::from_public_key_der(der)
+            .or_else(|_| RsaPublicKey::from_pkcs1_der(der))
+            .map(|key| Self::from(&key))
+            .or_else(|e| Self::from_ec_spki(der).ok_or(e))
+            .map_err(|e| ParseError::InvalidPublicKey(e.to_string()))
+    }
+
+    fn from_ec_spki(der: &[u8]) -> Option<Self> {
+        let spki = SubjectPublicKeyInfoOwned::from_der(der).ok()?;
+        if spki.algorithm.oid != EC_PUBLIC_KEY {
+            return None;
+        }
+        let params = spki.algorithm.parameters?.to_der().ok()?;
+        let curve = Curve::ALL.into_iter().find(|curve| curve.der() == params)?;
+        Self::from_ec_point(curve, spki.subject_public_key.as_bytes()?)
+    }
+
+    /// An RSA (`n`, `e`) or EC (`crv`, `x`, `y`) JWK; other members, `kid` included, are ignored.
+    pub fn from_jwk(jwk: &Value) -> Result<Self, ParseError> {
+        let invalid = |e: String| ParseError::InvalidPublicKey(format!("JWK {}", e));
+        let member = |name: &str| {
+            let value = jwk[name].as_str().ok_or_else(|| invalid(format!("has no '{}'", name)))?;
+            Base64UrlUnpadded::decode_vec(value).map_err(|_| invalid(format!("'{}' is not base64url", name)))
+        };
+        match jwk["kty"].as_str() {
+            Some("RSA") => Ok(Self::from_rsa_components(&member("n")?, &member("e")?)),
+            Some("EC") => {
+                let crv = jwk["crv"].as_str().unwrap_or_default();
+                let curve = Curve::ALL
+                    .into_iter()
+                    .find(|curve| curve.name() == crv)
+                    .ok_or_else(|| invalid(format!("curve '{}' is not supported", crv)))?;
+                let (x, y, len) = (member("x")?, member("y")?, curve.field_len());
+                if x.len() != len || y.len() != len {
+                    return Err(invalid(format!("x/y are not {}-byte coordinates for {}", len, crv)));
+                }
+                let point = [vec![0x04], x, y].concat();
+                Self::from_ec_point(curve, &point).ok_or_else(|| invalid(format!("point is not on {}", crv)))
+            }
+            kty => Err(ParseError::UnsupportedKeyFormat(format!("JWK key type {}", kty.unwrap_or("(none)")))),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseError> {
+        match core::str::from_utf8(bytes) {
+            Ok(pem) if pem.contains("-----BEGIN") => Self::from_pem(pem),
+            _ => Self::from_der(bytes),
+        }
+    }
+
+    /// Reconstructs the attested key from the modulus and exponent, or the curve and point, of a
+    /// key section.
+    pub fn from_section(section: &KeyAttributes) -> Option<Self> {
+        if let (Some(modulus), Some(exponent)) =
+            (section.get(TLVMapping::OBJ_ATTR_MODULUS), section.get(TLVMapping::OBJ_ATTR_PUBLIC_EXPONENT))
+        {
+            return Some(Self::from_rsa_components(&modulus.raw, &exponent.raw));
+        }
+        let curve = section.value(TLVMapping::OBJ_ATTR_EC_PARAMS)?;
+        let point = section.value(TLVMapping::OBJ_ATTR_EC_POINT)?;
+        match (curve, point) {
+            (TLVValue::Curve(curve), TLVValue::EcPoint(point)) => {
+                Self::from_ec_point(*curve, &point.to_uncompressed())
+            }
+            _ => None,
+        }
+    }
+
+    /// The attested key, from the public-key section if the bundle has one.
+    pub fn from_attestation(attestation: &Attestation) -> Result<Self, ParseError> {
+        let (public, other): (Vec<_>, Vec<_>) = attestation.sections.iter().partition(|s| is_public_section(s));
+        public.into_iter().chain(other).find_map(Self::from_section).ok_or(ParseError::NoPublicKey)
+    }
+
+    pub fn to_spki_der(&self) -> Result<Vec<u8>, ParseError> {
+        match self {
+            PublicKey::Rsa { modulus, exponent } => {
+                let key = RsaPublicKey::new(BigUint::from_bytes_be(modulus), BigUint::from_bytes_be(exponent))
+                    .map_err(|e| ParseError::InvalidPublicKey(e.to_string()))?;
+                key.to_public_key_der()
+                    .map(|der| der.as_bytes().to_vec())
+                    .map_err(|e| ParseError::InvalidPublicKey(e.to_string()))
+            }
+            PublicKey::Ec { curve, point } => {
+                let invalid = |e: der::Error| ParseError::InvalidPublicKey(e.to_string());
+                let spki = SubjectPublicKeyInfoOwned {
+                    algorithm: AlgorithmIdentifierOwned {
+                        oid: EC_PUBLIC_KEY,
+                        parameters: Some(Any::from_der(curve.der()).map_err(invalid)?),
+                    },
+                    subject_public_key: BitString::from_bytes(&point.to_uncompressed()).map_err(invalid)?,
+                };
+                spki.to_der().map_err(invalid)
+            }
+        }
+    }
+
+    pub fn to_pem(&self) -> Result<String, ParseError> {
+        pem::encode_string("PUBLIC KEY", LineEnding::LF, &self.to_spki_der()?)
+            .map_err(|e| ParseError::InvalidPublicKey(e.to_string()))
+    }
+
+    // the members RFC 7638 hashes, in lexicographic order; none of the values need escaping
+    fn jwk_members(&self) -> Vec<(&'static str, String)> {
+        match self {
+            PublicKey::Rsa { modulus, exponent } => {
+                vec![("e", base64url(exponent)), ("kty", "RSA".into()), ("n", base64url(modulus))]
+            }
+            PublicKey::Ec { curve, point } => vec![
+                ("crv", curve.name().into()),
+                ("kty", "EC".into()),
+                ("x", base64url(&point.x)),
+                ("y", base64url(&point.y)),
+            ],
+        }
+    }
+
+    pub fn jwk_thumbprint(&self) -> String {
+        let members = self.jwk_members().into_iter().map(|(name, value)| format!("\"{}\":\"{}\"", name, value));
+        let json = format!("{{{}}}", members.collect::<Vec<_>>().join(","));
+        base64url(&Sha256::digest(json))
+    }
+
+    /// The key as a JWK, with its thumbprint as the `kid`.
+    pub fn to_jwk(&self) -> Value {
+        let mut jwk = Map::new();
+        for (name, value) in self.jwk_members() {
+            jwk.insert(name.into(), Value::String(value));
+        }
+        jwk.insert("kid".into(), Value::String(self.jwk_thumbprint()));
+        Value::Object(jwk)
+    }
+
+    /// A line for `authorized_keys`. OpenSSH has no key type for secp256k1.
+    pub fn to_openssh(&self, comment: Option<&str>) -> Result<String, ParseError> {
+        let mut blob = Vec::new();
+        let key_type = match self {
+            PublicKey::Rsa { modulus, exponent } => {
+                ssh_string(&mut blob, b"ssh-rsa");
+                ssh_mpint(&mut blob, exponent);
+                ssh_mpint(&mut blob, modulus);
+                "ssh-rsa"
+            }
+            PublicKey::Ec { curve, point } => {
+                let (key_type, identifier) = match curve {
+                    Curve::P256 => ("ecdsa-sha2-nistp256", "nistp256"),
+                    Curve::P384 => ("ecdsa-sha2-nistp384", "nistp384"),
+                    Curve::P521 => ("ecdsa-sha2-nistp521", "nistp521"),
+                    Curve::Secp256k1 => {
+                        return Err(ParseError::UnsupportedKeyFormat(format!("no OpenSSH key type for {}", curve.name())))
+                    }
+                };
+                ssh_string(&mut blob, key_type.as_bytes());
+                ssh_string(&mut blob, identifier.as_bytes());
+                ssh_string(&mut blob, &point.to_uncompressed());
+                key_type
+            }
+        };
+        let line = format!("{} {}", key_type, Base64::encode_string(&blob));
+        Ok(match comment {
+            Some(comment) => format!("{} {}", line, comment),
+            None => line,
+        })
+    }
+
+    /// The key in `format`; PEM and DER are SubjectPublicKeyInfo, JWK is compact JSON.
+    pub fn export(&self, format: KeyFormat) -> Result<Vec<u8>, ParseError> {
+        match format {
+            KeyFormat::Pem => self.to_pem().map(String::into_bytes),
+            KeyFormat::Der => self.to_spki_der(),
+            KeyFormat::Jwk => Ok(self.to_jwk().to_string().into_bytes()),
+            KeyFormat::OpenSsh => self.to_openssh(None).map(String::into_bytes),
+        }
+    }
+
+    pub fn kcv(&self) -> Result<[u8; KCV_LEN], ParseError> {
+        let digest = Sha1::digest(self.to_spki_der()?);
+        let mut kcv = [0u8; KCV_LEN];
+        kcv.copy_from_slice(&digest[..KCV_LEN]);
+        Ok(kcv)
+    }
+
+    pub fn ekcv(&self) -> Result<[u8; 32], ParseError> {
+        Ok(Sha256::digest(self.to_spki_der()?).into())
+    }
+}
+
+impl From<&RsaPublicKey> for PublicKey {
+    fn from(key: &RsaPublicKey) -> Self {
+        Self::from_rsa_components(&key.n().to_bytes_be(), &key.e().to_bytes_be())
+    }
+}
+
+fn strip_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+fn base64url(bytes: &[u8]) -> String {
+    Base64UrlUnpadded::encode_string(bytes)
+}
+
+// RFC 4251 string and mpint: a four byte length, then the bytes; an mpint with the top bit set gets
+// a zero byte in front so it doesn't read as negative
+fn ssh_string(blob: &mut Vec<u8>, bytes: &[u8]) {
+    blob.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    blob.extend_from_slice(bytes);
+}
+
+fn ssh_mpint(blob: &mut Vec<u8>, bytes: &[u8]) {
+    let bytes = strip_zeros(bytes);
+    match bytes.first() {
+        Some(first) if first & 0x80 != 0 => ssh_string(blob, &[&[0], bytes].concat()),
+        _ => ssh_string(blob, bytes),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Match,
+    Mismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for CheckOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckOutcome::Match => write!(f, "match"),
+            CheckOutcome::Mismatch { expected, actual } => {
+                write!(f, "mismatch (expected {}, attested {})", expected, actual)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyCheck {
+    pub mode: KeyMode,
+    pub mapping: TLVMapping,
+    pub outcome: CheckOutcome,
+}
+
+impl KeyCheck {
+    pub fn is_match(&self) -> bool {
+        self.outcome == CheckOutcome::Match
+    }
+
+    /// The attribute, named in `style`, its section and the outcome; `Display` uses the Cavium names.
+    pub fn to_line(&self, style: NameStyle) -> String {
+        format!("{} ({:?}) = {}", self.mapping.label(style), self.mode, self.outcome)
+    }
+}
+
+impl fmt::Display for KeyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_line(NameStyle::Cavium))
+    }
+}
+
+// y^2 = x^3 + ax + b over the prime field of the curve, with the parameters from SEC 2. a is -3 for
+// the NIST curves, taken as p - 3 to stay unsigned, and 0 for secp256k1.
+fn is_on_curve(curve: Curve, point: &EcPoint) -> bool {
+    let (p, b) = match curve {
+        Curve::P256 => (
+            "ffffffff00000001000000000000000000000000ffffffffffffffffffffffff",
+            "5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b",
+        ),
+        Curve::P384 => (
+            "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffeffffffff0000000000000000ffffffff",
+            "b3312fa7e23ee7e4988e056be3f82d19181d9c6efe8141120314088f5013875ac656398d8a2ed19d2a85c8edd3ec2aef",
+        ),
+        Curve::P521 => (
+            "01ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff\
+             ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            "0051953eb9618e1c9a1f929a21a0b68540eea2da725b99b315f3b8b489918ef109\
+             e156193951ec7e937b1652c0bd3bb1bf073573df883d2c34f1ef451fd46b503f00",
+        ),
+        Curve::Secp256k1 => ("fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f", "07"),
+    };
+    let field = |hex: &str| BigUint::parse_bytes(hex.as_bytes(), 16).unwrap_or_default();
+    let (p, b) = (field(p), field(b));
+    let a = match curve {
+        Curve::Secp256k1 => BigUint::default(),
+        _ => &p - 3u32,
+    };
+
+    let (x, y) = (BigUint::from_bytes_be(&point.x), BigUint::from_bytes_be(&point.y));
+    if x >= p || y >= p {
+        return false;
+    }
+    (&y * &y) % &p == (&x * &x * &x + a * &x + b) % &p
+}
+
+// check values are either stored as raw digest bytes or as their hex encoding, in full: a truncated
+// value would match any key sharing its first bytes
+fn digest_matches(attested: &[u8], digest: &[u8]) -> bool {
+    attested == digest || attested.eq_ignore_ascii_case(to_hex(digest).as_bytes())
+}
+
+fn check(mode: KeyMode, mapping: TLVMapping, matched: bool, expected: String, actual: String) -> KeyCheck {
+    let outcome = match matched {
+        true => CheckOutcome::Match,
+        false => CheckOutcome::Mismatch { expected, actual },
+    };
+    KeyCheck { mode, mapping, outcome }
+}
+
+fn is_public_section(section: &KeyAttributes) -> bool {
+    match section.value(TLVMapping::OBJ_ATTR_CLASS) {
+        Some(TLVValue::ClassKey(class)) => *class == ClassKey::Pubkey,
+        _ => section.mode == KeyMode::Primary,
+    }
+}
+
+/// Compares every check value and public component present in `attestation` against `key`.
+pub fn compare(attestation: &Attestation, key: &PublicKey) -> Result<Vec<KeyCheck>, ParseError> {
+    let kcv = key.kcv()?;
+    let ekcv = key.ekcv()?;
+
+    let mut checks = Vec::new();
+    for section in &attestation.sections {
+        let mode = section.mode;
+        if let Some(attr) = section.get(TLVMapping::OBJ_ATTR_KCV) {
+            let matched = digest_matches(&attr.raw, &kcv);
+            checks.push(check(mode, attr.mapping, matched, to_hex(&kcv), attr.value.to_str()));
+        }
+        if let Some(attr) = section.get(TLVMapping::OBJ_ATTR_EKCV) {
+            let matched = digest_matches(&attr.raw, &ekcv);
+            checks.push(check(mode, attr.mapping, matched, to_hex(&ekcv), attr.value.to_str()));
+        }
+
+        if !is_public_section(section) {
+            continue;
+        }
+        match key {
+            PublicKey::Rsa { modulus, exponent } => {
+                if let Some(attr) = section.get(TLVMapping::OBJ_ATTR_MODULUS) {
+                    let attested = strip_zeros(&attr.raw);
+                    let matched = attested == &modulus[..];
+                    checks.push(check(mode, attr.mapping, matched, to_hex(modulus), to_hex(attested)));
+                }
+                if let Some(attr) = section.get(TLVMapping::OBJ_ATTR_PUBLIC_EXPONENT) {
+                    let attested = strip_zeros(&attr.raw);
+                    let matched = attested == &exponent[..];
+                    checks.push(check(mode, attr.mapping, matched, to_hex(exponent), to_hex(attested)));
+                }
+            }
+            PublicKey::Ec { curve, point } => {
+                if let Some(attr) = section.get(TLVMapping::OBJ_ATTR_EC_PARAMS) {
+                    let matched = attr.value == TLVValue::Curve(*curve);
+                    checks.push(check(mode, attr.mapping, matched, curve.to_str(), attr.value.to_str()));
+                }
+                if let Some(attr) = section.get(TLVMapping::OBJ_ATTR_EC_POINT) {
+                    // wrapped or bare, it's the coordinates that have to match
+                    let attested = match &attr.value {
+                        TLVValue::EcPoint(attested) => attested.to_uncompressed(),
+                        _ => attr.raw.clone(),
+                    };
+                    let expected = point.to_uncompressed();
+                    let matched = attested == expected;
+                    checks.push(check(mode, attr.mapping, matched, to_hex(&expected), to_hex(&attested)));
+                }
+            }
+        }
+    }
+    Ok(checks)
+}
@@ -1,12 +1,16 @@
 use crate::{Machine, State, Mode, KeyMode};
-use crate::function::{FuncState, Func};
-use crate::tlv_mapping::{Bytes, EncodeTLV, TLVMapping};
+use crate::attestation::Attribute;
+use crate::error::ParseError;
+use crate::tlv_mapping::TLVMapping;
+use alloc::vec::Vec;
 use num_enum::FromPrimitive;
 
 
 /* Here is where the state machine logic is defined.
  * Notes
- * - Shifting between states via map_func returns will reset the counters.
+ * - The handlers are laid out at compile time by `transitions!` into a static table, so there's
+ *    nothing to register and a `Machine` costs next to nothing to create or `reset()`.
+ * - Shifting between states via handler returns will reset the counters.
  * - All integer values in the TLV are in big-endian, therefore we must bit-shift these as they
  *    appear in network byte order.
  * - In the case where we don't have a full byte buffer payload, we attempt to store each byte on
@@ -24,9 +28,12 @@ use num_enum::FromPrimitive;
  *    processing the attestation data. You can prove this by reading in a single byte at a time, at
  *    an interval of your choosing.
  * 4. It doesn't use any unsafe functions, and doesn't require bounds checking for allocations as
- *    we're simply using a byte-by-byte method as they arrive from input.
- * 5. It's extremely lightweight, and reserves allocations only for the machine setup and
- *    serializing back to callers.
+ *    we're simply using a byte-by-byte method as they arrive from input. Lengths and offsets read
+ *    off the wire are checked before they're used, and the fuzz targets in fuzz/ keep hostile
+ *    input from panicking (`panic = "abort"` would take the wasm page down with it).
+ * 5. It's extremely lightweight, and reserves allocations only for serializing back to callers.
+ *    The state and handler tables are static, so one machine can be reset and reused across
+ *    thousands of bundles.
  * 6. The `Type ID`'s in the TLV payload are exactly to specification and are in their human
  *    readable form so no further lookups required.
  * 7. All TLV 'values' are parsed into their correct human representation (e.g., bytes, ints,
@@ -39,189 +46,295 @@ use num_enum::FromPrimitive;
  *     concerned with 'endianess', and therefore is a much safer implementation, and much more
  *     performant via bitwise operands.
  * */
-pub fn register_functions(machine: &mut Machine) {
-    machine.map_func(State::SKIP8.to(State::SKIP8), Func::Fun(|m| {
-        if m.inc_count() == 1 << 3 {
-            m.reset_count();
-            return Some(m.next_state());
-        }
-        None
-    }));
-
-    machine.map_func(State::BUFSIZE4.to(State::BUFSIZE4), Func::Fun(|m| {
-        m.be_int = m.be_int << 8 | m.current_byte() as u32;
-
-        if m.inc_count() == 1 << 2 {
-            m.set_buf_size(m.be_int);
-            return Some(m.next_state());
-        }
-        None
-    }));
-
-    machine.map_func(State::TOTALSIZE4.to(State::TOTALSIZE4), Func::Fun(|m| {
-        m.be_int = m.be_int << 8 | m.current_byte() as u32;
-
-        if m.inc_count() == 1 << 2 {
-            m.set_total_size(m.be_int);
-            return Some(m.next_state())
-        }
-        None
-    }));
-
-    machine.map_func(State::SkipToOffset.to(State::SkipToOffset), Func::Fun(|m| {
-        // set the attribute offset if it isn't already
-        let mask = (!((m.attr_offset == 0) as i32) + 1) as u32;
-        m.attr_offset = (mask & (m.total_size - (m.buff_size + m.signature_len as u32))) | (!mask & m.attr_offset);
-
-        // now check if the current index is at our offset
-        if m.attr_offset as usize == m.get_index() {
-            // move to the next state, skipping an additional three bytes
-            return Some(m.next_state());
-        }
-        None
-    }));
-
-    machine.map_func(State::SkipU16_2.to(State::SkipU16_2), Func::Fun(|m| {
-        if m.inc_count() == 3 {
-            return Some(m.next_state());
-        }
-        None
-    }));
-
-    machine.map_func(State::OffsetPubkey16.to(State::OffsetPubkey16), Func::Fun(|m| {
-        m.firstkey_offset = m.firstkey_offset << 8 | m.current_byte() as u32;
-        let mask = (!((m.inc_count() == 2) as i32) + 1) as u32;
-        let st = (mask & m.next_state() as u32) | (!mask & m.state() as u32);
-        Some(State::from_primitive(st as _))
-    }));
-
-    // this operation will also check on the last iteration whether secondkey_offset is greater
-    // than 0.
-    // If it is, then we have private key attestation to handle for assymetric keys
-    // If its not, then we have a symmetric key to parse, moving directly to signature at the end.
-    machine.map_func(State::OffsetPrivkey16.to(State::OffsetPrivkey16), Func::Fun(|m| {
-        m.secondkey_offset = m.secondkey_offset << 8 | m.current_byte() as u32;
-        let mask = (!((m.inc_count() == 2) as i32) + 1) as u32;
-        if mask == 0xFFFFFFFF {
-            if m.secondkey_offset == 0 {
-                m.set_mode(Mode::Symmetric);
-                m.push_state(State::Signature);
-            } else {
-                m.push_state(State::Signature);
-                m.push_state(State::SecondaryKey);
-            };
-        }
-        let st = (mask & m.next_state() as u32) | (!mask & m.state() as u32);
-        Some(State::from_primitive(st as _))
-    }));
-
-    machine.map_func(State::Skip4.to(State::Skip4), Func::Fun(|m| {
-        let mask = (!((m.inc_count() == 1 << 2) as i32) + 1) as u32;
-
-        Some(State::from_primitive(
-                ( (mask & m.next_state() as u32) | (!mask & m.state() as u32) ) as u8
-        ))
-    }));
-
-    machine.map_func(vec![State::AttrLen, State::SecondaryKey].to(State::AttrLen), Func::Fun(|m| {
-        // take four bytes here, and assign to our attr count.
-        // then skip another four and start parsing.
-        m.attr_count = m.attr_count << 8 | m.current_byte() as u32;
-
-        // check if counter is four bytes.
-        //let mask = (!((m.inc_count() == 4) as i32) + 1) as u32;
-        let mask = (((m.inc_count() as i32 ^ 4) - 1) >> 31) as u32;
-
-        let st = (mask & m.next_state() as u32) | (!mask & m.state() as u32);
-        Some(State::from_primitive(st as _))
-    }));
-
-    machine.map_func(State::SkipAttr4.to(State::SkipAttr4), Func::Fun(|m| {
-        let mask = (((m.inc_count() as i32 ^ 4) - 1) >> 31) as u32;
-
-        if mask == 0xFFFFFFFF {
-            (0..m.attr_count - 1).into_iter().for_each(|_| m.push_state(State::TLVType));
-            println!("------------KEYMODE: {:?} -------------", m.get_keymode());
-        };
-
-        Some(State::from_primitive(
-                ( (mask & m.next_state() as u32) | (!mask & m.state() as u32) ) as u8
-        ))
-    }));
-
-    machine.map_func(State::TLVType.to(State::TLVType), Func::Fun(|m| {
-        let mask = (((m.inc_count() as i32 ^ 4) - 1) >> 31) as u32;
-        m.tlv_type = m.tlv_type << 8 | m.current_byte() as u32;
-        Some(State::from_primitive(
-                ( (mask & m.next_state() as u32) | (!mask & m.state() as u32) ) as u8
-        ))
-    }));
-
-    machine.map_func(State::TLVLen.to(State::TLVLen), Func::Fun(|m| {
-        let mask = (((m.inc_count() as i32 ^ 4) - 1) >> 31) as u32;
-        m.tlv_len = m.tlv_len << 8 | m.current_byte() as u32;
-
-        Some(State::from_primitive(
-                ( (mask & m.next_state() as u32) | (!mask & m.state() as u32) ) as u8
-        ))
-    }));
-
-    machine.map_func(State::TLVValue.to(State::TLVValue), Func::Fun(|m| {
-
-        let mask = (((m.inc_count() as i32 ^ m.tlv_len as i32) - 1) >> 31) as u32;
-        let current_byte = m.current_byte();
-        m.stack_mut().push(current_byte);
-
-        if mask == 0xFFFFFFFF {
-            let byte_vals = m.stack_mut().drain(..).collect::<Vec<_>>();
-            let tlv = TLVMapping::from_int(m.tlv_type);
-            let encoded = tlv.encode(&byte_vals[..], m.tlv_len).to_str();
-            println!("Type = {:04x}  {} Len = {},  {:?}", // Value = {:?}",
-                m.tlv_type,
-                tlv,
-                m.tlv_len,
-                encoded,
-            );
-            m.write_tlv(format!("{}", tlv), encoded, m.get_keymode());
-
-            m.attrs_processed += 1;
-            m.tlv_type = 0;
-            m.tlv_len = 0;
-            let retval = m.pop_state();
-            retval
-        } else {
+transitions! {
+    pub static TRANSITIONS = {
+        // response code and request id, see `AttestationHeader`
+        SKIP8 => SKIP8: |m| {
+            m.be_int = m.be_int << 8 | m.current_byte() as u32;
+            match m.inc_count() {
+                4 => {
+                    m.response_code = m.be_int;
+                    m.be_int = 0;
+                }
+                8 => {
+                    m.request_id = m.be_int;
+                    m.reset_count();
+                    return Some(m.next_state());
+                }
+                _ => {}
+            }
+            None
+        },
+
+        BUFSIZE4 => BUFSIZE4: |m| {
+            m.be_int = m.be_int << 8 | m.current_byte() as u32;
+
+            if m.inc_count() == 1 << 2 {
+                m.set_buf_size(m.be_int);
+                return match m.locate_attributes() {
+                    Ok(_) => Some(m.next_state()),
+                    Err(e) => m.fail(e),
+                };
+            }
             None
-        }
-    }));
-
-    machine.map_func(State::SecondaryKey.to(State::SecondaryKey), Func::Fun(|m| {
-        // recall there's a 32byte header where we 'start' counting offset.
-        // 16 bytes for the signature delta,
-        // skip 3, then go to attr len
-        let mask = (((m.inc_count() as i32 ^ 4) - 1) >> 31) as u32;
-        if mask == 0xFFFFFFFF {
-            m.attr_count = 0;
-            m.set_keymode(KeyMode::Secondary);
-            Some(State::AttrLen)
-        } else {
+        },
+
+        TOTALSIZE4 => TOTALSIZE4: |m| {
+            m.be_int = m.be_int << 8 | m.current_byte() as u32;
+
+            if m.inc_count() == 1 << 2 {
+                m.set_total_size(m.be_int);
+                return Some(m.next_state())
+            }
+            None
+        },
+
+        SkipToOffset => SkipToOffset: |m| {
+            // now check if the current index is at our offset
+            if m.attr_offset as usize == m.get_index() {
+                // move to the next state, reading the three remaining bytes of the attribute format
+                m.attribute_format = m.current_byte() as u32;
+                return Some(m.next_state());
+            }
+            let current_byte = m.current_byte();
+            m.push_payload(current_byte);
+            None
+        },
+
+        SkipU16_2 => SkipU16_2: |m| {
+            m.attribute_format = m.attribute_format << 8 | m.current_byte() as u32;
+            if m.inc_count() == 3 {
+                return Some(m.next_state());
+            }
             None
-        }
-    }));
+        },
 
-    machine.map_func(State::Signature.to(State::Signature), Func::Fun(|m| {
-        let current_byte = m.current_byte();
-        m.stack_mut().push(current_byte);
-        let mask = (((m.inc_count() as i32 ^ m.signature_len as i32) -1) >> 31) as u32;
+        OffsetPubkey16 => OffsetPubkey16: |m| {
+            m.firstkey_offset = m.firstkey_offset << 8 | m.current_byte() as u32;
+            let mask = (!((m.inc_count() == 2) as i32) + 1) as u32;
+            let st = (mask & m.next_state() as u32) | (!mask & m.state() as u32);
+            Some(State::from_primitive(st as _))
+        },
 
-        if mask == 0xFFFFFFFF {
-            let byte_stack = {
-                m.stack_mut().drain(..).collect::<Vec<_>>()
+        // this operation will also check on the last iteration whether secondkey_offset is greater
+        // than 0.
+        // If it is, then we have private key attestation to handle for assymetric keys
+        // If its not, then we have a symmetric key to parse, moving directly to signature at the end.
+        OffsetPrivkey16 => OffsetPrivkey16: |m| {
+            m.secondkey_offset = m.secondkey_offset << 8 | m.current_byte() as u32;
+            let mask = (!((m.inc_count() == 2) as i32) + 1) as u32;
+            if mask == 0xFFFFFFFF {
+                if m.secondkey_offset == 0 {
+                    m.set_mode(Mode::Symmetric);
+                    m.push_state(State::Signature);
+                } else {
+                    m.push_state(State::Signature);
+                    m.push_state(State::SecondaryKey);
+                };
+                m.emit_header();
+
+                if let Err(e) = m.check_sections() {
+                    return m.fail(e);
+                }
+                // anything between the offsets and the first section is padding
+                let (start, _) = m.section_bounds(KeyMode::Primary);
+                if start > m.get_index() + 1 {
+                    return Some(m.skip_to(start, State::Skip4));
+                }
+            }
+            let st = (mask & m.next_state() as u32) | (!mask & m.state() as u32);
+            Some(State::from_primitive(st as _))
+        },
+
+        // object handle of the first key
+        Skip4 => Skip4: |m| {
+            m.be_int = m.be_int << 8 | m.current_byte() as u32;
+            let mask = (!((m.inc_count() == 1 << 2) as i32) + 1) as u32;
+            if mask == 0xFFFFFFFF {
+                m.object_handle = m.be_int;
+            }
+
+            Some(State::from_primitive(
+                    ( (mask & m.next_state() as u32) | (!mask & m.state() as u32) ) as u8
+            ))
+        },
+
+        AttrLen | SecondaryKey => AttrLen: |m| {
+            // take four bytes here, and assign to our attr count.
+            // then skip another four and start parsing.
+            m.attr_count = m.attr_count << 8 | m.current_byte() as u32;
+
+            // check if counter is four bytes.
+            //let mask = (!((m.inc_count() == 4) as i32) + 1) as u32;
+            let mask = (((m.inc_count() as i32 ^ 4) - 1) >> 31) as u32;
+
+            let st = (mask & m.next_state() as u32) | (!mask & m.state() as u32);
+            Some(State::from_primitive(st as _))
+        },
+
+        SkipAttr4 => SkipAttr4: |m| {
+            m.be_int = m.be_int << 8 | m.current_byte() as u32;
+            let mask = (((m.inc_count() as i32 ^ 4) - 1) >> 31) as u32;
+
+            if mask == 0xFFFFFFFF {
+                m.attr_size = m.be_int;
+                // the declared attributes have to fit before the next section (or the signature)
+                let (_, end) = m.section_bounds(m.get_keymode());
+                let declared_end = m.attributes_end() as u64;
+                if declared_end > end as u64 {
+                    return m.fail(match (m.get_keymode(), m.get_mode()) {
+                        (KeyMode::Primary, Mode::Asymetric) => ParseError::SectionOverlap {
+                            mode: KeyMode::Secondary,
+                            offset: end as u64,
+                            previous_end: declared_end,
+                        },
+                        (mode, _) => ParseError::SectionOutOfRange { mode, offset: declared_end, limit: end as u64 },
+                    });
+                }
+                // every attribute takes at least 8 bytes of type and length
+                if m.attr_count == 0 || m.attr_count as u64 * 8 > m.attr_size as u64 {
+                    return m.fail(ParseError::AttributeCountMismatch { declared: m.attr_count, parsed: 0 });
+                }
+                m.attrs_processed = 0;
+                m.start_section();
             };
-            let byte_stack = byte_stack.as_ref();
-            let signature = Bytes::encode(byte_stack, byte_stack.len() as _)?.to_str();
-            m.write_tlv("Signature".into(), signature, m.get_keymode());
-            println!("Attestation Signature \n{}", Bytes::encode(byte_stack, byte_stack.len() as _)?.to_str());
-        }
-        None
-    }));
+
+            Some(State::from_primitive(
+                    ( (mask & m.next_state() as u32) | (!mask & m.state() as u32) ) as u8
+            ))
+        },
+
+        TLVType => TLVType: |m| {
+            // the section's attributes ran out before its attribute count did
+            if m.current_count() == 0 && m.get_index() >= m.attributes_end() {
+                return m.fail(ParseError::AttributeCountMismatch {
+                    declared: m.attr_count,
+                    parsed: m.attrs_processed,
+                });
+            }
+            let mask = (((m.inc_count() as i32 ^ 4) - 1) >> 31) as u32;
+            m.tlv_type = m.tlv_type << 8 | m.current_byte() as u32;
+            Some(State::from_primitive(
+                    ( (mask & m.next_state() as u32) | (!mask & m.state() as u32) ) as u8
+            ))
+        },
+
+        TLVLen => TLVLen: |m| {
+            let mask = (((m.inc_count() as i32 ^ 4) - 1) >> 31) as u32;
+            m.tlv_len = m.tlv_len << 8 | m.current_byte() as u32;
+
+            if mask == 0xFFFFFFFF {
+                let end = (m.get_index() + 1) as u64 + m.tlv_len as u64;
+                if end > m.attributes_end() as u64 {
+                    return m.fail(ParseError::OffsetOutOfRange { offset: end, limit: m.attributes_end() as u64 });
+                }
+                // an empty value has no bytes to wait for
+                if m.tlv_len == 0 {
+                    return push_attribute(m, Vec::new());
+                }
+            }
+
+            Some(State::from_primitive(
+                    ( (mask & m.next_state() as u32) | (!mask & m.state() as u32) ) as u8
+            ))
+        },
+
+        TLVValue => TLVValue: |m| {
+
+            // compared rather than xor'd, lengths past i32::MAX would overflow the subtraction
+            let mask = (!((m.inc_count() == m.tlv_len) as i32) + 1) as u32;
+            let current_byte = m.current_byte();
+            m.stack_mut().push(current_byte);
+
+            if mask == 0xFFFFFFFF {
+                let byte_vals = m.stack_mut().drain(..).collect::<Vec<_>>();
+                push_attribute(m, byte_vals)
+            } else {
+                None
+            }
+        },
+
+        SecondaryKey => SecondaryKey: |m| {
+            // object handle of the second key, we're at `secondkey_offset` past any padding
+            m.be_int = m.be_int << 8 | m.current_byte() as u32;
+            let mask = (((m.inc_count() as i32 ^ 4) - 1) >> 31) as u32;
+            if mask == 0xFFFFFFFF {
+                m.object_handle = m.be_int;
+                m.attr_count = 0;
+                m.set_keymode(KeyMode::Secondary);
+                Some(State::AttrLen)
+            } else {
+                None
+            }
+        },
+
+        // padding between the sections, nothing in it is parsed
+        SkipToSection => SkipToSection: |m| {
+            if m.get_index() + 1 == m.skip_target() {
+                return m.pop_state();
+            }
+            None
+        },
+
+        Signature => Signature: |m| {
+            // the attributes have to account for the whole attribute buffer
+            if m.current_count() == 0 && m.get_index() != m.signature_offset() {
+                return m.fail(ParseError::AttributeCountMismatch {
+                    declared: m.attr_count,
+                    parsed: m.attrs_processed,
+                });
+            }
+
+            let current_byte = m.current_byte();
+            m.stack_mut().push(current_byte);
+            let mask = (!((m.inc_count() as usize == m.signature_len) as i32) + 1) as u32;
+
+            if mask == 0xFFFFFFFF {
+                let signature = m.stack_mut().drain(..).collect::<Vec<_>>();
+                m.set_signature(signature);
+                return Some(m.next_state());
+            }
+            None
+        },
+
+        Done => Done: |m| {
+            m.fail(ParseError::TrailingData { offset: m.get_index() })
+        },
+    }
+}
+
+fn push_attribute(m: &mut Machine, byte_vals: Vec<u8>) -> Option<State> {
+    let tlv = TLVMapping::from_int(m.tlv_type);
+    m.push_attribute(Attribute {
+        type_id: m.tlv_type,
+        mapping: tlv,
+        value: tlv.encode(&byte_vals[..], m.tlv_len),
+        raw: byte_vals,
+    });
+
+    m.attrs_processed += 1;
+    m.tlv_type = 0;
+    m.tlv_len = 0;
+    if m.attrs_processed == m.attr_count {
+        return end_section(m);
+    }
+    Some(State::TLVType)
+}
+
+// the last attribute has to end where the section header said it would, which is where the
+// parser checks its position against the recorded offsets
+fn end_section(m: &mut Machine) -> Option<State> {
+    let position = m.get_index() + 1;
+    if position != m.attributes_end() {
+        return m.fail(ParseError::AttributeCountMismatch {
+            declared: m.attr_count,
+            parsed: m.attrs_processed,
+        });
+    }
+
+    let (_, end) = m.section_bounds(m.get_keymode());
+    match m.pop_state() {
+        Some(next) if position < end => Some(m.skip_to(end, next)),
+        next => next,
+    }
 }
@@ -1,93 +1,129 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Formatter;
+use core::fmt::Write;
 use paste::paste;
-use std::fmt::Formatter;
-use std::fmt::Write;
 
 tlv_mapping! {
     pub enum TLVMapping {
         //// 2 => public key in a public-private key pair
         //// 3 => private key in a public-private key pair
         //// 4 => Secret (symmetric) key
-        // Class type of the key.
-        0x0000 => OBJ_ATTR_CLASS = ClassKey,
-        // Identifies the key as a token key.
-        0x0001 => OBJ_ATTR_TOKEN = Bytes,
-        // Indicates if this is a shared key or a private key (for symmetric or asymmetric keys).
-        0x0002 => OBJ_ATTR_PRIVATE = Bool,
-        // Key description.
-        0x0003 => OBJ_ATTR_LABEL = ByteStr,
-        // The key can be trusted for the application that it was created.
-        0x0086 => OBJ_ATTR_TRUSTED = Bool,
-        // Subclass type of the key.
-        0x0100 => OBJ_ATTR_KEY_TYPE = KeyType,
-        // Key identifier.
-        0x0102 => OBJ_ATTR_ID = HexStr128,
-        // Always true for keys generated on HSM.
-        0x0103 => OBJ_ATTR_SENSITIVE = Bool,
-        // Indicates if key can be used to encrypt data for operations like RSA_Encrypt. Not applicable to EC keys.
-        0x0104 => OBJ_ATTR_ENCRYPT = Bool,
-        // Indicates if key can be used to decrypt data for operations like RSA_Decrypt. Not applicable to EC keys.
-        0x0105 => OBJ_ATTR_DECRYPT = Bool,
-        // Indicates if key can be used to wrap other keys.
-        0x0106 => OBJ_ATTR_WRAP	= Bool,
-        // Indicates if key can be used to unwrap other keys.
-        0x0107 => OBJ_ATTR_UNWRAP = Bool,
-        // Indicates if key can be used for signing operations.
-        0x0108 => OBJ_ATTR_SIGN	= Bool,
-        // Indicates if key can be used for verifying operations.
-        0x010A => OBJ_ATTR_VERIFY = Bool,
-        // Indicates if key supports key derivation (i.e. if other keys can be derived from this one).
-        0x010C => OBJ_ATTR_DERIVE = Bool,
-        // RSA key modulus value.
-        0x0120 => OBJ_ATTR_MODULUS = Bytes,
-        // RSA key size in bits.
-        0x0121 => OBJ_ATTR_MODULUS_BITS	= Int,
-        // RSA key public exponent value.
-        0x0122 => OBJ_ATTR_PUBLIC_EXPONENT = Int,
-        // Length in bytes of any value.
-        0x0161 => OBJ_ATTR_VALUE_LEN = Int,
-        // Indicates if key can be extracted.
-        0x0162 => OBJ_ATTR_EXTRACTABLE = Bool,
-        // Indicates if key was generated locally
-        0x0163 => OBJ_ATTR_LOCAL = Bool,
-        // Indicates if key can never be extracted.
-        0x0164 => OBJ_ATTR_NEVER_EXTRACTABLE = Bool,
-        // Indicates if key has always had the OBJ_ATTR_SENSITIVE attribute set.
-        0x0165 => OBJ_ATTR_ALWAYS_SENSITIVE = Bool,
-        // Key Check Value.
-        0x0173 => OBJ_ATTR_KCV = Bytes,
-        // Extended Attribute #1
-        0x1000 => OBJ_EXT_ATTR1	= Bytes,
-        // Extended Key Check Value.
-        0x1003 => OBJ_ATTR_EKCV	= Bytes,
-        // Indicates if key can only be wrapped with a wrapping key that has OBJ_ATTR_TRUSTED set.
-        0x0210 => OBJ_ATTR_WRAP_WITH_TRUSTED = Bool,
-        // Indicates if key can be split into multiple parts.
-        0x80000002 => OBJ_ATTR_SPLITTABLE = Bool,
-        // Indicate if it is part of the key split.
-        0x80000003 => OBJ_ATTR_IS_SPLIT	= Bool,
-        // Indicate if key supports encryption.
-        0x80000174 => OBJ_ATTR_ENCRYPT_KEY_MECHANISMS = Bool,
-        // Indicate if key supports decryption.
-        0x80000175 => OBJ_ATTR_DECRYPT_KEY_MECHANISMS = Bool,
-        // Indicate if key supports signing.
-        0x80000176 => OBJ_ATTR_SIGN_KEY_MECHANISMS = Bool,
-        // Indicate if key supports signature verification.
-        0x80000177 => OBJ_ATTR_VERIFY_KEY_MECHANISMS = Bool,
-        // Indicate if key supports key wrapping.
-        0x80000178 => OBJ_ATTR_WRAP_KEY_MECHANISMS = Bool,
-        // Indicate if key supports key unwrapping.
-        0x80000179 => OBJ_ATTR_UNWAP_KEY_MECHANISMS = Bool,
-        // Indicate if key supports key derivation.
-        0x80000180 => OBJ_ATTR_DERIVE_KEY_MECHANISMS = Bool,
-
-        // Unknown
-        0x80000000 => OBJ_UNKNOWN = Bytes,
-
-        0xFFFFFF01 => SIGNATURE = ByteStr,
+        /// Class type of the key.
+        0x0000 => OBJ_ATTR_CLASS = ClassKey [Low] as CKA_CLASS,
+        /// Identifies the key as a token key.
+        0x0001 => OBJ_ATTR_TOKEN = Bytes [Low] as CKA_TOKEN,
+        /// Indicates if this is a shared key or a private key (for symmetric or asymmetric keys).
+        0x0002 => OBJ_ATTR_PRIVATE = Bool [Medium] as CKA_PRIVATE,
+        /// Key description.
+        0x0003 => OBJ_ATTR_LABEL = ByteStr [Info] as CKA_LABEL,
+        /// The key can be trusted for the application that it was created.
+        0x0086 => OBJ_ATTR_TRUSTED = Bool [Medium] as CKA_TRUSTED,
+        /// Subclass type of the key.
+        0x0100 => OBJ_ATTR_KEY_TYPE = KeyType [Low] as CKA_KEY_TYPE,
+        /// Key identifier.
+        0x0102 => OBJ_ATTR_ID = HexStr128 [Info] as CKA_ID,
+        /// Always true for keys generated on HSM.
+        0x0103 => OBJ_ATTR_SENSITIVE = Bool [High, weak if Bool(false)] as CKA_SENSITIVE,
+        /// Indicates if key can be used to encrypt data for operations like RSA_Encrypt. Not applicable to EC keys.
+        0x0104 => OBJ_ATTR_ENCRYPT = Bool [Low] as CKA_ENCRYPT,
+        /// Indicates if key can be used to decrypt data for operations like RSA_Decrypt. Not applicable to EC keys.
+        0x0105 => OBJ_ATTR_DECRYPT = Bool [Low] as CKA_DECRYPT,
+        /// Indicates if key can be used to wrap other keys.
+        0x0106 => OBJ_ATTR_WRAP	= Bool [Medium] as CKA_WRAP,
+        /// Indicates if key can be used to unwrap other keys.
+        0x0107 => OBJ_ATTR_UNWRAP = Bool [Medium] as CKA_UNWRAP,
+        /// Indicates if key can be used for signing operations.
+        0x0108 => OBJ_ATTR_SIGN	= Bool [Low] as CKA_SIGN,
+        /// Indicates if key can be used for verifying operations.
+        0x010A => OBJ_ATTR_VERIFY = Bool [Low] as CKA_VERIFY,
+        /// Indicates if key supports key derivation (i.e. if other keys can be derived from this one).
+        0x010C => OBJ_ATTR_DERIVE = Bool [Medium] as CKA_DERIVE,
+        /// RSA key modulus value.
+        0x0120 => OBJ_ATTR_MODULUS = Bytes [Info] as CKA_MODULUS,
+        /// RSA key size in bits.
+        0x0121 => OBJ_ATTR_MODULUS_BITS	= Int [Medium, weak if Int(0..=2047)] as CKA_MODULUS_BITS,
+        /// RSA key public exponent value.
+        0x0122 => OBJ_ATTR_PUBLIC_EXPONENT = Int [Low] as CKA_PUBLIC_EXPONENT,
+        /// Named curve of an EC key, as a DER encoded OID.
+        0x0180 => OBJ_ATTR_EC_PARAMS = Curve [Low] as CKA_EC_PARAMS,
+        /// EC public point, uncompressed and usually wrapped in a DER OCTET STRING.
+        0x0181 => OBJ_ATTR_EC_POINT = EcPoint [Info] as CKA_EC_POINT,
+        /// Length in bytes of any value.
+        0x0161 => OBJ_ATTR_VALUE_LEN = Int [Medium, weak if Int(1..=15)] as CKA_VALUE_LEN,
+        /// Indicates if key can be extracted.
+        0x0162 => OBJ_ATTR_EXTRACTABLE = Bool [High, weak if Bool(true)] as CKA_EXTRACTABLE,
+        /// Indicates if key was generated locally.
+        0x0163 => OBJ_ATTR_LOCAL = Bool [High, weak if Bool(false)] as CKA_LOCAL,
+        /// Indicates if key can never be extracted.
+        0x0164 => OBJ_ATTR_NEVER_EXTRACTABLE = Bool [High, weak if Bool(false)] as CKA_NEVER_EXTRACTABLE,
+        /// Indicates if key has always had the OBJ_ATTR_SENSITIVE attribute set.
+        0x0165 => OBJ_ATTR_ALWAYS_SENSITIVE = Bool [High, weak if Bool(false)] as CKA_ALWAYS_SENSITIVE,
+        /// Key Check Value.
+        0x0173 => OBJ_ATTR_KCV = Bytes [Info] as CKA_CHECK_VALUE,
+        /// Extended Attribute #1
+        0x1000 => OBJ_EXT_ATTR1	= Bytes [Info],
+        /// Extended Key Check Value.
+        0x1003 => OBJ_ATTR_EKCV	= Bytes [Info],
+        /// Indicates if key can only be wrapped with a wrapping key that has OBJ_ATTR_TRUSTED set.
+        0x0210 => OBJ_ATTR_WRAP_WITH_TRUSTED = Bool [Medium] as CKA_WRAP_WITH_TRUSTED,
+        /// Indicates if key can be split into multiple parts.
+        0x80000002 => OBJ_ATTR_SPLITTABLE = Bool [Medium],
+        /// Indicate if it is part of the key split.
+        0x80000003 => OBJ_ATTR_IS_SPLIT	= Bool [Low],
+        /// Indicate if key supports encryption.
+        0x80000174 => OBJ_ATTR_ENCRYPT_KEY_MECHANISMS = Bool [Low],
+        /// Indicate if key supports decryption.
+        0x80000175 => OBJ_ATTR_DECRYPT_KEY_MECHANISMS = Bool [Low],
+        /// Indicate if key supports signing.
+        0x80000176 => OBJ_ATTR_SIGN_KEY_MECHANISMS = Bool [Low],
+        /// Indicate if key supports signature verification.
+        0x80000177 => OBJ_ATTR_VERIFY_KEY_MECHANISMS = Bool [Low],
+        /// Indicate if key supports key wrapping.
+        0x80000178 => OBJ_ATTR_WRAP_KEY_MECHANISMS = Bool [Low],
+        /// Indicate if key supports key unwrapping.
+        0x80000179 => OBJ_ATTR_UNWAP_KEY_MECHANISMS = Bool [Low],
+        /// Indicate if key supports key derivation.
+        0x80000180 => OBJ_ATTR_DERIVE_KEY_MECHANISMS = Bool [Low],
+
+        /// Unknown
+        0x80000000 => OBJ_UNKNOWN = Bytes [Info],
+
+        /// Attestation signature.
+        0xFFFFFF01 => SIGNATURE = ByteStr [Info],
+    }
+}
+
+/// How much an attribute says about the protection of the key, from `Info` (identification only)
+/// to `High` (whether the key material can leave the HSM).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SecurityRelevance {
+    Info,
+    Low,
+    Medium,
+    High,
+}
+
+impl SecurityRelevance {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            SecurityRelevance::Info => "info",
+            SecurityRelevance::Low => "low",
+            SecurityRelevance::Medium => "medium",
+            SecurityRelevance::High => "high",
+        }
     }
 }
 
-#[derive(Debug)]
+impl core::fmt::Display for SecurityRelevance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum ClassKey {
     // asymmetric keys
@@ -102,6 +138,8 @@ pub trait EncodeTLV {
     where
         Self: Sized;
     fn to_str(&self) -> String;
+    // the wire form, the reverse of `encode`
+    fn to_bytes(&self) -> Vec<u8>;
 }
 
 impl EncodeTLV for ClassKey {
@@ -125,9 +163,13 @@ impl EncodeTLV for ClassKey {
         }
         .to_string()
     }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum KeyType {
     Rsa = 0x00,
@@ -185,25 +227,33 @@ impl EncodeTLV for KeyType {
         }
         .to_string()
     }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
 }
 
-#[derive(Debug)]
-pub struct Bool(bool);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bool(pub bool);
 impl EncodeTLV for Bool {
     fn encode(bytes: &[u8], _len: u32) -> Option<Self>
     where
         Self: Sized,
     {
-        bytes.get(0).map(|b| *b > 0).map(|b| Bool(b))
+        bytes.first().map(|b| *b > 0).map(Bool)
     }
 
     fn to_str(&self) -> String {
-        format!("{}", self.0)
+        self.0.to_string()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        vec![self.0 as u8]
     }
 }
 
-#[derive(Debug)]
-pub struct HexStr128(String);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexStr128(pub String);
 impl EncodeTLV for HexStr128 {
     fn encode(bytes: &[u8], _len: u32) -> Option<Self>
     where
@@ -213,37 +263,62 @@ impl EncodeTLV for HexStr128 {
     }
 
     fn to_str(&self) -> String {
-        format!("{}", self.0)
+        self.0.to_string()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.as_bytes().to_vec()
     }
 }
 
-#[derive(Debug)]
-pub struct Bytes(String);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytes(pub String);
 impl EncodeTLV for Bytes {
-    fn encode(bytes: &[u8], len: u32) -> Option<Self>
+    fn encode(bytes: &[u8], _len: u32) -> Option<Self>
     where
         Self: Sized,
     {
-        let mut s = String::with_capacity(len as _);
-        for byte in bytes {
-            write!(&mut s, "{:02x}", byte).ok()?;
-        }
-        Some(Bytes(s))
+        // the declared length comes off the wire, size by what actually arrived
+        Some(Bytes(to_hex(bytes)))
     }
 
     fn to_str(&self) -> String {
-        format!("{}", self.0)
+        self.0.to_string()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        from_hex(&self.0).unwrap_or_default()
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(&mut s, "{:02x}", byte);
+    }
+    s
+}
+
+// accepts an optional 0x prefix and colon separated bytes, as printed by openssl
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim().trim_start_matches("0x").replace(':', "");
+    if !hex.len().is_multiple_of(2) {
+        return None;
     }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
 }
 
-#[derive(Debug)]
-pub struct ByteStr(String);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteStr(pub String);
 impl EncodeTLV for ByteStr {
-    fn encode(bytes: &[u8], len: u32) -> Option<Self>
+    fn encode(bytes: &[u8], _len: u32) -> Option<Self>
     where
         Self: Sized,
     {
-        let mut s = Vec::with_capacity(len as _);
+        let mut s = Vec::with_capacity(bytes.len());
         for byte in bytes {
             if *byte == 0x00 {
                 break;
@@ -255,12 +330,157 @@ impl EncodeTLV for ByteStr {
     }
 
     fn to_str(&self) -> String {
-        format!("{}", self.0)
+        self.0.to_string()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.as_bytes().to_vec()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    P256,
+    P384,
+    P521,
+    Secp256k1,
+}
+
+impl Curve {
+    pub const ALL: [Curve; 4] = [Curve::P256, Curve::P384, Curve::P521, Curve::Secp256k1];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Curve::P256 => "P-256",
+            Curve::P384 => "P-384",
+            Curve::P521 => "P-521",
+            Curve::Secp256k1 => "secp256k1",
+        }
+    }
+
+    pub fn oid(&self) -> &'static str {
+        match self {
+            Curve::P256 => "1.2.840.10045.3.1.7",
+            Curve::P384 => "1.3.132.0.34",
+            Curve::P521 => "1.3.132.0.35",
+            Curve::Secp256k1 => "1.3.132.0.10",
+        }
+    }
+
+    // the OID with its DER tag and length, as it's attested
+    pub fn der(&self) -> &'static [u8] {
+        match self {
+            Curve::P256 => &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07],
+            Curve::P384 => &[0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x22],
+            Curve::P521 => &[0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x23],
+            Curve::Secp256k1 => &[0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x0a],
+        }
+    }
+
+    // bytes in each coordinate of a point on the curve
+    pub fn field_len(&self) -> usize {
+        match self {
+            Curve::P256 | Curve::Secp256k1 => 32,
+            Curve::P384 => 48,
+            Curve::P521 => 66,
+        }
+    }
+}
+
+impl EncodeTLV for Curve {
+    fn encode(bytes: &[u8], _len: u32) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        Curve::ALL.into_iter().find(|curve| curve.der() == bytes)
+    }
+
+    fn to_str(&self) -> String {
+        self.name().to_string()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.der().to_vec()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcPoint {
+    pub x: Vec<u8>,
+    pub y: Vec<u8>,
+    // PKCS#11 keeps the point in a DER OCTET STRING, some firmware leaves it bare
+    pub wrapped: bool,
+}
+
+impl EcPoint {
+    pub fn new(x: Vec<u8>, y: Vec<u8>) -> Self {
+        EcPoint { x, y, wrapped: true }
+    }
+
+    /// Splits `0x04 || X || Y`, the coordinates have to be as long as those of a known curve.
+    pub fn from_uncompressed(point: &[u8]) -> Option<Self> {
+        let [0x04, coordinates @ ..] = point else {
+            return None;
+        };
+        let len = coordinates.len() / 2;
+        if !Curve::ALL.iter().any(|curve| coordinates.len() == 2 * curve.field_len()) {
+            return None;
+        }
+        Some(EcPoint { x: coordinates[..len].to_vec(), y: coordinates[len..].to_vec(), wrapped: false })
+    }
+
+    pub fn to_uncompressed(&self) -> Vec<u8> {
+        let mut point = Vec::with_capacity(1 + self.x.len() + self.y.len());
+        point.push(0x04);
+        point.extend_from_slice(&self.x);
+        point.extend_from_slice(&self.y);
+        point
+    }
+}
+
+// the content of a DER OCTET STRING taking up all of `bytes`, points are short enough for the
+// one byte length forms
+fn octet_string(bytes: &[u8]) -> Option<&[u8]> {
+    let (len, content) = match bytes {
+        [0x04, 0x81, len, content @ ..] if *len >= 0x80 => (*len as usize, content),
+        [0x04, len, content @ ..] if *len < 0x80 => (*len as usize, content),
+        _ => return None,
+    };
+    (content.len() == len).then_some(content)
+}
+
+impl EncodeTLV for EcPoint {
+    fn encode(bytes: &[u8], _len: u32) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        octet_string(bytes)
+            .and_then(EcPoint::from_uncompressed)
+            .map(|point| EcPoint { wrapped: true, ..point })
+            .or_else(|| EcPoint::from_uncompressed(bytes))
+    }
+
+    fn to_str(&self) -> String {
+        format!("x: {}, y: {}", to_hex(&self.x), to_hex(&self.y))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let point = self.to_uncompressed();
+        if !self.wrapped {
+            return point;
+        }
+        let mut bytes = vec![0x04];
+        if point.len() >= 0x80 {
+            bytes.push(0x81);
+        }
+        bytes.push(point.len() as u8);
+        bytes.extend(point);
+        bytes
     }
 }
 
-#[derive(Debug)]
-pub struct RawBytes(Vec<u8>);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawBytes(pub Vec<u8>);
 impl EncodeTLV for RawBytes {
     fn encode(bytes: &[u8], _len: u32) -> Option<Self>
     where
@@ -272,10 +492,14 @@ impl EncodeTLV for RawBytes {
     fn to_str(&self) -> String {
         format!("{:?}", self.0)
     }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.clone()
+    }
 }
 
-#[derive(Debug)]
-pub struct Int(u32);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Int(pub u32);
 impl EncodeTLV for Int {
     fn encode(bytes: &[u8], _len: u32) -> Option<Self>
     where
@@ -290,11 +514,15 @@ impl EncodeTLV for Int {
     }
 
     fn to_str(&self) -> String {
-        format!("{}", self.0)
+        self.0.to_string()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_be_bytes().to_vec()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TLVValue {
     Bool(Bool),
     ClassKey(ClassKey),
@@ -304,9 +532,25 @@ pub enum TLVValue {
     RawBytes(RawBytes),
     ByteStr(ByteStr),
     Int(Int),
+    Curve(Curve),
+    EcPoint(EcPoint),
 }
 
 impl TLVValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TLVValue::Bool(b) => Some(b.0),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<u32> {
+        match self {
+            TLVValue::Int(i) => Some(i.0),
+            _ => None,
+        }
+    }
+
     pub fn to_str(&self) -> String {
         match self {
             TLVValue::Bool(b) => b.to_str(),
@@ -317,6 +561,23 @@ impl TLVValue {
             TLVValue::ByteStr(b) => b.to_str(),
             TLVValue::Int(b) => b.to_str(),
             TLVValue::KeyType(b) => b.to_str(),
+            TLVValue::Curve(b) => b.to_str(),
+            TLVValue::EcPoint(b) => b.to_str(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            TLVValue::Bool(b) => b.to_bytes(),
+            TLVValue::ClassKey(b) => b.to_bytes(),
+            TLVValue::HexStr128(b) => b.to_bytes(),
+            TLVValue::Bytes(b) => b.to_bytes(),
+            TLVValue::RawBytes(b) => b.to_bytes(),
+            TLVValue::ByteStr(b) => b.to_bytes(),
+            TLVValue::Int(b) => b.to_bytes(),
+            TLVValue::KeyType(b) => b.to_bytes(),
+            TLVValue::Curve(b) => b.to_bytes(),
+            TLVValue::EcPoint(b) => b.to_bytes(),
         }
     }
 }
@@ -0,0 +1,99 @@
+use crate::error::ParseError;
+use core::fmt;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::traits::PublicKeyParts;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256};
+
+/* Signature verification of the attestation bundle.
+ *
+ * The bundle is signed by the HSM with RSA PKCS#1 v1.5 over SHA-256. The signature is the last
+ * `signature_len` bytes of the bundle (the modulus size of the signing key), and the digest covers
+ * every byte that precedes it, header included.
+ * */
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SignatureVerdict {
+    Valid,
+    Invalid,
+}
+
+impl SignatureVerdict {
+    pub fn is_valid(&self) -> bool {
+        *self == SignatureVerdict::Valid
+    }
+}
+
+impl fmt::Display for SignatureVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureVerdict::Valid => write!(f, "valid"),
+            SignatureVerdict::Invalid => write!(f, "invalid"),
+        }
+    }
+}
+
+/// The owner or manufacturer RSA public key the bundle is checked against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestationKey(RsaPublicKey);
+
+impl AttestationKey {
+    /// Accepts either a `PUBLIC KEY` (SubjectPublicKeyInfo) or `RSA PUBLIC KEY` (PKCS#1) PEM.
+    pub fn from_pem(pem: &str) -> Result<Self, ParseError> {
+        let pem = pem.trim();
+        RsaPublicKey::from_public_key_pem(pem)
+            .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+            .map(Self)
+            .map_err(|e| ParseError::InvalidPublicKey(e.to_string()))
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, ParseError> {
+        RsaPublicKey::from_public_key_der(der)
+            .or_else(|_| RsaPublicKey::from_pkcs1_der(der))
+            .map(Self)
+            .map_err(|e| ParseError::InvalidPublicKey(e.to_string()))
+    }
+
+    /// Sniffs for a PEM armour and falls back to DER otherwise.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseError> {
+        match core::str::from_utf8(bytes) {
+            Ok(pem) if pem.contains("-----BEGIN") => Self::from_pem(pem),
+            _ => Self::from_der(bytes),
+        }
+    }
+
+    /// Length in bytes of signatures produced by this key.
+    pub fn signature_len(&self) -> usize {
+        self.0.size()
+    }
+
+    pub fn public_key(&self) -> &RsaPublicKey {
+        &self.0
+    }
+}
+
+impl From<RsaPublicKey> for AttestationKey {
+    fn from(value: RsaPublicKey) -> Self {
+        Self(value)
+    }
+}
+
+/// Splits a raw bundle into the signed data and its trailing signature.
+pub fn split_signature(bundle: &[u8], signature_len: usize) -> Result<(&[u8], &[u8]), ParseError> {
+    if bundle.len() <= signature_len {
+        return Err(ParseError::BundleTooShort(bundle.len()));
+    }
+    Ok(bundle.split_at(bundle.len() - signature_len))
+}
+
+/// Checks the trailing signature of a raw (decompressed) bundle against `key`.
+pub fn verify_bundle(bundle: &[u8], key: &AttestationKey) -> Result<SignatureVerdict, ParseError> {
+    let (data, signature) = split_signature(bundle, key.signature_len())?;
+    let digest = Sha256::digest(data);
+
+    match key.0.verify(Pkcs1v15Sign::new::<Sha256>(), &digest, signature) {
+        Ok(_) => Ok(SignatureVerdict::Valid),
+        Err(_) => Ok(SignatureVerdict::Invalid),
+    }
+}
@@ -1,65 +1,230 @@
-use crate::KeyMode;
+use crate::attestation::{Attestation, Attribute};
+use crate::pkcs11::NameStyle;
+use crate::pubkey::to_hex;
+use crate::tlv_mapping::TLVValue;
+use serde_json::{json, Value};
+use std::fmt::Write;
 
+/// JSON Schema for the documents produced by `Writer::to_json`.
+pub const JSON_SCHEMA: &str = include_str!("../schema/attestation.schema.json");
+
+/// Renders a parsed `Attestation` for the CLI and the browser.
 #[derive(Debug)]
-pub struct Writer {
-    inner: Vec<(KeyMode, String, String)>,
-    mode: KeyMode,
+pub struct Writer<'a> {
+    attestation: &'a Attestation,
+    extra: Vec<(String, String)>,
+    names: NameStyle,
 }
 
-impl Writer {
-    pub fn new() -> Self {
-        Self { inner: vec![], mode: KeyMode::default() }
+impl<'a> Writer<'a> {
+    pub fn new(attestation: &'a Attestation) -> Self {
+        Self { attestation, extra: vec![], names: NameStyle::default() }
     }
 
-    pub fn new_with_capacity(capacity: usize) -> Self {
-        Self { inner: Vec::with_capacity(capacity), mode: KeyMode::default() }
+    /// Names attributes by their PKCS#11 (`CKA_*`) names where they have one.
+    pub fn with_names(mut self, names: NameStyle) -> Self {
+        self.names = names;
+        self
     }
 
-    pub fn push<I: Into<String>>(&mut self, attr_name: I, attr_value: I, mode: KeyMode) {
-        self.inner.push((mode, attr_name.into(), attr_value.into()));
+    /// Appends a name/value pair after the signature, e.g. a verification verdict.
+    pub fn with_pair<I: Into<String>>(mut self, name: I, value: I) -> Self {
+        self.extra.push((name.into(), value.into()));
+        self
     }
 
-    pub fn take(self) -> Vec<(KeyMode, String, String)> {
-        self.inner
+    fn signature_hex(&self) -> String {
+        to_hex(&self.attestation.signature)
     }
 
-    pub fn to_json_bytes(self) -> Vec<u8> {
-        let mut json_str = Vec::new();
-        json_str.push(b'[');
-        json_str.extend_from_slice(format!(r#"{{"mode": "{:?}", "pairs": {{"#, self.mode).as_bytes());
-        let max_len = self.inner.len();
-        let mut current_mode = self.mode;
-        let mut mode_changed = false;
-        for (idx, (mode, key, val)) in self.inner.into_iter().enumerate() {
-            // close off mode and pairs
-            if mode != current_mode {
-                json_str.pop();
-                json_str.extend_from_slice(format!(r#"}}}}, {{"mode": "{:?}", "pairs": {{"#, mode).as_bytes());
-                current_mode = mode;
-                mode_changed = true;
+    /// The attestation as a JSON document, laid out as described by `JSON_SCHEMA`.
+    pub fn to_json(&self) -> Value {
+        let header = &self.attestation.header;
+        let sections = self
+            .attestation
+            .sections
+            .iter()
+            .map(|section| {
+                let attributes = section
+                    .attributes
+                    .iter()
+                    .map(|attr| {
+                        json!({
+                            "type": attr.type_id,
+                            "name": attr.mapping.label(self.names),
+                            "len": attr.raw.len(),
+                            "value": json_value(&attr.value),
+                            "description": attr.mapping.description(),
+                            "relevance": attr.mapping.security_relevance().to_str(),
+                            "weakens": section.is_weakened_by(attr),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                json!({
+                    "mode": format!("{:?}", section.mode),
+                    "object_handle": section.object_handle,
+                    "attr_count": section.attr_count,
+                    "attr_size": section.attr_size,
+                    "attributes": attributes,
+                })
+            })
+            .collect::<Vec<_>>();
+        // a list rather than an object, callers may add the same name more than once
+        let extra = self
+            .extra
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect::<Vec<_>>();
+
+        json!({
+            "header": {
+                "response_code": header.response_code,
+                "request_id": header.request_id,
+                "total_size": header.total_size,
+                "buff_size": header.buff_size,
+                "response_payload": to_hex(&header.response_payload),
+                "attr_offset": header.attr_offset,
+                "attribute_format": header.attribute_format,
+                "firstkey_offset": header.firstkey_offset,
+                "secondkey_offset": header.secondkey_offset,
+                "signature_len": header.signature_len,
+            },
+            "mode": format!("{:?}", self.attestation.mode()),
+            "sections": sections,
+            "signature": self.signature_hex(),
+            "extra": extra,
+        })
+    }
+
+    pub fn to_json_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(&self.to_json()).unwrap_or_default()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for section in &self.attestation.sections {
+            let _ = writeln!(out, "------------KEYMODE: {:?} -------------", section.mode);
+            for attr in &section.attributes {
+                let _ = writeln!(out, "{}", attr.to_line(self.names));
+                let _ = writeln!(out, "{}", explanation(attr, section.is_weakened_by(attr)));
             }
+        }
+        if !self.attestation.signature.is_empty() {
+            let _ = writeln!(out, "Attestation Signature \n{}", self.signature_hex());
+        }
+        for (name, value) in &self.extra {
+            let _ = writeln!(out, "{} = {}", name, value);
+        }
+        out
+    }
+}
+
+/// The line printed under each attribute: its relevance and description, and a marker if its value
+/// weakens the key.
+pub fn explanation(attr: &Attribute, weakens: bool) -> String {
+    let mapping = attr.mapping;
+    let marker = if weakens { " <- weakens the key" } else { "" };
+    format!("    [{}] {}{}", mapping.security_relevance(), mapping.description(), marker)
+}
+
+/* A minimal block style YAML emitter, enough for the documents we produce.
+ * Strings are written as JSON strings, which YAML accepts as double quoted scalars once the few
+ * characters it won't take unescaped (DEL, the C1 controls, the BOM) are escaped as well. Keys stay
+ * plain only when no YAML 1.1 parser could read them as anything but a string.
+ * */
+pub fn to_yaml(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => write_yaml(&mut out, value, 0),
+        Value::Array(items) if !items.is_empty() => write_yaml(&mut out, value, 0),
+        scalar => {
+            let _ = writeln!(out, "{}", yaml_scalar(scalar));
+        }
+    }
+    out
+}
+
+fn is_block(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+// words YAML 1.1 reads as booleans or null
+const YAML_RESERVED: [&str; 10] = ["y", "n", "yes", "no", "true", "false", "on", "off", "null", "~"];
 
-            json_str.extend_from_slice(format!(r#""{}":"{}""#, key, val).as_bytes());
-            if mode_changed {
-                json_str.push(b',');
-                mode_changed = false;
-            } else if idx != max_len - 1 {
-                json_str.push(b',');
+fn yaml_key(key: &str) -> String {
+    let plain = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !YAML_RESERVED.iter().any(|word| word.eq_ignore_ascii_case(key));
+    match plain {
+        true => key.to_string(),
+        false => yaml_string(key),
+    }
+}
+
+fn yaml_string(s: &str) -> String {
+    let json = Value::String(s.to_string()).to_string();
+    let mut out = String::with_capacity(json.len());
+    for c in json.chars() {
+        match c {
+            '\u{7f}'..='\u{9f}' | '\u{feff}' | '\u{fffe}' | '\u{ffff}' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
             }
+            c => out.push(c),
         }
-        json_str.push(b'}');
-        json_str.push(b'}');
-        json_str.push(b']');
-        json_str
+    }
+    out
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => yaml_string(s),
+        other => other.to_string(),
     }
 }
 
-/*
- * {
- *   mode: "symmetric",
- *   kv_pairs: { "key": "val"},
- * }
- * }
- * JSON.parse(`[{"mode": "something", "pairs": {"key": "vals"}}, {"mode": "something", "pairs": {"key": "vals"}} ]`);
+fn write_yaml(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) => {
+            for (key, item) in map {
+                if is_block(item) {
+                    let _ = writeln!(out, "{}{}:", pad, yaml_key(key));
+                    write_yaml(out, item, indent + 2);
+                } else {
+                    let _ = writeln!(out, "{}{}: {}", pad, yaml_key(key), yaml_scalar(item));
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                if is_block(item) {
+                    // render the item one level in, then hang its first line off the dash
+                    let mut nested = String::new();
+                    write_yaml(&mut nested, item, indent + 2);
+                    let _ = write!(out, "{}- {}", pad, &nested[indent + 2..]);
+                } else {
+                    let _ = writeln!(out, "{}- {}", pad, yaml_scalar(item));
+                }
+            }
+        }
+        scalar => {
+            let _ = writeln!(out, "{}{}", pad, yaml_scalar(scalar));
+        }
+    }
+}
 
- */
+// booleans and integers keep their type, EC points are split into their coordinates, everything
+// else is rendered as its display string, with byte values in hex
+fn json_value(value: &TLVValue) -> Value {
+    match value {
+        TLVValue::Bool(b) => Value::Bool(b.0),
+        TLVValue::Int(i) => Value::from(i.0),
+        TLVValue::RawBytes(b) => Value::String(to_hex(&b.0)),
+        TLVValue::EcPoint(p) => json!({ "x": to_hex(&p.x), "y": to_hex(&p.y) }),
+        other => Value::String(other.to_str()),
+    }
+}
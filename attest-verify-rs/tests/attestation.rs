@@ -0,0 +1,100 @@
+mod common;
+
+use common::*;
+use hsmattest::attestation::{Attestation, AttestationHeader};
+use hsmattest::builder::AttestationBuilder;
+use hsmattest::tlv_mapping::*;
+use hsmattest::writer::Writer;
+use hsmattest::{KeyMode, Machine, Mode};
+
+#[test]
+fn values_keep_their_types() {
+    let attestation = parse(&rsa_builder().build().unwrap()).unwrap();
+    let private = attestation.section(KeyMode::Secondary).unwrap();
+    assert_eq!(private.mode, KeyMode::Secondary);
+    assert_eq!(private.value(TLVMapping::OBJ_ATTR_EXTRACTABLE).and_then(TLVValue::as_bool), Some(false));
+    assert_eq!(private.value(TLVMapping::OBJ_ATTR_CLASS), Some(&TLVValue::ClassKey(ClassKey::Privkey)));
+    assert_eq!(private.value(TLVMapping::OBJ_ATTR_KEY_TYPE), Some(&TLVValue::KeyType(KeyType::Rsa)));
+    // no reading a boolean as an integer, nor looking up an attribute the section doesn't have
+    assert_eq!(private.value(TLVMapping::OBJ_ATTR_SIGN).and_then(TLVValue::as_int), None);
+    assert_eq!(private.value(TLVMapping::OBJ_ATTR_MODULUS_BITS), None);
+
+    let public = attestation.section(KeyMode::Primary).unwrap();
+    assert_eq!(public.value(TLVMapping::OBJ_ATTR_MODULUS_BITS).and_then(TLVValue::as_int), Some(2048));
+    assert_eq!(public.value(TLVMapping::OBJ_ATTR_LOCAL).and_then(TLVValue::as_int), None);
+}
+
+#[test]
+fn attributes_keep_their_raw_bytes() {
+    let attestation = parse(&rsa_builder().build().unwrap()).unwrap();
+    let public = attestation.section(KeyMode::Primary).unwrap();
+
+    let label = public.get(TLVMapping::OBJ_ATTR_LABEL).unwrap();
+    assert_eq!(label.type_id, TLVMapping::OBJ_ATTR_LABEL.to_int());
+    assert_eq!(label.raw, b"fixture-key\0");
+    assert_eq!(label.value.to_str(), "fixture-key");
+
+    let exponent = public.get(TLVMapping::OBJ_ATTR_PUBLIC_EXPONENT).unwrap();
+    assert_eq!(exponent.raw, [0x01, 0x00, 0x01]);
+    assert_eq!(exponent.value.to_str(), "65537");
+    assert_eq!(exponent.to_string(), "Type = 0122  ObjAttrPublicExponent Len = 3,  \"65537\"");
+}
+
+#[test]
+fn types_without_a_mapping_are_kept() {
+    let section = aes_section().with_raw(0x7777_0001, vec![1, 2, 3]);
+    let bundle = AttestationBuilder::new()
+        .with_section(section)
+        .with_signature(vec![0; 256])
+        .build()
+        .unwrap();
+    let attestation = parse(&bundle).unwrap();
+    let unknown = attestation.sections[0].attributes.last().unwrap();
+    assert_eq!(unknown.type_id, 0x7777_0001);
+    assert_eq!(unknown.mapping, TLVMapping::UNKNOWN);
+    assert_eq!(unknown.raw, [1, 2, 3]);
+    assert_eq!(attestation.sections[0].get(TLVMapping::UNKNOWN), Some(unknown));
+}
+
+#[test]
+fn symmetric_keys_have_no_secondary_section() {
+    let attestation = parse(&aes_builder().build().unwrap()).unwrap();
+    assert_eq!(attestation.mode(), Mode::Symmetric);
+    assert!(attestation.section(KeyMode::Primary).is_some());
+    assert!(attestation.section(KeyMode::Secondary).is_none());
+    assert_eq!(attestation.signature.len(), 256);
+}
+
+#[test]
+fn finish_returns_the_attestation() {
+    let bundle = ec_builder().build().unwrap();
+    let mut machine = Machine::new();
+    machine.run_buf(&bundle);
+    let attestation = machine.finish().unwrap();
+    assert_eq!(attestation.header.total_size as usize, bundle.len());
+    assert_eq!(attestation.sections.len(), 2);
+
+    // the default is an empty attestation of a symmetric key
+    let empty = Attestation::default();
+    assert_eq!(empty.header, AttestationHeader::default());
+    assert_eq!(empty.mode(), Mode::Symmetric);
+    assert!(empty.sections.is_empty());
+}
+
+#[test]
+fn writers_render_the_typed_values() {
+    let attestation = parse(&rsa_builder().build().unwrap()).unwrap();
+    let json = Writer::new(&attestation).to_json();
+    let private = json["sections"][1]["attributes"].as_array().unwrap();
+    let extractable = private.iter().find(|attr| attr["name"] == "ObjAttrExtractable").unwrap();
+    assert_eq!(extractable["value"], false);
+    assert_eq!(json["sections"][0]["attributes"][6]["value"], 2048);
+    assert_eq!(json["header"]["total_size"], attestation.header.total_size);
+
+    let text = Writer::new(&attestation).to_text();
+    for section in &attestation.sections {
+        for attr in &section.attributes {
+            assert!(text.contains(&attr.to_string()), "{}", attr);
+        }
+    }
+}
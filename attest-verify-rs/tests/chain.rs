@@ -0,0 +1,233 @@
+mod common;
+
+use common::{EC_KEY, OWNER_KEY};
+use hsmattest::chain::{parse_certificates, subject_of, Certificate, ChainVerifier};
+use hsmattest::error::ParseError;
+use hsmattest::verify::AttestationKey;
+use rsa::pkcs8::{DecodePrivateKey, EncodePublicKey};
+use rsa::{Pkcs1v15Sign, RsaPrivateKey};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use x509_cert::der::asn1::{BitString, OctetString};
+use x509_cert::der::oid::AssociatedOid;
+use x509_cert::der::pem::LineEnding;
+use x509_cert::der::{Decode, DecodePem, Encode, EncodePem};
+use x509_cert::ext::pkix::{BasicConstraints, KeyUsage, KeyUsages};
+use x509_cert::ext::Extension;
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::spki::{AlgorithmIdentifierOwned, ObjectIdentifier, SubjectPublicKeyInfoOwned};
+use x509_cert::time::Validity;
+use x509_cert::{TbsCertificate, Version};
+
+const SHA256_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+
+// one key signs every certificate, only the names and extensions tell them apart
+fn key() -> RsaPrivateKey {
+    RsaPrivateKey::from_pkcs8_pem(OWNER_KEY).unwrap()
+}
+
+fn extension<T: Encode + AssociatedOid>(value: T) -> Extension {
+    let extn_value = OctetString::new(value.to_der().unwrap()).unwrap();
+    Extension { extn_id: T::OID, critical: true, extn_value }
+}
+
+fn ca(path_len: Option<u8>) -> Vec<Extension> {
+    vec![
+        extension(BasicConstraints { ca: true, path_len_constraint: path_len }),
+        extension(KeyUsage(KeyUsages::KeyCertSign | KeyUsages::CRLSign)),
+    ]
+}
+
+fn end_entity() -> Vec<Extension> {
+    vec![
+        extension(BasicConstraints { ca: false, path_len_constraint: None }),
+        extension(KeyUsage(KeyUsages::DigitalSignature.into())),
+    ]
+}
+
+// valid for the hour from now, for the key of `key()`
+fn tbs(subject: &str, issuer: &str, extensions: Vec<Extension>) -> TbsCertificate {
+    let spki = key().to_public_key().to_public_key_der().unwrap();
+    TbsCertificate {
+        version: Version::V3,
+        serial_number: SerialNumber::new(&[1]).unwrap(),
+        signature: AlgorithmIdentifierOwned { oid: SHA256_WITH_RSA, parameters: None },
+        issuer: Name::from_str(issuer).unwrap(),
+        validity: Validity::from_now(Duration::from_secs(3600)).unwrap(),
+        subject: Name::from_str(subject).unwrap(),
+        subject_public_key_info: SubjectPublicKeyInfoOwned::from_der(spki.as_bytes()).unwrap(),
+        issuer_unique_id: None,
+        subject_unique_id: None,
+        extensions: Some(extensions),
+    }
+}
+
+fn sign(tbs_certificate: TbsCertificate) -> Certificate {
+    let digest = Sha256::digest(tbs_certificate.to_der().unwrap());
+    let signature = key().sign(Pkcs1v15Sign::new::<Sha256>(), &digest).unwrap();
+    let signature = BitString::from_bytes(&signature).unwrap();
+    let signature_algorithm = tbs_certificate.signature.clone();
+    Certificate { tbs_certificate, signature_algorithm, signature }
+}
+
+fn mint(subject: &str, issuer: &str, extensions: Vec<Extension>) -> Certificate {
+    sign(tbs(subject, issuer, extensions))
+}
+
+fn verify(root: Certificate, pool: &[Certificate]) -> Result<Vec<String>, ParseError> {
+    let chains = ChainVerifier::new().with_owner_roots(vec![root]).verify(pool)?;
+    Ok(chains.owner.unwrap().certificates().iter().map(subject_of).collect())
+}
+
+fn issuer_refused(err: &ParseError) -> Option<&str> {
+    match err {
+        ParseError::NotACertificateAuthority { subject, .. } => Some(subject),
+        _ => None,
+    }
+}
+
+#[test]
+fn path_through_an_intermediate_ca() {
+    let root = mint("CN=Root", "CN=Root", ca(Some(1)));
+    let pool = [
+        mint("CN=Leaf", "CN=Intermediate", end_entity()),
+        mint("CN=Intermediate", "CN=Root", ca(Some(0))),
+    ];
+    assert_eq!(verify(root, &pool).unwrap(), ["CN=Leaf", "CN=Intermediate", "CN=Root"]);
+}
+
+#[test]
+fn leaf_signed_certificate_is_rejected() {
+    let root = mint("CN=Root", "CN=Root", ca(None));
+    let pool = [
+        mint("CN=Intermediate", "CN=Root", ca(None)),
+        mint("CN=Leaf", "CN=Intermediate", end_entity()),
+        mint("CN=Forged", "CN=Leaf", end_entity()),
+    ];
+    let err = verify(root, &pool).unwrap_err();
+    assert_eq!(issuer_refused(&err), Some("CN=Leaf"), "{:?}", err);
+}
+
+#[test]
+fn issuers_need_ca_constraints_and_key_cert_sign() {
+    let leaf = mint("CN=Leaf", "CN=Intermediate", end_entity());
+
+    // an intermediate without basicConstraints at all
+    let pool = [leaf.clone(), mint("CN=Intermediate", "CN=Root", ca(None).split_off(1))];
+    let err = verify(mint("CN=Root", "CN=Root", ca(None)), &pool).unwrap_err();
+    let expected = "Certificate 'CN=Intermediate' may not issue certificates: basicConstraints lacks cA";
+    assert_eq!(err.to_string(), expected);
+
+    // a CA that may not sign certificates
+    let mut extensions = ca(None);
+    extensions[1] = extension(KeyUsage(KeyUsages::CRLSign.into()));
+    let pool = [leaf.clone(), mint("CN=Intermediate", "CN=Root", extensions)];
+    let err = verify(mint("CN=Root", "CN=Root", ca(None)), &pool).unwrap_err();
+    let expected = "Certificate 'CN=Intermediate' may not issue certificates: keyUsage lacks keyCertSign";
+    assert_eq!(err.to_string(), expected);
+
+    // the root anchor is held to the same rules
+    let pool = [leaf, mint("CN=Intermediate", "CN=Root", ca(None))];
+    let err = verify(mint("CN=Root", "CN=Root", end_entity()), &pool).unwrap_err();
+    assert_eq!(issuer_refused(&err), Some("CN=Root"), "{:?}", err);
+
+    let err = verify(mint("CN=Root", "CN=Root", ca(Some(0))), &pool).unwrap_err();
+    assert_eq!(issuer_refused(&err), Some("CN=Root"), "{:?}", err);
+    assert!(err.to_string().ends_with("pathLenConstraint 0 with 1 CAs below it"), "{}", err);
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+// a root, an intermediate and a leaf, in no particular order
+fn owner_chain() -> (Certificate, Vec<Certificate>) {
+    let root = mint("CN=Root", "CN=Root", ca(None));
+    let pool = vec![
+        mint("CN=Intermediate", "CN=Root", ca(None)),
+        mint("CN=Leaf", "CN=Intermediate", end_entity()),
+    ];
+    (root, pool)
+}
+
+#[test]
+fn certificates_read_from_pem_bundles_and_der() {
+    let (root, pool) = owner_chain();
+    let pem = pool.iter().map(|cert| cert.to_pem(LineEnding::LF).unwrap()).collect::<Vec<_>>();
+    // gcloud's bundles carry text between the blocks
+    let bundle = format!("subject=CN=Intermediate\n{}\nsubject=CN=Leaf\n{}", pem[0], pem[1]);
+    assert_eq!(parse_certificates(bundle.as_bytes()).unwrap(), pool);
+    assert_eq!(parse_certificates(&root.to_der().unwrap()).unwrap(), [root]);
+
+    let garbled = b"-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n";
+    for bytes in [&b""[..], b"no certificates here", garbled] {
+        let err = parse_certificates(bytes).unwrap_err();
+        assert!(matches!(err, ParseError::InvalidCertificate(_)), "{:?}", err);
+    }
+}
+
+#[test]
+fn validity_is_checked_at_the_given_time() {
+    let (root, pool) = owner_chain();
+    let verifier = ChainVerifier::new().with_owner_roots(vec![root]);
+    assert!(verifier.clone().at_time(now()).verify(&pool).is_ok());
+    // without a time there is nothing to check against
+    assert!(verifier.clone().verify(&pool).is_ok());
+
+    let err = verifier.clone().at_time(now() + 7200).verify(&pool).unwrap_err();
+    let expired = matches!(&err, ParseError::CertificateExpired { subject, .. } if subject == "CN=Leaf");
+    assert!(expired, "{:?}", err);
+    let err = verifier.at_time(now() - 7200).verify(&pool).unwrap_err();
+    let early = matches!(&err, ParseError::CertificateNotYetValid { subject, .. } if subject == "CN=Leaf");
+    assert!(early, "{:?}", err);
+}
+
+#[test]
+fn chains_have_to_reach_a_trust_anchor() {
+    let (root, pool) = owner_chain();
+
+    let err = ChainVerifier::new().verify(&pool).unwrap_err();
+    assert!(matches!(err, ParseError::MissingTrustAnchors), "{:?}", err);
+
+    // the pool's own root is not trusted for being there
+    let pool_with_root = [pool.clone(), vec![root.clone()]].concat();
+    let other = mint("CN=Other Root", "CN=Other Root", ca(None));
+    let err = verify(other, &pool_with_root).unwrap_err();
+    assert!(matches!(&err, ParseError::UntrustedRoot(subject) if subject == "CN=Root"), "{:?}", err);
+
+    // no intermediate to get from the leaf to the root
+    let err = verify(root.clone(), &pool[1..]).unwrap_err();
+    assert!(matches!(&err, ParseError::BrokenChain(subject) if subject == "CN=Leaf"), "{:?}", err);
+
+    // an intermediate by the right name, but not by the right signature
+    let mut forged = pool.clone();
+    let mut signature = forged[0].signature.raw_bytes().to_vec();
+    signature[0] ^= 1;
+    forged[0].signature = BitString::from_bytes(&signature).unwrap();
+    let err = verify(root, &forged).unwrap_err();
+    assert!(matches!(&err, ParseError::BrokenChain(subject) if subject == "CN=Intermediate"), "{:?}", err);
+}
+
+#[test]
+fn both_chains_have_to_certify_the_same_key() {
+    let manufacturer = mint("CN=Manufacturer", "CN=Manufacturer", ca(None));
+    let owner = mint("CN=Owner", "CN=Owner", ca(None));
+    let hsm = mint("CN=HSM", "CN=Manufacturer", end_entity());
+    let verifier =
+        ChainVerifier::new().with_manufacturer_roots(vec![manufacturer]).with_owner_roots(vec![owner]);
+
+    let pool = [hsm.clone(), mint("CN=HSM (owner)", "CN=Owner", end_entity())];
+    let chains = verifier.verify(&pool).unwrap();
+    assert_eq!(subject_of(chains.manufacturer.as_ref().unwrap().leaf()), "CN=HSM");
+    assert_eq!(subject_of(chains.owner.as_ref().unwrap().leaf()), "CN=HSM (owner)");
+    assert_eq!(chains.leaf_key(), &AttestationKey::from_certificate(&hsm).unwrap());
+    assert_eq!(chains.leaf_key().public_key(), &key().to_public_key());
+
+    // the owner vouching for some other key
+    let mut other = tbs("CN=HSM (owner)", "CN=Owner", end_entity());
+    other.subject_public_key_info = SubjectPublicKeyInfoOwned::from_pem(EC_KEY).unwrap();
+    let err = verifier.verify(&[hsm, sign(other)]).unwrap_err();
+    assert!(matches!(err, ParseError::LeafKeyMismatch), "{:?}", err);
+}
@@ -0,0 +1,257 @@
+use hsmattest::attestation::{AttestationHeader, Attribute};
+use hsmattest::builder::{AttestationBuilder, KeySection};
+use hsmattest::error::ParseError;
+use hsmattest::observer::AttestationObserver;
+use hsmattest::tlv_mapping::*;
+use hsmattest::writer::Writer;
+use hsmattest::{KeyMode, Machine};
+use proptest::prelude::*;
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Read};
+use std::rc::Rc;
+
+/* However a bundle is split across reads, the machine has to come to the same result and report
+ * the same events in the same order. Failing cases are shrunk and saved by proptest under
+ * tests/chunking.proptest-regressions.
+ * */
+
+fn attribute() -> impl Strategy<Value = (TLVMapping, TLVValue)> {
+    let bool_mapping = prop::sample::select(vec![
+        TLVMapping::OBJ_ATTR_PRIVATE,
+        TLVMapping::OBJ_ATTR_SENSITIVE,
+        TLVMapping::OBJ_ATTR_EXTRACTABLE,
+        TLVMapping::OBJ_ATTR_NEVER_EXTRACTABLE,
+        TLVMapping::OBJ_ATTR_SPLITTABLE,
+    ]);
+    prop_oneof![
+        prop::sample::select(vec![ClassKey::Pubkey, ClassKey::Privkey, ClassKey::Secret])
+            .prop_map(|class| (TLVMapping::OBJ_ATTR_CLASS, TLVValue::ClassKey(class))),
+        prop::sample::select(vec![KeyType::Rsa, KeyType::Ec, KeyType::Aes, KeyType::Des3, KeyType::Generic])
+            .prop_map(|key_type| (TLVMapping::OBJ_ATTR_KEY_TYPE, TLVValue::KeyType(key_type))),
+        (bool_mapping, any::<bool>()).prop_map(|(mapping, b)| (mapping, TLVValue::Bool(Bool(b)))),
+        any::<u32>().prop_map(|i| (TLVMapping::OBJ_ATTR_MODULUS_BITS, TLVValue::Int(Int(i)))),
+        prop::collection::vec(any::<u8>(), 0..300)
+            .prop_map(|bytes| (TLVMapping::OBJ_ATTR_MODULUS, TLVValue::Bytes(Bytes(to_hex(&bytes))))),
+        "[a-zA-Z0-9 _-]{1,32}".prop_map(|label| (TLVMapping::OBJ_ATTR_LABEL, TLVValue::ByteStr(ByteStr(label)))),
+        "[0-9a-f]{128}".prop_map(|id| (TLVMapping::OBJ_ATTR_ID, TLVValue::HexStr128(HexStr128(id)))),
+        prop::sample::select(Curve::ALL.to_vec())
+            .prop_map(|curve| (TLVMapping::OBJ_ATTR_EC_PARAMS, TLVValue::Curve(curve))),
+        (prop::sample::select(Curve::ALL.to_vec()), any::<u8>(), any::<bool>()).prop_map(|(curve, fill, wrapped)| {
+            let len = curve.field_len();
+            let point = EcPoint { x: vec![fill; len], y: vec![!fill; len], wrapped };
+            (TLVMapping::OBJ_ATTR_EC_POINT, TLVValue::EcPoint(point))
+        }),
+    ]
+}
+
+fn section() -> impl Strategy<Value = KeySection> {
+    let unknown = (0x4000u32..0x5000, prop::collection::vec(any::<u8>(), 0..40));
+    (
+        any::<u32>(),
+        prop::collection::vec(attribute(), 1..12),
+        prop::collection::vec(unknown, 0..3),
+    )
+        .prop_map(|(handle, attributes, unknown)| {
+            let section = attributes
+                .into_iter()
+                .fold(KeySection::new(handle), |section, (mapping, value)| section.with(mapping, value));
+            unknown.into_iter().fold(section, |section, (type_id, raw)| section.with_raw(type_id, raw))
+        })
+}
+
+prop_compose! {
+    fn bundle()(
+        response_code in any::<u32>(),
+        request_id in any::<u32>(),
+        payload in prop::collection::vec(any::<u8>(), 0..64),
+        format in any::<u32>(),
+        sections in prop::collection::vec(section(), 1..=2),
+        signature_len in prop::sample::select(vec![256usize, 384, 512]),
+        filler in any::<u8>(),
+    ) -> (Vec<u8>, usize) {
+        let builder = AttestationBuilder::new()
+            .with_response_code(response_code)
+            .with_request_id(request_id)
+            .with_response_payload(payload)
+            .with_attribute_format(format)
+            .with_signature(vec![filler; signature_len]);
+        let builder = sections.into_iter().fold(builder, |builder, section| builder.with_section(section));
+        (builder.build().unwrap(), signature_len)
+    }
+}
+
+// read sizes, with 0 standing for an empty read; the schedule repeats, so it ends on a real one
+fn chunks() -> impl Strategy<Value = Vec<usize>> {
+    let size = prop_oneof![Just(0usize), Just(1), 0usize..16, 16usize..512, 512usize..4096];
+    (prop::collection::vec(size, 0..32), 1usize..4096).prop_map(|(mut sizes, last)| {
+        sizes.push(last);
+        sizes
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    hsmattest::pubkey::to_hex(bytes)
+}
+
+// every event as a line, in the order the observer saw them
+#[derive(Clone, Default)]
+struct Recorder(Rc<RefCell<Vec<String>>>);
+
+impl AttestationObserver for Recorder {
+    fn on_header(&mut self, header: &AttestationHeader) {
+        self.0.borrow_mut().push(format!("header {:?}", header));
+    }
+
+    fn on_key_section_start(&mut self, mode: KeyMode) {
+        self.0.borrow_mut().push(format!("section {:?}", mode));
+    }
+
+    fn on_attribute(&mut self, mode: KeyMode, attribute: &Attribute) {
+        self.0.borrow_mut().push(format!("attribute {:?} {}", mode, attribute));
+    }
+
+    fn on_signature(&mut self, signature: &[u8]) {
+        self.0.borrow_mut().push(format!("signature {}", to_hex(signature)));
+    }
+
+    fn on_error(&mut self, error: &ParseError) {
+        self.0.borrow_mut().push(format!("error {}", error));
+    }
+}
+
+// what a caller can see of a parse: the rendered output or the error, and the events
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    result: Result<(Vec<u8>, String), String>,
+    events: Vec<String>,
+}
+
+fn outcome<F: FnOnce(&mut Machine)>(signature_len: Option<usize>, feed: F) -> Outcome {
+    let recorder = Recorder::default();
+    let mut machine = match signature_len {
+        Some(len) => Machine::new_with_signature_len(len),
+        None => Machine::new(),
+    }
+    .with_observer(recorder.clone());
+
+    feed(&mut machine);
+    let result = machine
+        .finish()
+        .map(|attestation| {
+            let writer = Writer::new(&attestation);
+            (writer.to_json_bytes(), writer.to_text())
+        })
+        .map_err(|e| e.to_string());
+    let events = recorder.0.borrow().clone();
+    Outcome { result, events }
+}
+
+fn whole(bundle: &[u8], signature_len: Option<usize>) -> Outcome {
+    outcome(signature_len, |machine| machine.run_buf(bundle))
+}
+
+fn chunked(bundle: &[u8], signature_len: Option<usize>, sizes: &[usize]) -> Outcome {
+    outcome(signature_len, |machine| {
+        let mut rest = bundle;
+        for size in sizes.iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at((*size).min(rest.len()));
+            machine.run_buf(chunk);
+            rest = tail;
+        }
+    })
+}
+
+// hands out the bundle in the scheduled sizes, skipping the empty reads (0 means EOF to BufReader)
+struct Scheduled<'a> {
+    bytes: &'a [u8],
+    sizes: Vec<usize>,
+    next: usize,
+}
+
+impl Read for Scheduled<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.sizes[self.next % self.sizes.len()].max(1);
+        self.next += 1;
+        let len = size.min(buf.len()).min(self.bytes.len());
+        buf[..len].copy_from_slice(&self.bytes[..len]);
+        self.bytes = &self.bytes[len..];
+        Ok(len)
+    }
+}
+
+// the read loop the command line uses
+fn buffered(bundle: &[u8], sizes: &[usize], capacity: usize) -> Outcome {
+    outcome(None, |machine| {
+        let reader = Scheduled { bytes: bundle, sizes: sizes.to_vec(), next: 0 };
+        let mut reader = BufReader::with_capacity(capacity, reader);
+        loop {
+            let buffer = reader.fill_buf().unwrap();
+            let length = buffer.len();
+            machine.run_buf(buffer);
+            if length == 0 {
+                break;
+            }
+            reader.consume(length);
+        }
+    })
+}
+
+fn mutate(bundle: &mut Vec<u8>, mutations: &[(usize, u8, u8)]) {
+    for (position, kind, value) in mutations {
+        if bundle.is_empty() {
+            return;
+        }
+        let position = position % bundle.len();
+        match kind % 5 {
+            0 => bundle[position] = *value,
+            1 => bundle.truncate(position),
+            2 => bundle.insert(position, *value),
+            3 => {
+                bundle.remove(position);
+            }
+            _ => {
+                let extreme = [0u32, u32::MAX, 0x8000_0000, 0x7fff_ffff][*value as usize % 4];
+                let end = (position + 4).min(bundle.len());
+                bundle[position..end].copy_from_slice(&extreme.to_be_bytes()[..end - position]);
+            }
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn valid_bundles_parse_the_same_however_they_are_read(
+        (bundle, signature_len) in bundle(),
+        sizes in chunks(),
+    ) {
+        let expected = whole(&bundle, Some(signature_len));
+        prop_assert!(expected.result.is_ok(), "{:?}", expected.result);
+        prop_assert_eq!(&chunked(&bundle, Some(signature_len), &sizes), &expected);
+        prop_assert_eq!(&chunked(&bundle, Some(signature_len), &[1]), &expected);
+    }
+
+    #[test]
+    fn signature_detection_does_not_depend_on_chunking(
+        (bundle, _) in bundle(),
+        sizes in chunks(),
+        capacity in 1usize..2048,
+    ) {
+        let expected = whole(&bundle, None);
+        prop_assert_eq!(&chunked(&bundle, None, &sizes), &expected);
+        prop_assert_eq!(&buffered(&bundle, &sizes, capacity), &expected);
+    }
+
+    #[test]
+    fn mutated_bundles_do_not_panic(
+        (mut bundle, _) in bundle(),
+        mutations in prop::collection::vec((any::<usize>(), any::<u8>(), any::<u8>()), 1..6),
+        sizes in chunks(),
+    ) {
+        mutate(&mut bundle, &mutations);
+        let expected = whole(&bundle, None);
+        prop_assert_eq!(&chunked(&bundle, None, &sizes), &expected);
+    }
+}
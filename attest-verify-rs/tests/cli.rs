@@ -0,0 +1,270 @@
+mod common;
+
+use common::*;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/* Runs the hsmattest_bin built alongside these tests against fixtures written to the temp dir. */
+
+const POLICY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/policies/hsm-generated.toml");
+const OWNER_PUBLIC_KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/owner.pub.pem");
+// a key version none of the fixtures attests
+const KEY_NAME: &str = "projects/p/locations/l/keyRings/r/cryptoKeys/k/cryptoKeyVersions/1";
+
+fn fixture(name: &str, contents: &[u8]) -> String {
+    let path = std::env::temp_dir().join(format!("hsmattest-cli-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path.display().to_string()
+}
+
+fn run(args: &[&str]) -> (i32, String) {
+    let binary = PathBuf::from(env!("CARGO_BIN_EXE_hsmattest_bin"));
+    let output = Command::new(binary).args(args).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    (output.status.code().unwrap(), stdout)
+}
+
+fn extra(stdout: &str) -> Vec<String> {
+    let document: Value = serde_json::from_str(stdout).unwrap();
+    let pairs = document["extra"].as_array().unwrap();
+    pairs.iter().map(|pair| pair["name"].as_str().unwrap().to_string()).collect()
+}
+
+#[test]
+fn every_requested_check_is_reported() {
+    let bundle = fixture("checks.dat", &rsa_builder().build().unwrap());
+    let (code, stdout) = run(&["check", &bundle, "--gcp-key-name", KEY_NAME, "--policy", POLICY]);
+    assert_eq!(code, 3);
+    assert!(stdout.contains("No ObjAttrId matches the derived KeyID"), "{}", stdout);
+    assert!(stdout.contains("------------POLICY -------------"), "{}", stdout);
+    assert!(stdout.contains("PASS Secondary ObjAttrLocal equals true"), "{}", stdout);
+
+    let json = ["check", &bundle, "--gcp-key-name", KEY_NAME, "--policy", POLICY, "--format", "json"];
+    let (code, stdout) = run(&json);
+    assert_eq!(code, 3);
+    let names = extra(&stdout);
+    assert!(names.iter().any(|name| name.starts_with("KeyID")), "{:?}", names);
+    assert_eq!(names.iter().filter(|name| name.starts_with("Policy")).count(), 4, "{:?}", names);
+}
+
+#[test]
+fn the_most_severe_failure_sets_the_exit_code() {
+    let policy = fixture("strict.toml", b"[secondary]\nObjAttrLocal = { equals = false }\n");
+    let bundle = rsa_builder().build().unwrap();
+    let valid = fixture("severity.dat", &bundle);
+    // the response payload is covered by the signature, but not parsed
+    let mut tampered = bundle.clone();
+    tampered[16] ^= 1;
+    let tampered = fixture("severity-tampered.dat", &tampered);
+
+    let (code, _) = run(&["check", &valid, "--policy", &policy]);
+    assert_eq!(code, 2);
+    let (code, _) = run(&["check", &valid, "--policy", &policy, "--gcp-key-name", KEY_NAME]);
+    assert_eq!(code, 3);
+
+    let (code, stdout) = run(&[
+        "parse", &tampered, "--key", OWNER_PUBLIC_KEY, "--policy", &policy, "--gcp-key-name", KEY_NAME, "--format", "json",
+    ]);
+    assert_eq!(code, 4);
+    let names = extra(&stdout);
+    assert!(names.contains(&"Signature verification".to_string()), "{:?}", names);
+    assert!(names.iter().any(|name| name.starts_with("KeyID")), "{:?}", names);
+    assert!(names.iter().any(|name| name.starts_with("Policy")), "{:?}", names);
+}
+
+#[test]
+fn checks_name_attributes_as_asked() {
+    let bundle = fixture("names.dat", &rsa_builder().build().unwrap());
+    let args = ["check", &bundle, "--public-key", OWNER_PUBLIC_KEY, "--policy", POLICY, "--names", "pkcs11"];
+    let (code, stdout) = run(&args);
+    assert_eq!(code, 3);
+    assert!(stdout.contains("CKA_MODULUS (Primary) = mismatch"), "{}", stdout);
+    assert!(stdout.contains("PASS Secondary CKA_LOCAL equals true"), "{}", stdout);
+    assert!(!stdout.contains("ObjAttr"), "{}", stdout);
+
+    let names = extra(&run(&[&args[..], &["--format", "json"]].concat()).1);
+    assert!(names.iter().any(|name| name.starts_with("CKA_MODULUS (Primary)")), "{:?}", names);
+    assert!(names.contains(&"Policy Secondary CKA_LOCAL equals true".to_string()), "{:?}", names);
+}
+
+#[test]
+fn an_owner_key_and_certificates_conflict() {
+    let bundle = fixture("conflict.dat", &rsa_builder().build().unwrap());
+    let certs = fixture("conflict.pem", b"");
+    // the legacy form takes the owner key after the bundle
+    for args in [
+        &["verify", &bundle, "--key", OWNER_PUBLIC_KEY, "--certs", &certs][..],
+        &["parse", &bundle, OWNER_PUBLIC_KEY, "--certs", &certs],
+    ] {
+        let binary = PathBuf::from(env!("CARGO_BIN_EXE_hsmattest_bin"));
+        let output = Command::new(binary).args(args).output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Conflicting arguments, give the owner key or --certs, not both"), "{}", stderr);
+    }
+}
+
+#[test]
+fn a_closed_pipe_ends_the_run_quietly() {
+    let bundle = fixture("pipe.dat", &rsa_builder().build().unwrap());
+    let binary = PathBuf::from(env!("CARGO_BIN_EXE_hsmattest_bin"));
+    let mut child = Command::new(binary)
+        .args(["parse", &bundle, "--key", OWNER_PUBLIC_KEY])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // as `| head -0` would, before the first line is written
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn malformed_bundles_end_in_an_error() {
+    let bundle = rsa_builder().build().unwrap();
+    let truncated = fixture("truncated.dat", &bundle[..bundle.len() - 1]);
+    let mut trailing = bundle;
+    trailing.push(0);
+    let trailing = fixture("trailing.dat", &trailing);
+
+    for path in [&truncated, &trailing] {
+        let binary = PathBuf::from(env!("CARGO_BIN_EXE_hsmattest_bin"));
+        let output = Command::new(binary).args(["parse", path]).output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("Done"));
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn kcv_of_a_public_key() {
+    let (code, stdout) = run(&["kcv", OWNER_PUBLIC_KEY]);
+    assert_eq!(code, 0);
+    // `openssl pkey -pubin -outform der | openssl dgst -sha1`, and -sha256
+    let ekcv = "6925d763dc2173aabc24f381f84f2515c492c4f08d8d33defd85d6bea5e88a93";
+    assert_eq!(stdout, format!("KCV = 412e6c\nEKCV = {}\n", ekcv));
+
+    let (code, stdout) = run(&["kcv", "--public-key", OWNER_PUBLIC_KEY, "--format", "json"]);
+    assert_eq!(code, 0);
+    let document: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(document, serde_json::json!({ "kcv": "412e6c", "ekcv": ekcv }));
+
+    let (code, _) = run(&["kcv"]);
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn diff_exits_on_differences() {
+    let rsa = fixture("diff-rsa.dat", &rsa_builder().build().unwrap());
+    let ec = fixture("diff-ec.dat", &ec_builder().build().unwrap());
+
+    let (code, stdout) = run(&["diff", &rsa, &rsa]);
+    assert_eq!((code, stdout.as_str()), (0, "No differences\n"));
+
+    let (code, stdout) = run(&["diff", &rsa, &ec, "--format", "json"]);
+    assert_eq!(code, 5);
+    let document: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(document["identical"], false);
+    let differences = document["differences"].as_array().unwrap();
+    assert!(differences.iter().any(|d| d["name"] == "ObjAttrKeyType" && d["right"] == "ec"), "{}", stdout);
+}
+
+#[test]
+fn inspect_shows_the_layout() {
+    let bundle = aes_builder().build().unwrap();
+    let path = fixture("inspect.dat", &bundle);
+    let (code, stdout) = run(&["inspect", &path]);
+    assert_eq!(code, 0);
+    assert!(stdout.contains(&format!("Bundle = {} bytes\n", bundle.len())), "{}", stdout);
+    assert!(stdout.contains("Mode = Symmetric\n"), "{}", stdout);
+    assert!(stdout.ends_with("Signature = 256 bytes\n"), "{}", stdout);
+
+    let (code, stdout) = run(&["inspect", &path, "--format", "json"]);
+    assert_eq!(code, 0);
+    let document: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(document["bundle_len"], bundle.len());
+    assert_eq!(document["compressed"], false);
+    assert_eq!(document["header"]["attr_offset"], 42);
+    assert_eq!(document["sections"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn export_pubkey_checks_the_signature_first() {
+    let bundle = ec_builder().build().unwrap();
+    let path = fixture("export.dat", &bundle);
+    let (code, stdout) = run(&["export-pubkey", &path, "--key", OWNER_PUBLIC_KEY]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, EC_KEY);
+
+    let mut tampered = bundle;
+    tampered[16] ^= 1;
+    let tampered = fixture("export-tampered.dat", &tampered);
+    let (code, stdout) = run(&["export-pubkey", &tampered, "--key", OWNER_PUBLIC_KEY]);
+    assert_eq!((code, stdout.as_str()), (4, ""));
+}
+
+#[test]
+fn bundles_read_from_stdin() {
+    let binary = PathBuf::from(env!("CARGO_BIN_EXE_hsmattest_bin"));
+    let mut child = Command::new(binary)
+        .args(["parse", "-", "--format", "json"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&rsa_builder().build().unwrap()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let document: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(document["mode"], "Asymetric");
+}
+
+#[test]
+fn parse_is_the_default_command() {
+    let path = fixture("default.dat", &aes_builder().build().unwrap());
+    let (code, stdout) = run(&[&path]);
+    assert_eq!(code, 0);
+    assert!(stdout.ends_with("Done\n"), "{}", stdout);
+    assert_eq!(stdout, run(&["parse", &path]).1);
+
+    let (code, stdout) = run(&["help"]);
+    assert_eq!(code, 0);
+    assert!(stdout.starts_with("Usage: hsmattest_bin <command>"), "{}", stdout);
+    let (code, _) = run(&[&fixture("missing.dat", b"")[..], "--format", "toml"]);
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn signature_lengths_are_detected_without_a_key() {
+    for len in [384, 512] {
+        let bundle = rsa_builder().with_signature(vec![0x5a; len]).build().unwrap();
+        let path = fixture(&format!("signature-{}.dat", len), &bundle);
+        let (code, stdout) = run(&["parse", &path, "--format", "json"]);
+        assert_eq!(code, 0);
+        let document: Value = serde_json::from_str(&stdout).unwrap();
+        assert_eq!(document["header"]["signature_len"], len);
+        assert_eq!(document["signature"], "5a".repeat(len));
+    }
+
+    // a response payload that also reads as an attribute buffer, 128 bytes ahead of the real one;
+    // the owner key's size settles it
+    let mut payload = vec![0; 128];
+    payload[4..8].copy_from_slice(&[0, 8, 0, 0]);
+    payload[12..20].copy_from_slice(&[0, 0, 0, 1, 0, 0, 0, 8]);
+    let bundle = hsmattest::builder::AttestationBuilder::new()
+        .with_response_payload(payload)
+        .with_section(aes_section())
+        .with_signing_key(owner_key())
+        .build()
+        .unwrap();
+    let path = fixture("ambiguous.dat", &bundle);
+    let (code, _) = run(&["parse", &path]);
+    assert_eq!(code, 1);
+    let (code, stdout) = run(&["parse", &path, "--key", OWNER_PUBLIC_KEY]);
+    assert_eq!(code, 0);
+    assert!(stdout.contains("Signature verification = valid"), "{}", stdout);
+}
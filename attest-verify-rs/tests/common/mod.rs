@@ -0,0 +1,132 @@
+#![allow(dead_code)]
+
+use hsmattest::attestation::Attestation;
+use hsmattest::builder::{AttestationBuilder, KeySection, SigningKey};
+use hsmattest::error::ParseError;
+use hsmattest::pubkey::PublicKey;
+use hsmattest::tlv_mapping::*;
+use hsmattest::Machine;
+
+// a throwaway 2048 bit key, only ever used to sign these fixtures
+pub const OWNER_KEY: &str = include_str!("../fixtures/owner.key");
+
+pub fn owner_key() -> SigningKey {
+    SigningKey::from_pem(OWNER_KEY).expect("fixture key")
+}
+
+// the public half of a throwaway P-256 key, the one attested by the EC fixture
+pub const EC_KEY: &str = include_str!("../fixtures/ec.pub.pem");
+
+pub fn ec_key() -> PublicKey {
+    PublicKey::from_pem(EC_KEY).expect("fixture key")
+}
+
+pub fn parse(bundle: &[u8]) -> Result<Attestation, ParseError> {
+    let mut machine = Machine::new();
+    machine.run_buf(bundle);
+    machine.finish()
+}
+
+pub fn parse_bytewise(bundle: &[u8]) -> Result<Attestation, ParseError> {
+    let mut machine = Machine::new();
+    bundle.iter().for_each(|byte| machine.parse(*byte));
+    machine.finish()
+}
+
+/// `bundle` with `padding` in front of its first section and the key offsets moved past it. The
+/// signature is left as it is, so only for bundles built `with_signature`.
+pub fn padded(bundle: &[u8], signature_len: usize, padding: &[u8]) -> Vec<u8> {
+    let field = |at: usize| u32::from_be_bytes(bundle[at..at + 4].try_into().unwrap());
+    let (total_size, buff_size) = (field(8), field(12));
+    let attr_offset = (total_size - buff_size) as usize - signature_len;
+    let grow = padding.len() as u32;
+
+    let mut padded = bundle.to_vec();
+    padded[8..12].copy_from_slice(&(total_size + grow).to_be_bytes());
+    padded[12..16].copy_from_slice(&(buff_size + grow).to_be_bytes());
+    for at in [attr_offset + 4, attr_offset + 6] {
+        let offset = u16::from_be_bytes([bundle[at], bundle[at + 1]]);
+        if offset != 0 {
+            padded[at..at + 2].copy_from_slice(&(offset + grow as u16).to_be_bytes());
+        }
+    }
+    padded.splice(attr_offset + 8..attr_offset + 8, padding.iter().copied());
+    padded
+}
+
+fn header() -> AttestationBuilder {
+    AttestationBuilder::new()
+        .with_request_id(0x11)
+        .with_response_payload(b"firmware 3.4.0 partition 7".to_vec())
+        .with_attribute_format(0x0002_0001)
+}
+
+fn common(section: KeySection, class: ClassKey, key_type: KeyType) -> KeySection {
+    section
+        .with(TLVMapping::OBJ_ATTR_CLASS, TLVValue::ClassKey(class))
+        .with(TLVMapping::OBJ_ATTR_KEY_TYPE, TLVValue::KeyType(key_type))
+        .with_raw(TLVMapping::OBJ_ATTR_LABEL.to_int(), b"fixture-key\0".to_vec())
+        .with(TLVMapping::OBJ_ATTR_TOKEN, TLVValue::Bytes(Bytes("01".into())))
+        .with(TLVMapping::OBJ_ATTR_LOCAL, TLVValue::Bool(Bool(true)))
+}
+
+pub fn aes_section() -> KeySection {
+    common(KeySection::new(0x0020_0001), ClassKey::Secret, KeyType::Aes)
+        .with(TLVMapping::OBJ_ATTR_VALUE_LEN, TLVValue::Int(Int(32)))
+        .with(TLVMapping::OBJ_ATTR_SENSITIVE, TLVValue::Bool(Bool(true)))
+        .with(TLVMapping::OBJ_ATTR_EXTRACTABLE, TLVValue::Bool(Bool(false)))
+        .with(TLVMapping::OBJ_ATTR_NEVER_EXTRACTABLE, TLVValue::Bool(Bool(true)))
+        .with(TLVMapping::OBJ_ATTR_KCV, TLVValue::Bytes(Bytes("8a2f1c".into())))
+}
+
+pub fn rsa_sections() -> (KeySection, KeySection) {
+    let modulus = (0..256).map(|i| (i * 7 + 1) as u8).collect::<Vec<_>>();
+    let public = common(KeySection::new(0x0010_0010), ClassKey::Pubkey, KeyType::Rsa)
+        .with(TLVMapping::OBJ_ATTR_MODULUS, TLVValue::Bytes(Bytes(hsmattest::pubkey::to_hex(&modulus))))
+        .with(TLVMapping::OBJ_ATTR_MODULUS_BITS, TLVValue::Int(Int(2048)))
+        .with_raw(TLVMapping::OBJ_ATTR_PUBLIC_EXPONENT.to_int(), vec![0x01, 0x00, 0x01])
+        .with(TLVMapping::OBJ_ATTR_ID, TLVValue::HexStr128(HexStr128("ab".repeat(64))))
+        .with(TLVMapping::OBJ_ATTR_VERIFY, TLVValue::Bool(Bool(true)));
+    let private = common(KeySection::new(0x0010_0011), ClassKey::Privkey, KeyType::Rsa)
+        .with(TLVMapping::OBJ_ATTR_SIGN, TLVValue::Bool(Bool(true)))
+        .with(TLVMapping::OBJ_ATTR_EXTRACTABLE, TLVValue::Bool(Bool(false)))
+        .with(TLVMapping::OBJ_ATTR_NEVER_EXTRACTABLE, TLVValue::Bool(Bool(true)));
+    (public, private)
+}
+
+pub fn ec_sections() -> (KeySection, KeySection) {
+    let PublicKey::Ec { curve, point } = ec_key() else {
+        panic!("not an EC key");
+    };
+    let public = common(KeySection::new(0x0030_0010), ClassKey::Pubkey, KeyType::Ec)
+        .with(TLVMapping::OBJ_ATTR_EC_PARAMS, TLVValue::Curve(curve))
+        .with(TLVMapping::OBJ_ATTR_EC_POINT, TLVValue::EcPoint(EcPoint::new(point.x, point.y)))
+        .with(TLVMapping::OBJ_ATTR_VERIFY, TLVValue::Bool(Bool(true)));
+    let private = common(KeySection::new(0x0030_0011), ClassKey::Privkey, KeyType::Ec)
+        .with(TLVMapping::OBJ_ATTR_SIGN, TLVValue::Bool(Bool(true)))
+        .with(TLVMapping::OBJ_ATTR_DERIVE, TLVValue::Bool(Bool(false)));
+    (public, private)
+}
+
+pub fn aes_builder() -> AttestationBuilder {
+    header().with_section(aes_section()).with_signing_key(owner_key())
+}
+
+pub fn rsa_builder() -> AttestationBuilder {
+    let (public, private) = rsa_sections();
+    header().with_section(public).with_section(private).with_signing_key(owner_key())
+}
+
+pub fn ec_builder() -> AttestationBuilder {
+    let (public, private) = ec_sections();
+    header().with_section(public).with_section(private).with_signing_key(owner_key())
+}
+
+/// Every fixture, by the name of its golden file.
+pub fn bundles() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("aes", aes_builder().build().expect("aes bundle")),
+        ("rsa", rsa_builder().build().expect("rsa bundle")),
+        ("ec", ec_builder().build().expect("ec bundle")),
+    ]
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use common::*;
+use hsmattest::builder::AttestationBuilder;
+use hsmattest::error::ParseError;
+use hsmattest::gcp::{match_key_id, split_object_id, IdHalf, KeyVersionName};
+use hsmattest::tlv_mapping::{HexStr128, TLVMapping, TLVValue};
+use hsmattest::KeyMode;
+
+const NAME: &str = "projects/p/locations/l/keyRings/r/cryptoKeys/k/cryptoKeyVersions/1";
+// `echo -n "$NAME" | openssl dgst -sha256 -hex`
+const KEY_ID: &str = "49ca6c8e2a747a73e079314b3e23389338cbfc02a05202becfba92adb5373e3f";
+
+// the command from the KeyID section of docs/hsm-process.md, run as it is written there
+const TEMPLATE: &str = "projects/{{ project_name }}/locations/{{ location }}/keyRings/{{ keyring_name }}\
+                        /cryptoKeys/{{ cryptokey_name }}/cryptoKeyVersions/{{ version }}";
+const TEMPLATE_KEY_ID: &str = "26c769cd8ae654aff2f7c8b366f086a1da4525fff6f9e061ac92b3045346fbc7";
+
+// the symmetric fixture with `object_id` for its ObjAttrId
+fn matches(object_id: &str) -> Vec<(KeyMode, Option<IdHalf>)> {
+    let section = aes_section().with(TLVMapping::OBJ_ATTR_ID, TLVValue::HexStr128(HexStr128(object_id.into())));
+    let bundle = AttestationBuilder::new().with_section(section).with_signature(vec![0; 256]).build().unwrap();
+    let name = KeyVersionName::parse(NAME).unwrap();
+    match_key_id(&parse(&bundle).unwrap(), &name).into_iter().map(|m| (m.mode, m.matched)).collect()
+}
+
+#[test]
+fn key_ids_match_openssl() {
+    let name = KeyVersionName::parse(NAME).unwrap();
+    assert_eq!(name, KeyVersionName::new("p", "l", "r", "k", "1"));
+    assert_eq!(name.to_string(), NAME);
+    assert_eq!(name.key_id(), KEY_ID);
+    assert_eq!(KeyVersionName::parse(TEMPLATE).unwrap().key_id(), TEMPLATE_KEY_ID);
+}
+
+#[test]
+fn full_resource_names() {
+    let expected = KeyVersionName::parse(NAME).unwrap();
+    for name in [format!("//cloudkms.googleapis.com/{}", NAME), format!("/{}/", NAME), format!("  {}\n", NAME)] {
+        assert_eq!(KeyVersionName::parse(&name).unwrap(), expected, "{:?}", name);
+    }
+}
+
+#[test]
+fn malformed_names_are_rejected() {
+    for name in [
+        "",
+        "projects/p",
+        "projects/p/locations/l/keyRings/r/cryptoKeys/k",
+        "projects/p/locations/l/keyRings/r/cryptoKeys/k/cryptoKeyVersions",
+        "projects/p/locations/l/keyRings/r/cryptoKeys/k/cryptoKeyVersions/",
+        "projects/p/locations/l/keyRings/r/cryptoKeys/k/cryptoKeyVersions/1/extra/2",
+        "projects/p/locations/l/keyRings//cryptoKeys/k/cryptoKeyVersions/1",
+        "projects/p/locations/l/keyrings/r/cryptoKeys/k/cryptoKeyVersions/1",
+        "locations/l/projects/p/keyRings/r/cryptoKeys/k/cryptoKeyVersions/1",
+        "//storage.googleapis.com/projects/p/locations/l/keyRings/r/cryptoKeys/k/cryptoKeyVersions/1",
+    ] {
+        let err = KeyVersionName::parse(name).unwrap_err();
+        assert!(matches!(&err, ParseError::InvalidKeyName(n) if n == name), "{:?}: {:?}", name, err);
+    }
+}
+
+#[test]
+fn object_ids_split_into_two_digests() {
+    let id = format!("{}{}", KEY_ID, TEMPLATE_KEY_ID);
+    assert_eq!(split_object_id(&id), Some((KEY_ID, TEMPLATE_KEY_ID)));
+    assert_eq!(split_object_id(KEY_ID), None);
+    assert_eq!(split_object_id(&format!("{}00", id)), None);
+    assert_eq!(split_object_id(&id[..127]), None);
+    // the right length in bytes, but not in characters
+    assert_eq!(split_object_id(&format!("{}é", &id[..126])), None);
+}
+
+#[test]
+fn either_half_of_the_object_id_matches() {
+    let other = "ab".repeat(32);
+    assert_eq!(matches(&format!("{}{}", KEY_ID, other)), [(KeyMode::Primary, Some(IdHalf::First))]);
+    assert_eq!(matches(&format!("{}{}", other, KEY_ID)), [(KeyMode::Primary, Some(IdHalf::Second))]);
+    assert_eq!(matches(&format!("{}{}", other, KEY_ID.to_uppercase())), [(KeyMode::Primary, Some(IdHalf::Second))]);
+    assert_eq!(matches(&format!("{}{}", other, other)), [(KeyMode::Primary, None)]);
+}
+
+#[test]
+fn object_ids_of_the_wrong_length_never_match() {
+    assert_eq!(matches(KEY_ID), [(KeyMode::Primary, None)]);
+    assert_eq!(matches(&format!("{}{}00", KEY_ID, KEY_ID)), [(KeyMode::Primary, None)]);
+    assert_eq!(matches(&format!("{}{}", KEY_ID, &KEY_ID[..62])), [(KeyMode::Primary, None)]);
+}
+
+#[test]
+fn only_sections_with_an_object_id_are_checked() {
+    let name = KeyVersionName::parse(NAME).unwrap();
+    assert!(match_key_id(&parse(&aes_builder().build().unwrap()).unwrap(), &name).is_empty());
+
+    let checked = match_key_id(&parse(&rsa_builder().build().unwrap()).unwrap(), &name);
+    assert_eq!(checked.len(), 1);
+    assert_eq!(checked[0].mode, KeyMode::Primary);
+    assert!(!checked[0].is_match());
+    assert_eq!(checked[0].outcome(), format!("mismatch (expected {}, attested {})", KEY_ID, "ab".repeat(64)));
+}
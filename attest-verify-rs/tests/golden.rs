@@ -0,0 +1,179 @@
+mod common;
+
+use common::*;
+use hsmattest::builder::{AttestationBuilder, KeySection};
+use hsmattest::pkcs11::NameStyle;
+use hsmattest::tlv_mapping::*;
+use hsmattest::attestation::Attestation;
+use hsmattest::writer::{Writer, JSON_SCHEMA};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+
+/* The JSON for each fixture is compared byte for byte with tests/golden/<name>.json.
+ * After an intended change to the output, regenerate them with
+ *   UPDATE_GOLDEN=1 cargo test --test golden
+ * and review the diff.
+ * */
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.json", name))
+}
+
+#[test]
+fn json_matches_golden_files() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    for (name, bundle) in bundles() {
+        let attestation = parse(&bundle).unwrap();
+        let json = Writer::new(&attestation).to_json_bytes();
+        let path = golden_path(name);
+
+        if update {
+            fs::write(&path, &json).unwrap();
+            continue;
+        }
+        let golden = fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert!(
+            json == golden,
+            "{} differs from {}:\n{}",
+            name,
+            path.display(),
+            String::from_utf8_lossy(&json)
+        );
+    }
+}
+
+// the property names of an object in the schema, all of them required
+fn schema_keys(schema: &Value) -> Vec<String> {
+    let mut keys = schema["properties"].as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+    let required = schema["required"].as_array().unwrap();
+    let mut required = required.iter().map(|key| key.as_str().unwrap()).collect::<Vec<_>>();
+    keys.sort();
+    required.sort();
+    assert_eq!(keys, required);
+    keys
+}
+
+fn keys(document: &Value) -> Vec<String> {
+    document.as_object().unwrap().keys().cloned().collect()
+}
+
+#[test]
+fn json_follows_the_schema() {
+    let schema: Value = serde_json::from_str(JSON_SCHEMA).unwrap();
+    let empty = Attestation::default();
+    let mut documents = vec![Writer::new(&empty).to_json()];
+    for (_, bundle) in bundles() {
+        documents.push(Writer::new(&parse(&bundle).unwrap()).with_pair("Signature", "valid").to_json());
+    }
+
+    for document in &documents {
+        assert_eq!(keys(document), schema_keys(&schema));
+        assert_eq!(keys(&document["header"]), schema_keys(&schema["properties"]["header"]));
+        for section in document["sections"].as_array().unwrap() {
+            assert_eq!(keys(section), schema_keys(&schema["$defs"]["section"]));
+            for attr in section["attributes"].as_array().unwrap() {
+                assert_eq!(keys(attr), schema_keys(&schema["$defs"]["attribute"]));
+            }
+        }
+    }
+}
+
+#[test]
+fn json_of_an_empty_attestation() {
+    let attestation = Attestation::default();
+    let json: Value = serde_json::from_slice(&Writer::new(&attestation).to_json_bytes()).unwrap();
+    assert_eq!(json["sections"], json!([]));
+    assert_eq!(json["signature"], "");
+    assert_eq!(json["extra"], json!([]));
+    assert_eq!(json["header"]["total_size"], 0);
+}
+
+#[test]
+fn json_strings_are_escaped() {
+    let label = "a \"quoted\" label\\with a backslash\n";
+    let section = aes_section().with_raw(TLVMapping::OBJ_ATTR_LABEL.to_int(), label.as_bytes().to_vec());
+    let bundle = AttestationBuilder::new().with_section(section).with_signature(vec![0; 256]).build().unwrap();
+    let attestation = parse(&bundle).unwrap();
+    let bytes = Writer::new(&attestation).with_pair("Note \"1\"", "C:\\keys").to_json_bytes();
+
+    let json: Value = serde_json::from_slice(&bytes).unwrap();
+    let attributes = json["sections"][0]["attributes"].as_array().unwrap();
+    assert_eq!(attributes.last().unwrap()["value"], label);
+    assert_eq!(json["extra"], json!([{ "name": "Note \"1\"", "value": "C:\\keys" }]));
+}
+
+#[test]
+fn json_extra_pairs_follow_the_signature() {
+    let attestation = parse(&aes_builder().build().unwrap()).unwrap();
+    let json = Writer::new(&attestation).with_pair("Signature", "valid").to_json();
+
+    assert_eq!(json["extra"], json!([{ "name": "Signature", "value": "valid" }]));
+    assert_eq!(json["mode"], "Symmetric");
+    assert_eq!(json["sections"][0]["attributes"][0]["name"], "ObjAttrClass");
+    assert_eq!(json["sections"][0]["attributes"][0]["value"], "secret-key (symmetric)");
+}
+
+#[test]
+fn json_extra_pairs_keep_repeated_names() {
+    let attestation = parse(&rsa_builder().build().unwrap()).unwrap();
+    let writer = Writer::new(&attestation)
+        .with_pair("KeyID", "match (first half)")
+        .with_pair("Signature", "valid")
+        .with_pair("KeyID", "match (second half)");
+
+    let expected = json!([
+        { "name": "KeyID", "value": "match (first half)" },
+        { "name": "Signature", "value": "valid" },
+        { "name": "KeyID", "value": "match (second half)" },
+    ]);
+    assert_eq!(writer.to_json()["extra"], expected);
+    assert!(writer.to_text().ends_with("KeyID = match (first half)\nSignature = valid\nKeyID = match (second half)\n"));
+}
+
+#[test]
+fn json_and_text_explain_each_attribute() {
+    let section = aes_section().with(TLVMapping::OBJ_ATTR_NEVER_EXTRACTABLE, TLVValue::Bool(Bool(false)));
+    let bundle = AttestationBuilder::new().with_section(section).with_signature(vec![0; 256]).build().unwrap();
+    let attestation = parse(&bundle).unwrap();
+    let writer = Writer::new(&attestation);
+
+    let json = writer.to_json();
+    let attributes = json["sections"][0]["attributes"].as_array().unwrap();
+    assert_eq!(attributes[0]["description"], "Class type of the key.");
+    assert_eq!(attributes[0]["relevance"], "low");
+    let weak = attributes.iter().filter(|attr| attr["weakens"] == true).collect::<Vec<_>>();
+    assert_eq!(weak.len(), 1);
+    assert_eq!(weak[0]["name"], "ObjAttrNeverExtractable");
+
+    let text = writer.to_text();
+    assert!(text.contains("    [high] Indicates if key can never be extracted. <- weakens the key\n"), "{}", text);
+    assert_eq!(text.matches("weakens the key").count(), 1);
+}
+
+#[test]
+fn attributes_can_be_named_as_in_pkcs11() {
+    let attestation = parse(&aes_builder().build().unwrap()).unwrap();
+    let writer = Writer::new(&attestation).with_names(NameStyle::Pkcs11);
+
+    let json = writer.to_json();
+    let names = json["sections"][0]["attributes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|attr| attr["name"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names[..3], ["CKA_CLASS", "CKA_KEY_TYPE", "CKA_LABEL"]);
+    assert!(names.contains(&"CKA_CHECK_VALUE".to_string()), "{:?}", names);
+    assert!(writer.to_text().contains("Type = 0162  CKA_EXTRACTABLE Len = 1,  \"false\"\n"));
+
+    let value_len = |len| {
+        let section = KeySection::new(1).with(TLVMapping::OBJ_ATTR_VALUE_LEN, TLVValue::Int(Int(len)));
+        let bundle = AttestationBuilder::new().with_section(section).with_signature(vec![0; 256]).build();
+        parse(&bundle.unwrap()).unwrap()
+    };
+    let diffs = value_len(16).diff(&value_len(32));
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].to_line(NameStyle::Pkcs11), r#"~ Primary CKA_VALUE_LEN: "16" -> "32""#);
+    assert_eq!(diffs[0].to_string(), r#"~ Primary ObjAttrValueLen: "16" -> "32""#);
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::*;
+use hsmattest::error::ParseError;
+use hsmattest::gzip::{Decoder, GZIP_MAGIC};
+use miniz_oxide::deflate::compress_to_vec;
+
+const FNAME: u8 = 1 << 3;
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in bytes {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// a single gzip member as `gzip` writes it, the file name in the header
+fn gzip(bundle: &[u8]) -> Vec<u8> {
+    let mut member = GZIP_MAGIC.to_vec();
+    member.extend_from_slice(&[8, FNAME, 0, 0, 0, 0, 0, 3]);
+    member.extend_from_slice(b"rsa.compressed.dat\0");
+    member.extend(compress_to_vec(bundle, 6));
+    member.extend_from_slice(&crc32(bundle).to_le_bytes());
+    member.extend_from_slice(&(bundle.len() as u32).to_le_bytes());
+    member
+}
+
+fn decode_chunks(input: &[u8], size: usize) -> Result<(Vec<u8>, bool), ParseError> {
+    let mut decoder = Decoder::new();
+    let mut output = Vec::new();
+    for chunk in input.chunks(size) {
+        decoder.feed(chunk, |bytes| output.extend_from_slice(bytes))?;
+    }
+    decoder.finish(|bytes| output.extend_from_slice(bytes))?;
+    Ok((output, decoder.is_compressed()))
+}
+
+fn decode(input: &[u8]) -> Result<(Vec<u8>, bool), ParseError> {
+    decode_chunks(input, input.len().max(1))
+}
+
+#[test]
+fn plain_bundles_pass_through() {
+    let bundle = rsa_builder().build().unwrap();
+    assert_eq!(decode(&bundle).unwrap(), (bundle.clone(), false));
+    assert_eq!(decode_chunks(&bundle, 1).unwrap(), (bundle, false));
+
+    // a lone first magic byte is held back, then handed on
+    assert_eq!(decode(&GZIP_MAGIC[..1]).unwrap(), (GZIP_MAGIC[..1].to_vec(), false));
+    assert_eq!(decode_chunks(&[GZIP_MAGIC[0], 0], 1).unwrap(), (vec![GZIP_MAGIC[0], 0], false));
+}
+
+#[test]
+fn gzipped_bundles_inflate() {
+    let bundle = rsa_builder().build().unwrap();
+    let member = gzip(&bundle);
+    assert_eq!(decode(&member).unwrap(), (bundle.clone(), true));
+    assert_eq!(parse(&decode(&member).unwrap().0).unwrap(), parse(&bundle).unwrap());
+    for size in [1, 2, 3, 7, 64] {
+        assert_eq!(decode_chunks(&member, size).unwrap(), (bundle.clone(), true), "in chunks of {}", size);
+    }
+}
+
+#[test]
+fn magic_split_across_chunks() {
+    let bundle = aes_builder().build().unwrap();
+    let member = gzip(&bundle);
+    let mut decoder = Decoder::new();
+    let mut output = Vec::new();
+    decoder.feed(&member[..1], |bytes| output.extend_from_slice(bytes)).unwrap();
+    assert!(output.is_empty());
+    assert!(!decoder.is_compressed());
+    decoder.feed(&member[1..], |bytes| output.extend_from_slice(bytes)).unwrap();
+    decoder.finish(|bytes| output.extend_from_slice(bytes)).unwrap();
+    assert!(decoder.is_compressed());
+    assert_eq!(output, bundle);
+}
+
+#[test]
+fn corrupted_trailers_are_rejected() {
+    let member = gzip(&aes_builder().build().unwrap());
+    let len = member.len();
+
+    let mut crc = member.clone();
+    crc[len - 8] ^= 1;
+    let err = decode(&crc).unwrap_err();
+    assert!(matches!(&err, ParseError::Decompression(reason) if reason == "crc32 mismatch"), "{:?}", err);
+
+    let mut size = member;
+    size[len - 1] ^= 1;
+    let err = decode(&size).unwrap_err();
+    assert!(matches!(&err, ParseError::Decompression(reason) if reason == "length mismatch"), "{:?}", err);
+}
+
+#[test]
+fn truncated_members_fail_on_finish() {
+    let member = gzip(&aes_builder().build().unwrap());
+    // in the trailer, in the deflate stream and in the header
+    for len in [member.len() - 1, member.len() / 2, 5] {
+        let mut decoder = Decoder::new();
+        decoder.feed(&member[..len], |_| {}).unwrap();
+        let err = decoder.finish(|_| {}).unwrap_err();
+        assert!(matches!(err, ParseError::Decompression(_)), "{}: {:?}", len, err);
+    }
+}
+
+#[test]
+fn trailing_bytes_are_rejected() {
+    let mut member = gzip(&aes_builder().build().unwrap());
+    member.push(0);
+    let err = decode(&member).unwrap_err();
+    let trailing = "trailing data after gzip member";
+    assert!(matches!(&err, ParseError::Decompression(reason) if reason == trailing), "{:?}", err);
+}
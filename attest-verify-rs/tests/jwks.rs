@@ -0,0 +1,79 @@
+mod common;
+
+use common::*;
+use hsmattest::error::ParseError;
+use hsmattest::jwks::{match_attestation, Jwks};
+use hsmattest::pubkey::PublicKey;
+use hsmattest::verify::{AttestationKey, SignatureVerdict};
+use serde_json::json;
+
+const OWNER_PUBLIC_KEY: &str = include_str!("fixtures/owner.pub.pem");
+
+// a set as a service would publish it: the EC fixture key, a key no fixture attests, one without a
+// kid and a symmetric key
+fn jwks() -> Jwks {
+    let PublicKey::Rsa { modulus, .. } = PublicKey::from_pem(OWNER_PUBLIC_KEY).unwrap() else {
+        panic!("not an RSA key");
+    };
+    let mut ec = ec_key().to_jwk();
+    ec["kid"] = json!("ec-signing");
+    let mut other = PublicKey::from_rsa_components(&modulus, &[3]).to_jwk();
+    other["kid"] = json!("retired");
+    let mut anonymous = PublicKey::from_pem(OWNER_PUBLIC_KEY).unwrap().to_jwk();
+    anonymous.as_object_mut().unwrap().remove("kid");
+    let document = json!({ "keys": [other, ec, anonymous, { "kty": "oct", "k": "c2VjcmV0" }] });
+    Jwks::from_json(&document.to_string()).unwrap()
+}
+
+#[test]
+fn keys_decode_from_the_set() {
+    let jwks = jwks();
+    assert_eq!(jwks.keys.len(), 4);
+    assert_eq!(jwks.keys[1].key.as_ref().unwrap(), &ec_key());
+    assert_eq!(jwks.keys[1].to_string(), "kid 'ec-signing'");
+    assert_eq!(jwks.keys[2].to_string(), "key #2");
+    assert!(matches!(jwks.keys[3].key, Err(ParseError::UnsupportedKeyFormat(_))), "{:?}", jwks.keys[3]);
+
+    let err = Jwks::from_json(r#"{"kty": "RSA"}"#).unwrap_err();
+    assert!(matches!(err, ParseError::InvalidJwks(_)), "{:?}", err);
+}
+
+#[test]
+fn attested_key_is_found_with_its_verdict() {
+    let jwks = jwks();
+    let owner = AttestationKey::from_pem(OWNER_PUBLIC_KEY).unwrap();
+    let bundle = ec_builder().build().unwrap();
+    let found = match_attestation(&jwks, &parse(&bundle).unwrap(), &bundle, Some(&owner)).unwrap();
+    assert_eq!((found.index, found.kid.as_deref()), (Some(1), Some("ec-signing")));
+    assert_eq!(found.verdict, Some(SignatureVerdict::Valid));
+    assert_eq!(found.to_string(), "JWK = match (kid 'ec-signing'), signature valid");
+
+    let mut tampered = bundle.clone();
+    tampered[5] ^= 1;
+    let found = match_attestation(&jwks, &parse(&bundle).unwrap(), &tampered, Some(&owner)).unwrap();
+    assert!(found.is_match());
+    assert_eq!(found.verdict, Some(SignatureVerdict::Invalid));
+    // a key is only accounted for by an attestation whose signature holds
+    assert_eq!(jwks.unmatched(&[found]).len(), 4);
+}
+
+#[test]
+fn keys_without_an_attestation() {
+    let jwks = jwks();
+    let matches = [ec_builder(), rsa_builder()]
+        .into_iter()
+        .map(|builder| {
+            let bundle = builder.build().unwrap();
+            match_attestation(&jwks, &parse(&bundle).unwrap(), &bundle, None).unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert!(matches[0].is_match());
+    assert!(!matches[1].is_match());
+    assert_eq!(matches[1].outcome(), "no key in the set matches");
+
+    let unmatched = jwks.unmatched(&matches).iter().map(|jwk| jwk.index).collect::<Vec<_>>();
+    assert_eq!(unmatched, [0, 2, 3]);
+
+    let err = match_attestation(&jwks, &parse(&aes_builder().build().unwrap()).unwrap(), &[], None).unwrap_err();
+    assert!(matches!(err, ParseError::NoPublicKey), "{:?}", err);
+}
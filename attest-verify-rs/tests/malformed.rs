@@ -0,0 +1,182 @@
+mod common;
+
+use common::*;
+use hsmattest::builder::{AttestationBuilder, KeySection};
+use hsmattest::error::ParseError;
+use hsmattest::tlv_mapping::TLVMapping;
+use hsmattest::KeyMode;
+
+#[test]
+fn truncated_bundle() {
+    let bundle = rsa_builder().build().unwrap();
+    let err = parse(&bundle[..bundle.len() - 10]).unwrap_err();
+    assert!(matches!(err, ParseError::Truncated { .. }), "{:?}", err);
+}
+
+#[test]
+fn trailing_data() {
+    let mut bundle = rsa_builder().build().unwrap();
+    bundle.push(0);
+    let err = parse(&bundle).unwrap_err();
+    assert!(matches!(err, ParseError::TrailingData { .. }), "{:?}", err);
+}
+
+#[test]
+fn empty_section() {
+    let bundle = AttestationBuilder::new()
+        .with_section(KeySection::new(1))
+        .with_signature(vec![0; 256])
+        .build()
+        .unwrap();
+    let mut machine = hsmattest::Machine::new_with_signature_len(256);
+    machine.run_buf(&bundle);
+    let err = machine.finish().unwrap_err();
+    assert!(matches!(err, ParseError::AttributeCountMismatch { declared: 0, .. }), "{:?}", err);
+
+    // nor does it give detection a section to go on
+    let err = parse(&bundle).unwrap_err();
+    assert!(matches!(err, ParseError::UnknownSignatureLength), "{:?}", err);
+}
+
+#[test]
+fn overlapping_sections() {
+    let bundle = rsa_builder().with_key_offsets(8, 12).build().unwrap();
+    let mut machine = hsmattest::Machine::new_with_signature_len(256);
+    machine.run_buf(&bundle);
+
+    let err = machine.finish().unwrap_err();
+    assert!(matches!(err, ParseError::SectionOverlap { mode: KeyMode::Secondary, .. }), "{:?}", err);
+}
+
+fn pinned(bundle: &[u8]) -> ParseError {
+    let mut machine = hsmattest::Machine::new_with_signature_len(256);
+    machine.run_buf(bundle);
+    machine.finish().unwrap_err()
+}
+
+#[test]
+fn sections_out_of_range() {
+    let err = pinned(&aes_builder().with_key_offsets(4, 0).build().unwrap());
+    assert!(matches!(err, ParseError::SectionOverlap { mode: KeyMode::Primary, offset: 46, .. }), "{:?}", err);
+
+    let err = pinned(&rsa_builder().with_key_offsets(8, 60000).build().unwrap());
+    assert!(matches!(err, ParseError::SectionOutOfRange { mode: KeyMode::Secondary, .. }), "{:?}", err);
+}
+
+// the first section's attribute size, just past its object handle and attribute count
+const FIRST_ATTR_SIZE: usize = 16 + 26 + 8 + 8;
+
+// `builder`'s bundle with the first section claiming one byte more than it has
+fn oversized_first_section(builder: AttestationBuilder) -> Vec<u8> {
+    let mut bundle = builder.build().unwrap();
+    let size = u32::from_be_bytes(bundle[FIRST_ATTR_SIZE..FIRST_ATTR_SIZE + 4].try_into().unwrap());
+    bundle[FIRST_ATTR_SIZE..FIRST_ATTR_SIZE + 4].copy_from_slice(&u32::to_be_bytes(size + 1));
+    bundle
+}
+
+#[test]
+fn attribute_sizes_are_checked_against_the_next_section() {
+    // past the end of the only section
+    let err = pinned(&oversized_first_section(aes_builder()));
+    assert!(matches!(err, ParseError::SectionOutOfRange { mode: KeyMode::Primary, .. }), "{:?}", err);
+    // into the secondary section
+    let err = pinned(&oversized_first_section(rsa_builder()));
+    assert!(matches!(err, ParseError::SectionOverlap { mode: KeyMode::Secondary, .. }), "{:?}", err);
+}
+
+#[test]
+fn total_size_out_of_range() {
+    let bundle = aes_builder().with_total_size(64).build().unwrap();
+    let err = parse(&bundle).unwrap_err();
+    assert!(matches!(err, ParseError::OffsetOutOfRange { .. }), "{:?}", err);
+}
+
+#[test]
+fn builder_needs_sections_and_a_signature() {
+    let err = AttestationBuilder::new().with_signature(vec![0; 256]).build().unwrap_err();
+    assert!(matches!(err, ParseError::SectionCount(0)), "{:?}", err);
+
+    let err = AttestationBuilder::new().with_section(aes_section()).build().unwrap_err();
+    assert!(matches!(err, ParseError::MissingArg(_)), "{:?}", err);
+}
+
+// header, attribute format and offsets, then one section of `attr_count` attributes whose first
+// TLV claims `tlv_len` bytes; only the first few bytes are actually there
+fn oversized(buff_size: u32, attr_count: u32, attr_size: u32, tlv_len: u32) -> Vec<u8> {
+    let total_size = 24 + buff_size as u64 + 256;
+    let mut bundle = Vec::new();
+    for field in [0, 1, total_size as u32, buff_size, 0, 0, 0x0002_0001] {
+        bundle.extend_from_slice(&field.to_be_bytes());
+    }
+    bundle.extend_from_slice(&[0, 8, 0, 0]);
+    for field in [1, attr_count, attr_size, TLVMapping::OBJ_ATTR_MODULUS.to_int(), tlv_len] {
+        bundle.extend_from_slice(&field.to_be_bytes());
+    }
+    bundle.extend_from_slice(&[0; 64]);
+    bundle
+}
+
+#[test]
+fn lengths_past_i32_max_do_not_panic() {
+    let bundle = oversized(0x8000_1000, 1, 0x8000_0f00, 0x8000_0001);
+    assert_eq!(parse(&bundle).unwrap_err().to_string(), parse_bytewise(&bundle).unwrap_err().to_string());
+    assert!(matches!(parse(&bundle).unwrap_err(), ParseError::Truncated { state: hsmattest::State::TLVValue, .. }));
+}
+
+#[test]
+fn huge_attribute_counts_are_not_allocated_up_front() {
+    let bundle = oversized(0xf000_0000, 0x1000_0000, 0xe000_0000, 16);
+    let err = parse(&bundle).unwrap_err();
+    assert!(matches!(err, ParseError::Truncated { .. }), "{:?}", err);
+}
+
+#[test]
+fn input_that_stops_short_of_the_attributes() {
+    let bundle = aes_builder().build().unwrap();
+    let err = parse(&bundle[..30]).unwrap_err();
+    assert!(matches!(err, ParseError::Truncated { offset: 30, .. }), "{:?}", err);
+}
+
+// the first section's attribute count, just past the payload, the buffer header and the object handle
+const FIRST_ATTR_COUNT: usize = 16 + 26 + 8 + 4;
+
+#[test]
+fn attribute_counts_that_do_not_match_the_buffer() {
+    let bundle = rsa_builder().build().unwrap();
+    let declared = u32::from_be_bytes(bundle[FIRST_ATTR_COUNT..FIRST_ATTR_COUNT + 4].try_into().unwrap());
+    assert_eq!(declared, 10);
+
+    // one attribute left over in the buffer, or one too few to fill the count
+    for (count, parsed) in [(9, 9), (11, 10)] {
+        let mut miscounted = bundle.clone();
+        miscounted[FIRST_ATTR_COUNT..FIRST_ATTR_COUNT + 4].copy_from_slice(&u32::to_be_bytes(count));
+        let mut machine = hsmattest::Machine::new_with_signature_len(256);
+        machine.run_buf(&miscounted);
+        let err = machine.finish().unwrap_err();
+        let expected = ParseError::AttributeCountMismatch { declared: count, parsed };
+        assert_eq!(err.to_string(), expected.to_string());
+    }
+}
+
+#[test]
+fn every_prefix_is_truncated() {
+    for (name, bundle) in bundles() {
+        for len in 0..bundle.len() {
+            let err = parse(&bundle[..len]).unwrap_err();
+            assert!(matches!(err, ParseError::Truncated { .. }), "{} cut at {}: {:?}", name, len, err);
+            assert_eq!(err.to_string(), parse_bytewise(&bundle[..len]).unwrap_err().to_string());
+        }
+    }
+}
+
+#[test]
+fn errors_say_where_the_bundle_went_wrong() {
+    let bundle = aes_builder().build().unwrap();
+    let err = parse(&bundle[..30]).unwrap_err();
+    assert_eq!(err.to_string(), "Bundle truncated in state SkipToOffset after 30 bytes");
+
+    let mut trailing = bundle.clone();
+    trailing.extend_from_slice(&[0; 3]);
+    let err = parse(&trailing).unwrap_err();
+    assert_eq!(err.to_string(), format!("Unexpected data after the signature at offset {}", bundle.len()));
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::*;
+use hsmattest::attestation::{AttestationHeader, Attribute};
+use hsmattest::error::ParseError;
+use hsmattest::observer::AttestationObserver;
+use hsmattest::{KeyMode, Machine};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Header(AttestationHeader),
+    Section(KeyMode),
+    Attribute(KeyMode, Attribute),
+    Signature(Vec<u8>),
+    Error(String),
+}
+
+#[derive(Clone, Default)]
+struct Recorder(Rc<RefCell<Vec<Event>>>);
+
+impl Recorder {
+    fn events(&self) -> Vec<Event> {
+        self.0.borrow().clone()
+    }
+}
+
+impl AttestationObserver for Recorder {
+    fn on_header(&mut self, header: &AttestationHeader) {
+        self.0.borrow_mut().push(Event::Header(header.clone()));
+    }
+
+    fn on_key_section_start(&mut self, mode: KeyMode) {
+        self.0.borrow_mut().push(Event::Section(mode));
+    }
+
+    fn on_attribute(&mut self, mode: KeyMode, attribute: &Attribute) {
+        self.0.borrow_mut().push(Event::Attribute(mode, attribute.clone()));
+    }
+
+    fn on_signature(&mut self, signature: &[u8]) {
+        self.0.borrow_mut().push(Event::Signature(signature.to_vec()));
+    }
+
+    fn on_error(&mut self, error: &ParseError) {
+        self.0.borrow_mut().push(Event::Error(error.to_string()));
+    }
+}
+
+// counts signatures and leaves every other event to the defaults
+#[derive(Clone, Default)]
+struct Signatures(Rc<RefCell<usize>>);
+
+impl AttestationObserver for Signatures {
+    fn on_signature(&mut self, _signature: &[u8]) {
+        *self.0.borrow_mut() += 1;
+    }
+}
+
+#[test]
+fn events_follow_the_bundle() {
+    for (name, bundle) in bundles() {
+        let recorder = Recorder::default();
+        let mut machine = Machine::new().with_observer(recorder.clone());
+        machine.run_buf(&bundle);
+        let attestation = machine.finish().unwrap();
+
+        let mut expected = vec![Event::Header(attestation.header.clone())];
+        for section in &attestation.sections {
+            expected.push(Event::Section(section.mode));
+            let attributes = section.attributes.iter().map(|attr| Event::Attribute(section.mode, attr.clone()));
+            expected.extend(attributes);
+        }
+        expected.push(Event::Signature(attestation.signature.clone()));
+        assert_eq!(recorder.events(), expected, "{}", name);
+    }
+}
+
+#[test]
+fn every_observer_is_told() {
+    let (first, second, signatures) = (Recorder::default(), Recorder::default(), Signatures::default());
+    let mut machine = Machine::new().with_observer(first.clone()).with_observer(signatures.clone());
+    machine.add_observer(Box::new(second.clone()));
+    machine.run_buf(&rsa_builder().build().unwrap());
+    machine.finish().unwrap();
+
+    assert!(!first.events().is_empty());
+    assert_eq!(first.events(), second.events());
+    assert_eq!(*signatures.0.borrow(), 1);
+}
+
+#[test]
+fn only_the_first_error_is_reported() {
+    let mut bundle = aes_builder().build().unwrap();
+    bundle.extend_from_slice(&[0; 16]);
+    let recorder = Recorder::default();
+    let mut machine = Machine::new().with_observer(recorder.clone());
+    machine.run_buf(&bundle);
+    let err = machine.finish().unwrap_err();
+
+    let events = recorder.events();
+    let errors = events.iter().filter(|event| matches!(event, Event::Error(_))).collect::<Vec<_>>();
+    assert_eq!(errors, [&Event::Error(err.to_string())]);
+    assert_eq!(events.last(), Some(&Event::Error(err.to_string())));
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use common::*;
+use hsmattest::error::ParseError;
+use hsmattest::pkcs11::NameStyle;
+use hsmattest::policy::{Assertion, Expected, Policy, PolicyReport};
+use hsmattest::tlv_mapping::TLVMapping;
+use hsmattest::KeyMode;
+
+const HSM_GENERATED: &str = include_str!("../policies/hsm-generated.toml");
+
+fn evaluate(policy: &Policy, bundle: &[u8]) -> PolicyReport {
+    policy.evaluate(&parse(bundle).unwrap())
+}
+
+// (attribute, passed) for every assertion, in the order they were evaluated
+fn outcomes(report: &PolicyReport) -> Vec<(TLVMapping, bool)> {
+    report.results.iter().map(|result| (result.mapping, result.passed)).collect()
+}
+
+fn invalid(err: ParseError) -> String {
+    match err {
+        ParseError::InvalidPolicy(reason) => reason,
+        err => panic!("not a policy error: {:?}", err),
+    }
+}
+
+#[test]
+fn toml_and_json_read_the_same() {
+    let toml = Policy::from_toml(
+        r#"
+        [primary]
+        ObjAttrValueLen = { equals = 32 }
+        [secondary]
+        OBJ_ATTR_LOCAL = { equals = true }
+        CKA_KEY_TYPE = { one_of = ["rsa", "ec"] }
+        "#,
+    )
+    .unwrap();
+    let json = Policy::from_json(
+        r#"{
+            "primary": { "ObjAttrValueLen": { "equals": 32 } },
+            "secondary": { "OBJ_ATTR_LOCAL": { "equals": true }, "CKA_KEY_TYPE": { "one_of": ["rsa", "ec"] } }
+        }"#,
+    )
+    .unwrap();
+
+    let bundle = rsa_builder().build().unwrap();
+    let (toml, json) = (evaluate(&toml, &bundle), evaluate(&json, &bundle));
+    assert_eq!(outcomes(&toml), outcomes(&json));
+    let first = &toml.results[0];
+    assert_eq!((first.mode, first.mapping), (KeyMode::Primary, TLVMapping::OBJ_ATTR_VALUE_LEN));
+    assert_eq!(first.assertion, Assertion::Equals(Expected::Int(32)));
+    // an RSA key has no value length
+    assert_eq!(first.actual, None);
+    assert!(!first.passed);
+}
+
+#[test]
+fn assertions() {
+    let policy = Policy::from_toml(
+        r#"
+        [primary]
+        ObjAttrClass = { equals = "secret-key (symmetric)" }
+        ObjAttrValueLen = { equals = 32 }
+        ObjAttrSensitive = { equals = true }
+        ObjAttrKeyType = { one_of = ["des3", "aes"] }
+        ObjAttrLabel = { present = true }
+        ObjAttrModulus = { present = false }
+        ObjAttrKcv = { hex_prefix = "8A2F" }
+        "#,
+    )
+    .unwrap();
+    let report = evaluate(&policy, &aes_builder().build().unwrap());
+    assert!(report.passed(), "{:?}", report);
+
+    let policy = Policy::from_toml(
+        r#"
+        [primary]
+        ObjAttrClass = { equals = "private-key" }
+        ObjAttrValueLen = { equals = 16 }
+        ObjAttrSensitive = { equals = false }
+        ObjAttrKeyType = { one_of = ["rsa", "ec"] }
+        ObjAttrLabel = { present = false }
+        ObjAttrModulus = { present = true }
+        ObjAttrKcv = { hex_prefix = "2f" }
+        "#,
+    )
+    .unwrap();
+    let report = evaluate(&policy, &aes_builder().build().unwrap());
+    assert_eq!(report.failures().count(), 7, "{:?}", report);
+}
+
+#[test]
+fn symmetric_bundles_have_no_secondary_section() {
+    let policy = Policy::from_toml("[secondary]\nObjAttrLocal = { equals = true }\n").unwrap();
+    let report = evaluate(&policy, &aes_builder().build().unwrap());
+    assert!(!report.passed());
+    assert_eq!(report.results[0].actual, None);
+    assert_eq!(report.results[0].to_string(), "FAIL Secondary ObjAttrLocal equals true (actual: absent)");
+    let line = report.results[0].to_line(NameStyle::Pkcs11);
+    assert_eq!(line, "FAIL Secondary CKA_LOCAL equals true (actual: absent)");
+
+    let policy = Policy::from_toml("[primary]\nObjAttrLocal = { equals = true }\n").unwrap();
+    assert!(evaluate(&policy, &aes_builder().build().unwrap()).passed());
+}
+
+#[test]
+fn unknown_attributes_are_rejected() {
+    let reason = invalid(Policy::from_toml("[secondary]\nObjAttrLocl = { equals = true }\n").unwrap_err());
+    assert_eq!(reason, "unknown attribute 'ObjAttrLocl'");
+    let reason = invalid(Policy::from_json(r#"{"primary": {"CKA_NOPE": {"present": true}}}"#).unwrap_err());
+    assert_eq!(reason, "unknown attribute 'CKA_NOPE'");
+}
+
+#[test]
+fn unknown_fields_are_rejected() {
+    // a misspelt section
+    let reason = invalid(Policy::from_toml("[secondry]\nObjAttrLocal = { equals = true }\n").unwrap_err());
+    assert!(reason.contains("unknown field `secondry`"), "{}", reason);
+    // a misspelt assertion
+    let reason = invalid(Policy::from_json(r#"{"secondary": {"ObjAttrLocal": {"equal": true}}}"#).unwrap_err());
+    assert!(reason.contains("unknown variant `equal`"), "{}", reason);
+    // two assertions on one attribute
+    let reason = invalid(Policy::from_toml("[primary]\nObjAttrLocal = { equals = true, present = true }\n").unwrap_err());
+    assert!(reason.starts_with("TOML parse error"), "{}", reason);
+}
+
+#[test]
+fn hsm_generated_policy() {
+    let policy = Policy::from_toml(HSM_GENERATED).unwrap();
+    let all = [
+        TLVMapping::OBJ_ATTR_CLASS,
+        TLVMapping::OBJ_ATTR_EXTRACTABLE,
+        TLVMapping::OBJ_ATTR_LOCAL,
+        TLVMapping::OBJ_ATTR_NEVER_EXTRACTABLE,
+    ];
+
+    let report = evaluate(&policy, &rsa_builder().build().unwrap());
+    assert_eq!(outcomes(&report), all.map(|mapping| (mapping, true)));
+    assert!(report.passed());
+
+    // nothing to check the private half against
+    let report = evaluate(&policy, &aes_builder().build().unwrap());
+    assert_eq!(outcomes(&report), all.map(|mapping| (mapping, false)));
+
+    // the EC fixture never says whether its private key can leave the HSM
+    let report = evaluate(&policy, &ec_builder().build().unwrap());
+    assert_eq!(
+        outcomes(&report),
+        [
+            (TLVMapping::OBJ_ATTR_CLASS, true),
+            (TLVMapping::OBJ_ATTR_EXTRACTABLE, false),
+            (TLVMapping::OBJ_ATTR_LOCAL, true),
+            (TLVMapping::OBJ_ATTR_NEVER_EXTRACTABLE, false),
+        ]
+    );
+}
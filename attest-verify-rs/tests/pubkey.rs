@@ -0,0 +1,304 @@
+mod common;
+
+use common::*;
+use base64ct::{Base64UrlUnpadded, Encoding};
+use hsmattest::builder::{AttestationBuilder, KeySection};
+use hsmattest::error::ParseError;
+use hsmattest::pkcs11::NameStyle;
+use hsmattest::pubkey::{compare, from_hex, to_hex, CheckOutcome, KeyFormat, PublicKey};
+use hsmattest::tlv_mapping::*;
+use hsmattest::KeyMode;
+
+// digests of the fixture key's SubjectPublicKeyInfo, from `openssl ec -pubin -outform der`
+const EC_KCV: &str = "59486c";
+const EC_EKCV: &str = "b477413e6a95a6627bc45eb35c5caf014bcf45e353496ac446067db5a7069c5e";
+// and of owner.pub.pem, from `openssl pkey -pubin -outform der`
+const OWNER_KCV: &str = "412e6c";
+const OWNER_EKCV: &str = "6925d763dc2173aabc24f381f84f2515c492c4f08d8d33defd85d6bea5e88a93";
+
+// the public half of owner.key (`openssl pkey -pubout`), and both keys as `ssh-keygen -i -m PKCS8` has them
+const OWNER_PUBLIC_KEY: &str = include_str!("fixtures/owner.pub.pem");
+const OWNER_SSH: &str = include_str!("fixtures/owner.ssh.pub");
+const EC_SSH: &str = include_str!("fixtures/ec.ssh.pub");
+
+fn bundle(public: KeySection, private: KeySection) -> Vec<u8> {
+    AttestationBuilder::new()
+        .with_section(public)
+        .with_section(private)
+        .with_signature(vec![0; 256])
+        .build()
+        .unwrap()
+}
+
+// the EC fixture with both check values of its key
+fn ec_bundle() -> Vec<u8> {
+    let (public, private) = ec_sections();
+    let public = public
+        .with_raw(TLVMapping::OBJ_ATTR_KCV.to_int(), from_hex(EC_KCV).unwrap())
+        .with(TLVMapping::OBJ_ATTR_EKCV, TLVValue::Bytes(Bytes(EC_EKCV.into())));
+    bundle(public, private)
+}
+
+fn mismatches(bundle: &[u8], key: &PublicKey) -> Vec<TLVMapping> {
+    let checks = compare(&parse(bundle).unwrap(), key).unwrap();
+    assert!(!checks.is_empty());
+    checks.into_iter().filter(|check| !check.is_match()).map(|check| check.mapping).collect()
+}
+
+#[test]
+fn ec_check_values_match_openssl() {
+    let key = ec_key();
+    assert_eq!(to_hex(&key.kcv().unwrap()), EC_KCV);
+    assert_eq!(to_hex(&key.ekcv().unwrap()), EC_EKCV);
+    assert_eq!(PublicKey::from_der(&key.to_spki_der().unwrap()).unwrap(), key);
+}
+
+#[test]
+fn ec_key_from_the_attested_section() {
+    let attestation = parse(&ec_builder().build().unwrap()).unwrap();
+    assert_eq!(PublicKey::from_section(&attestation.sections[0]), Some(ec_key()));
+}
+
+#[test]
+fn ec_key_matches_its_attestation() {
+    let checks = compare(&parse(&ec_bundle()).unwrap(), &ec_key()).unwrap();
+    let mappings = checks.iter().map(|check| check.mapping).collect::<Vec<_>>();
+    assert_eq!(
+        mappings,
+        [TLVMapping::OBJ_ATTR_KCV, TLVMapping::OBJ_ATTR_EKCV, TLVMapping::OBJ_ATTR_EC_PARAMS, TLVMapping::OBJ_ATTR_EC_POINT]
+    );
+    assert!(checks.iter().all(|check| check.is_match()), "{:?}", checks);
+}
+
+#[test]
+fn other_ec_keys_do_not_match() {
+    let PublicKey::Ec { curve, point } = ec_key() else {
+        panic!("not an EC key");
+    };
+    let mut y = point.y.clone();
+    y[0] ^= 1;
+    let moved = PublicKey::Ec { curve, point: EcPoint::new(point.x.clone(), y) };
+    assert_eq!(
+        mismatches(&ec_bundle(), &moved),
+        [TLVMapping::OBJ_ATTR_KCV, TLVMapping::OBJ_ATTR_EKCV, TLVMapping::OBJ_ATTR_EC_POINT]
+    );
+
+    let p384 = PublicKey::Ec { curve: Curve::P384, point: EcPoint::new(vec![1; 48], vec![2; 48]) };
+    let checks = compare(&parse(&ec_bundle()).unwrap(), &p384).unwrap();
+    let params = checks.iter().find(|check| check.mapping == TLVMapping::OBJ_ATTR_EC_PARAMS).unwrap();
+    assert_eq!(params.outcome, CheckOutcome::Mismatch { expected: "P-384".into(), actual: "P-256".into() });
+    let mismatch = "(Primary) = mismatch (expected P-384, attested P-256)";
+    assert_eq!(params.to_string(), format!("ObjAttrEcParams {}", mismatch));
+    assert_eq!(params.to_line(NameStyle::Pkcs11), format!("CKA_EC_PARAMS {}", mismatch));
+}
+
+#[test]
+fn bare_points_compare_the_same() {
+    let (public, private) = ec_sections();
+    let PublicKey::Ec { point, .. } = ec_key() else {
+        panic!("not an EC key");
+    };
+    let bare = KeySection::new(public.object_handle)
+        .with(TLVMapping::OBJ_ATTR_EC_PARAMS, TLVValue::Curve(Curve::P256))
+        .with(TLVMapping::OBJ_ATTR_EC_POINT, TLVValue::EcPoint(EcPoint { wrapped: false, ..point }));
+    assert!(mismatches(&bundle(bare, private), &ec_key()).is_empty());
+}
+
+fn owner_components() -> (Vec<u8>, Vec<u8>) {
+    match PublicKey::from_pem(OWNER_PUBLIC_KEY).unwrap() {
+        PublicKey::Rsa { modulus, exponent } => (modulus, exponent),
+        key => panic!("not an RSA key: {:?}", key),
+    }
+}
+
+// the RSA fixture, attesting the owner key's modulus and exponent as given
+fn rsa_bundle(modulus: &[u8], exponent: &[u8]) -> Vec<u8> {
+    let (public, private) = rsa_sections();
+    let public = KeySection::new(public.object_handle)
+        .with(TLVMapping::OBJ_ATTR_CLASS, TLVValue::ClassKey(ClassKey::Pubkey))
+        .with_raw(TLVMapping::OBJ_ATTR_MODULUS.to_int(), modulus.to_vec())
+        .with_raw(TLVMapping::OBJ_ATTR_PUBLIC_EXPONENT.to_int(), exponent.to_vec());
+    bundle(public, private)
+}
+
+#[test]
+fn rsa_check_values_match_openssl() {
+    let owner = PublicKey::from_pem(OWNER_PUBLIC_KEY).unwrap();
+    assert_eq!(to_hex(&owner.kcv().unwrap()), OWNER_KCV);
+    assert_eq!(to_hex(&owner.ekcv().unwrap()), OWNER_EKCV);
+}
+
+#[test]
+fn rsa_components_compare_byte_for_byte() {
+    let (modulus, exponent) = owner_components();
+    let owner = PublicKey::from_pem(OWNER_PUBLIC_KEY).unwrap();
+    let checks = compare(&parse(&rsa_bundle(&modulus, &exponent)).unwrap(), &owner).unwrap();
+    let mappings = checks.iter().map(|check| check.mapping).collect::<Vec<_>>();
+    // the private section has no public components to compare
+    assert_eq!(mappings, [TLVMapping::OBJ_ATTR_MODULUS, TLVMapping::OBJ_ATTR_PUBLIC_EXPONENT]);
+    assert!(checks.iter().all(|check| check.mode == KeyMode::Primary && check.is_match()), "{:?}", checks);
+
+    let mut flipped = modulus.clone();
+    *flipped.last_mut().unwrap() ^= 2;
+    assert_eq!(mismatches(&rsa_bundle(&flipped, &exponent), &owner), [TLVMapping::OBJ_ATTR_MODULUS]);
+    let checks = compare(&parse(&rsa_bundle(&modulus, &[0x03])).unwrap(), &owner).unwrap();
+    assert_eq!(checks[1].outcome, CheckOutcome::Mismatch { expected: "010001".into(), actual: "03".into() });
+}
+
+#[test]
+fn leading_zeros_do_not_count() {
+    let (modulus, exponent) = owner_components();
+    let owner = PublicKey::from_pem(OWNER_PUBLIC_KEY).unwrap();
+    let padded_modulus = [&[0, 0][..], &modulus].concat();
+    let padded_exponent = [&[0][..], &exponent].concat();
+    assert_eq!(PublicKey::from_rsa_components(&padded_modulus, &padded_exponent), owner);
+    assert!(mismatches(&rsa_bundle(&padded_modulus, &padded_exponent), &owner).is_empty());
+}
+
+#[test]
+fn rsa_check_values_are_compared_in_every_section() {
+    let owner = PublicKey::from_pem(OWNER_PUBLIC_KEY).unwrap();
+    let (public, private) = rsa_sections();
+    let public = public
+        .with_raw(TLVMapping::OBJ_ATTR_KCV.to_int(), from_hex(OWNER_KCV).unwrap())
+        .with(TLVMapping::OBJ_ATTR_EKCV, TLVValue::Bytes(Bytes(OWNER_EKCV.into())));
+    let private = private.with_raw(TLVMapping::OBJ_ATTR_KCV.to_int(), OWNER_KCV.as_bytes().to_vec());
+    let checks = compare(&parse(&bundle(public, private)).unwrap(), &owner).unwrap();
+    let outcomes = checks.iter().map(|check| (check.mode, check.mapping, check.is_match()));
+    // the fixture's modulus is not the owner's, the exponent is
+    assert_eq!(
+        outcomes.collect::<Vec<_>>(),
+        [
+            (KeyMode::Primary, TLVMapping::OBJ_ATTR_KCV, true),
+            (KeyMode::Primary, TLVMapping::OBJ_ATTR_EKCV, true),
+            (KeyMode::Primary, TLVMapping::OBJ_ATTR_MODULUS, false),
+            (KeyMode::Primary, TLVMapping::OBJ_ATTR_PUBLIC_EXPONENT, true),
+            (KeyMode::Secondary, TLVMapping::OBJ_ATTR_KCV, true),
+        ]
+    );
+}
+
+// the EC fixture with the check values stored as given, raw
+fn check_value_mismatches(kcv: &[u8], ekcv: &[u8]) -> Vec<TLVMapping> {
+    let (public, private) = ec_sections();
+    let public = public
+        .with_raw(TLVMapping::OBJ_ATTR_KCV.to_int(), kcv.to_vec())
+        .with_raw(TLVMapping::OBJ_ATTR_EKCV.to_int(), ekcv.to_vec());
+    mismatches(&bundle(public, private), &ec_key())
+}
+
+#[test]
+fn check_values_compare_in_full() {
+    let kcv = from_hex(EC_KCV).unwrap();
+    let ekcv = from_hex(EC_EKCV).unwrap();
+    assert!(check_value_mismatches(&kcv, &ekcv).is_empty());
+    // hex, in either case
+    let (kcv_hex, ekcv_hex) = (EC_KCV.to_uppercase(), EC_EKCV.to_uppercase());
+    assert!(check_value_mismatches(kcv_hex.as_bytes(), ekcv_hex.as_bytes()).is_empty());
+
+    let both = [TLVMapping::OBJ_ATTR_KCV, TLVMapping::OBJ_ATTR_EKCV];
+    // truncated digests
+    assert_eq!(check_value_mismatches(&kcv[..2], &ekcv[..16]), both);
+    assert_eq!(check_value_mismatches(&kcv[..1], &ekcv[..1]), both);
+    assert_eq!(check_value_mismatches(&[], &[]), both);
+    // truncated hex
+    assert_eq!(check_value_mismatches(&EC_KCV.as_bytes()[..4], &EC_EKCV.as_bytes()[..32]), both);
+    assert_eq!(check_value_mismatches(&EC_KCV.as_bytes()[..2], &EC_EKCV.as_bytes()[..6]), both);
+    // values running past the digest
+    let mut longer = kcv.clone();
+    longer.push(0);
+    assert_eq!(check_value_mismatches(&longer, &ekcv), [TLVMapping::OBJ_ATTR_KCV]);
+}
+
+#[test]
+fn attested_keys_export_as_openssl_and_ssh_keygen_have_them() {
+    let key = PublicKey::from_attestation(&parse(&ec_builder().build().unwrap()).unwrap()).unwrap();
+    assert_eq!(key, ec_key());
+    assert_eq!(key.to_pem().unwrap(), EC_KEY);
+    assert_eq!(key.to_openssh(None).unwrap(), EC_SSH.trim_end());
+    assert_eq!(key.export(KeyFormat::Der).unwrap(), key.to_spki_der().unwrap());
+
+    let owner = PublicKey::from_pem(OWNER_PUBLIC_KEY).unwrap();
+    assert_eq!(owner.to_pem().unwrap(), OWNER_PUBLIC_KEY);
+    assert_eq!(owner.to_openssh(Some("owner")).unwrap(), format!("{} owner", OWNER_SSH.trim_end()));
+}
+
+#[test]
+fn jwk_thumbprints() {
+    // RFC 7638 over the required members, checked with python's hashlib
+    let jwk = ec_key().to_jwk();
+    assert_eq!(jwk["kty"], "EC");
+    assert_eq!(jwk["crv"], "P-256");
+    assert_eq!(jwk["x"], "5LDHb1uKnnU3yhzTROVaQgVdnSi1PSPfspYURid0_Sc");
+    assert_eq!(jwk["y"], "iqxDDeWtZtZY4pJD43MNBCjh8TXcnPpMlimtQo-ffMU");
+    assert_eq!(jwk["kid"], "fx4nMzdRlFvRtDnREYq18s7092EW2ZelpLwLbuCGx7U");
+
+    let owner = PublicKey::from_pem(OWNER_PUBLIC_KEY).unwrap();
+    assert_eq!(owner.jwk_thumbprint(), "kTUqBEzNXmmUDBRqUA-csKlHgT78jSVFX0uP0RVJK9Q");
+    assert_eq!(owner.to_jwk()["e"], "AQAB");
+    let exported = owner.export(KeyFormat::Jwk).unwrap();
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&exported).unwrap(), owner.to_jwk());
+
+    for key in [owner, ec_key()] {
+        assert_eq!(PublicKey::from_jwk(&key.to_jwk()).unwrap(), key);
+    }
+    let mut short = ec_key().to_jwk();
+    short["x"] = "AAAA".into();
+    assert!(matches!(PublicKey::from_jwk(&short), Err(ParseError::InvalidPublicKey(_))));
+}
+
+// public halves of throwaway keys, from `openssl ecparam -name <curve> -genkey | openssl ec -pubout`
+const OTHER_CURVES: [(Curve, &str); 3] = [
+    (Curve::P384, include_str!("fixtures/p384.pub.pem")),
+    (Curve::P521, include_str!("fixtures/p521.pub.pem")),
+    (Curve::Secp256k1, include_str!("fixtures/secp256k1.pub.pem")),
+];
+
+fn jwk_error(jwk: &serde_json::Value) -> String {
+    match PublicKey::from_jwk(jwk) {
+        Err(ParseError::InvalidPublicKey(reason)) => reason,
+        other => panic!("not rejected as an invalid key: {:?}", other),
+    }
+}
+
+#[test]
+fn ec_points_have_to_lie_on_their_curve() {
+    for (curve, pem) in OTHER_CURVES {
+        let key = PublicKey::from_pem(pem).unwrap();
+        assert!(matches!(&key, PublicKey::Ec { curve: c, .. } if *c == curve), "{:?}", key);
+        assert_eq!(PublicKey::from_jwk(&key.to_jwk()).unwrap(), key);
+    }
+
+    // one bit off the fixture key
+    let PublicKey::Ec { curve, point } = ec_key() else {
+        panic!("not an EC key");
+    };
+    let mut y = point.y.clone();
+    y[31] ^= 1;
+    let off_curve = [vec![0x04], point.x.clone(), y].concat();
+    assert_eq!(PublicKey::from_ec_point(curve, &point.to_uncompressed()), Some(ec_key()));
+    assert_eq!(PublicKey::from_ec_point(curve, &off_curve), None);
+    let mut jwk = ec_key().to_jwk();
+    jwk["y"] = Base64UrlUnpadded::encode_string(&off_curve[33..]).into();
+    assert_eq!(jwk_error(&jwk), "JWK point is not on P-256");
+
+    // the right curve, the wrong size
+    let mut jwk = ec_key().to_jwk();
+    jwk["crv"] = "P-384".into();
+    assert_eq!(jwk_error(&jwk), "JWK x/y are not 48-byte coordinates for P-384");
+    // the fixture's point, read as a point on another curve of the same size
+    let mut jwk = ec_key().to_jwk();
+    jwk["crv"] = "secp256k1".into();
+    assert_eq!(jwk_error(&jwk), "JWK point is not on secp256k1");
+}
+
+#[test]
+fn keys_that_do_not_export() {
+    let err = PublicKey::from_attestation(&parse(&aes_builder().build().unwrap()).unwrap()).unwrap_err();
+    assert!(matches!(err, ParseError::NoPublicKey), "{:?}", err);
+
+    let secp256k1 = PublicKey::Ec { curve: Curve::Secp256k1, point: EcPoint::new(vec![1; 32], vec![2; 32]) };
+    assert_eq!(secp256k1.to_jwk()["crv"], "secp256k1");
+    let err = secp256k1.export(KeyFormat::OpenSsh).unwrap_err();
+    assert!(matches!(err, ParseError::UnsupportedKeyFormat(_)), "{:?}", err);
+}
@@ -0,0 +1,288 @@
+mod common;
+
+use common::*;
+use hsmattest::builder::{AttestationBuilder, KeySection, SigningKey};
+use hsmattest::error::ParseError;
+use hsmattest::tlv_mapping::{TLVMapping, TLVValue};
+use hsmattest::verify::verify_bundle;
+use hsmattest::{KeyMode, Machine, Mode};
+use rsa::pkcs1::{EncodeRsaPrivateKey, LineEnding};
+use rsa::pkcs8::{DecodePrivateKey, EncodePrivateKey};
+use rsa::RsaPrivateKey;
+
+#[test]
+fn parses_what_the_builder_wrote() {
+    let (public, private) = rsa_sections();
+    let bundle = rsa_builder().build().unwrap();
+    let attestation = parse(&bundle).unwrap();
+
+    assert_eq!(attestation.mode(), Mode::Asymetric);
+    assert_eq!(attestation.sections.len(), 2);
+    assert_eq!(attestation.sections[0].mode, KeyMode::Primary);
+    assert_eq!(attestation.sections[0].object_handle, public.object_handle);
+    assert_eq!(attestation.sections[0].attributes, public.attributes);
+    assert_eq!(attestation.sections[1].mode, KeyMode::Secondary);
+    assert_eq!(attestation.sections[1].object_handle, private.object_handle);
+    assert_eq!(attestation.sections[1].attributes, private.attributes);
+    assert_eq!(attestation.signature, bundle[bundle.len() - 256..]);
+}
+
+#[test]
+fn symmetric_bundle_has_a_single_section() {
+    let attestation = parse(&aes_builder().build().unwrap()).unwrap();
+
+    assert_eq!(attestation.mode(), Mode::Symmetric);
+    assert_eq!(attestation.header.secondkey_offset, 0);
+    assert_eq!(attestation.sections.len(), 1);
+    assert_eq!(attestation.sections[0].attributes, aes_section().attributes);
+}
+
+#[test]
+fn header_fields_are_decoded() {
+    let bundle = ec_builder().with_response_code(0x8000_0001).with_request_id(0xdead_beef).build().unwrap();
+    let attestation = parse(&bundle).unwrap();
+    let header = &attestation.header;
+
+    assert_eq!(header.response_code, 0x8000_0001);
+    assert!(!header.is_success());
+    assert_eq!(header.request_id, 0xdead_beef);
+    assert_eq!(header.total_size as usize, bundle.len());
+    assert_eq!(header.response_payload, b"firmware 3.4.0 partition 7");
+    assert_eq!(header.attr_offset as usize, 16 + header.response_payload.len());
+    assert_eq!(header.attribute_format, 0x0002_0001);
+    assert_eq!(header.firstkey_offset, 8);
+    assert_eq!(header.signature_len, 256);
+
+    let public = &attestation.sections[0];
+    assert_eq!(public.attr_count as usize, public.attributes.len());
+    assert_eq!(header.secondkey_offset, header.firstkey_offset + 12 + public.attr_size);
+}
+
+// one section holding a one byte label, written out by hand rather than by the builder
+fn hand_written() -> Vec<u8> {
+    let mut section = Vec::new();
+    for field in [0x0020_0001, 1, 9, TLVMapping::OBJ_ATTR_LABEL.to_int(), 1] {
+        section.extend_from_slice(&u32::to_be_bytes(field));
+    }
+    section.push(b'k');
+    let mut attributes = u32::to_be_bytes(0x0002_0001).to_vec();
+    attributes.extend_from_slice(&[0, 8, 0, 0]);
+    attributes.extend_from_slice(&section);
+
+    let payload = b"hsm 0";
+    let mut bundle = Vec::new();
+    let total_size = 16 + payload.len() + attributes.len() + 256;
+    for field in [0, 0x11, total_size, attributes.len()] {
+        bundle.extend_from_slice(&u32::to_be_bytes(field as u32));
+    }
+    bundle.extend_from_slice(payload);
+    bundle.extend_from_slice(&attributes);
+    bundle.extend_from_slice(&[0x5a; 256]);
+    bundle
+}
+
+#[test]
+fn header_fields_at_their_documented_offsets() {
+    let bundle = hand_written();
+    let attestation = parse(&bundle).unwrap();
+    let header = &attestation.header;
+    assert!(header.is_success());
+    assert_eq!(header.request_id, 0x11);
+    assert_eq!(header.total_size as usize, bundle.len());
+    assert_eq!(header.buff_size, 29);
+    assert_eq!(header.response_payload, b"hsm 0");
+    assert_eq!(header.attr_offset, 21);
+    assert_eq!(header.attribute_format, 0x0002_0001);
+    assert_eq!((header.firstkey_offset, header.secondkey_offset), (8, 0));
+    assert_eq!(attestation.sections[0].object_handle, 0x0020_0001);
+    assert_eq!(attestation.sections[0].attributes[0].raw, b"k");
+}
+
+#[test]
+fn bundles_without_a_response_payload() {
+    let bundle = aes_builder().with_response_payload(Vec::new()).build().unwrap();
+    let header = parse(&bundle).unwrap().header;
+    assert!(header.response_payload.is_empty());
+    assert_eq!(header.attr_offset, 16);
+    assert_eq!(header.firstkey_offset, 8);
+}
+
+#[test]
+fn builder_writes_the_documented_layout() {
+    let section = KeySection::new(0x0020_0001).with_raw(TLVMapping::OBJ_ATTR_LABEL.to_int(), b"k".to_vec());
+    let builder = AttestationBuilder::new()
+        .with_request_id(0x11)
+        .with_response_payload(b"hsm 0".to_vec())
+        .with_attribute_format(0x0002_0001)
+        .with_section(section);
+    let expected = hand_written();
+    assert_eq!(builder.clone().with_signature(vec![0x5a; 256]).build().unwrap(), expected);
+
+    // signed rather than given a signature, everything before it stays the same
+    let signed = builder.with_signing_key(owner_key()).build().unwrap();
+    assert_eq!(signed[..signed.len() - 256], expected[..expected.len() - 256]);
+    assert!(verify_bundle(&signed, &owner_key().public_key()).unwrap().is_valid());
+}
+
+#[test]
+fn signing_keys_read_from_pem_and_der() {
+    let private = RsaPrivateKey::from_pkcs8_pem(OWNER_KEY).unwrap();
+    let public = owner_key().public_key();
+    let pkcs1 = private.to_pkcs1_pem(LineEnding::LF).unwrap();
+    assert_eq!(SigningKey::from_pem(&pkcs1).unwrap().public_key(), public);
+    assert_eq!(SigningKey::from_bytes(pkcs1.as_bytes()).unwrap().public_key(), public);
+    let pkcs1_der = private.to_pkcs1_der().unwrap();
+    assert_eq!(SigningKey::from_der(pkcs1_der.as_bytes()).unwrap().public_key(), public);
+    let pkcs8_der = private.to_pkcs8_der().unwrap();
+    assert_eq!(SigningKey::from_bytes(pkcs8_der.as_bytes()).unwrap().public_key(), public);
+    assert_eq!(SigningKey::from(private).signature_len(), 256);
+
+    for bytes in [&b""[..], EC_KEY.as_bytes(), include_bytes!("fixtures/owner.pub.pem")] {
+        let err = SigningKey::from_bytes(bytes).unwrap_err();
+        assert!(matches!(err, ParseError::InvalidPrivateKey(_)), "{:?}", err);
+    }
+}
+
+#[test]
+fn fixtures_are_signed_by_the_owner_key() {
+    let key = owner_key().public_key();
+    for (name, bundle) in bundles() {
+        assert!(verify_bundle(&bundle, &key).unwrap().is_valid(), "{}", name);
+    }
+
+    let mut tampered = rsa_builder().build().unwrap();
+    tampered[20] ^= 1;
+    assert!(!verify_bundle(&tampered, &key).unwrap().is_valid());
+}
+
+#[test]
+fn rebuilds_byte_for_byte() {
+    for (name, bundle) in bundles() {
+        let attestation = parse(&bundle).unwrap();
+        assert_eq!(AttestationBuilder::from_attestation(&attestation).build().unwrap(), bundle, "{}", name);
+    }
+}
+
+#[test]
+fn byte_at_a_time_matches_whole_buffer() {
+    for (name, bundle) in bundles() {
+        assert_eq!(parse_bytewise(&bundle).unwrap(), parse(&bundle).unwrap(), "{}", name);
+    }
+}
+
+#[test]
+fn chunk_boundaries_do_not_matter() {
+    for (name, bundle) in bundles() {
+        let whole = parse(&bundle).unwrap();
+        for size in [2, 3, 7, 16, 19, 64, 255, 1024] {
+            let mut machine = Machine::new();
+            bundle.chunks(size).for_each(|chunk| machine.run_buf(chunk));
+            assert_eq!(machine.finish().unwrap(), whole, "{} in chunks of {}", name, size);
+        }
+    }
+}
+
+#[test]
+fn detects_longer_signatures() {
+    for len in [256, 384, 512] {
+        let bundle = rsa_builder().with_signature(vec![0x5a; len]).build().unwrap();
+        let attestation = parse(&bundle).unwrap();
+        assert_eq!(attestation.header.signature_len as usize, len);
+        assert_eq!(attestation.signature, vec![0x5a; len]);
+        assert_eq!(attestation.sections[0].attributes, rsa_sections().0.attributes);
+    }
+}
+
+#[test]
+fn detects_longer_signatures_past_padding() {
+    for len in [256, 384, 512] {
+        let bundle = rsa_builder().with_signature(vec![0x5a; len]).build().unwrap();
+        let attestation = parse(&padded(&bundle, len, &[0; 40])).unwrap();
+        assert_eq!(attestation.header.signature_len as usize, len);
+        assert_eq!(attestation.header.firstkey_offset, 48);
+        assert_eq!(attestation.signature, vec![0x5a; len]);
+        assert_eq!(attestation.sections[0].attributes, rsa_sections().0.attributes);
+        assert_eq!(attestation.sections[1].attributes, rsa_sections().1.attributes);
+    }
+}
+
+#[test]
+fn padded_first_section_is_checked_at_its_offset() {
+    let bundle = rsa_builder().with_signature(vec![0x5a; 384]).build().unwrap();
+    // where the 256 byte candidate would find its attribute buffer, key offsets that fit it with
+    // nothing but zeros for a section header
+    let mut padding = vec![0; 200];
+    padding[120..128].copy_from_slice(&[0, 0, 0, 0, 0, 16, 0, 0]);
+    let padded = padded(&bundle, 384, &padding);
+
+    let attestation = parse(&padded).unwrap();
+    assert_eq!(attestation.header.signature_len, 384);
+    assert_eq!(attestation.header.firstkey_offset, 208);
+    assert_eq!(attestation.sections[0].attributes, rsa_sections().0.attributes);
+    assert_eq!(attestation.sections[1].attributes, rsa_sections().1.attributes);
+    assert_eq!(parse_bytewise(&padded).unwrap(), attestation);
+}
+
+#[test]
+fn one_machine_parses_many_bundles() {
+    let mut machine = Machine::new();
+    for (name, bundle) in bundles() {
+        machine.run_buf(&bundle);
+        assert_eq!(machine.finish().unwrap(), parse(&bundle).unwrap(), "{}", name);
+        machine.reset();
+    }
+
+    // nor does an error carry over
+    machine.run_buf(&[0xff; 64]);
+    assert!(machine.finish().is_err());
+    machine.reset();
+    let bundle = aes_builder().build().unwrap();
+    machine.run_buf(&bundle);
+    assert_eq!(machine.finish().unwrap(), parse(&bundle).unwrap());
+}
+
+#[test]
+fn reset_keeps_the_pinned_signature_length() {
+    let bundle = aes_builder().with_signature(vec![1; 384]).build().unwrap();
+    let mut machine = Machine::new_with_signature_len(384);
+    machine.run_buf(&bundle[..40]);
+    machine.reset();
+    assert_eq!(machine.signature_len, 384);
+    machine.run_buf(&bundle);
+    assert_eq!(machine.finish().unwrap().header.signature_len, 384);
+}
+
+#[test]
+fn pinned_signature_length_is_used() {
+    let bundle = aes_builder().with_signature(vec![1; 384]).build().unwrap();
+    let mut machine = Machine::new_with_signature_len(384);
+    machine.run_buf(&bundle);
+    let attestation = machine.finish().unwrap();
+
+    assert_eq!(attestation.header.signature_len, 384);
+    assert_eq!(
+        attestation.sections[0].value(TLVMapping::OBJ_ATTR_VALUE_LEN),
+        Some(&TLVValue::Int(hsmattest::tlv_mapping::Int(32)))
+    );
+}
+
+#[test]
+fn ambiguous_layouts_need_a_pinned_signature_length() {
+    // a response payload that also reads as an attribute buffer, 128 bytes ahead of the real one
+    let mut payload = vec![0; 128];
+    payload[4..8].copy_from_slice(&[0, 8, 0, 0]);
+    payload[12..20].copy_from_slice(&[0, 0, 0, 1, 0, 0, 0, 8]);
+    let bundle = AttestationBuilder::new()
+        .with_response_payload(payload)
+        .with_section(aes_section())
+        .with_signature(vec![1; 256])
+        .build()
+        .unwrap();
+
+    let err = parse(&bundle).unwrap_err();
+    assert!(matches!(&err, ParseError::AmbiguousSignatureLength(lens) if lens == &[256, 384]), "{:?}", err);
+
+    let mut machine = Machine::new_with_signature_len(256);
+    machine.run_buf(&bundle);
+    assert_eq!(machine.finish().unwrap().sections[0].attributes, aes_section().attributes);
+}
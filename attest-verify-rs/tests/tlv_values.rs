@@ -0,0 +1,251 @@
+mod common;
+
+use common::*;
+use hsmattest::builder::{AttestationBuilder, KeySection};
+use hsmattest::pkcs11::{self, NameStyle, Pkcs11Attribute};
+use hsmattest::tlv_mapping::*;
+
+// the value a mapping decodes `raw` to, and the bytes it writes back out
+fn decode(mapping: TLVMapping, raw: &[u8]) -> TLVValue {
+    let value = mapping.encode(raw, raw.len() as u32);
+    assert_eq!(value.to_bytes(), raw, "{:?}", mapping);
+    value
+}
+
+#[test]
+fn class_key() {
+    for (byte, class, name) in [
+        (2, ClassKey::Pubkey, "public-key"),
+        (3, ClassKey::Privkey, "private-key"),
+        (4, ClassKey::Secret, "secret-key (symmetric)"),
+    ] {
+        assert_eq!(decode(TLVMapping::OBJ_ATTR_CLASS, &[byte]), TLVValue::ClassKey(class));
+        assert_eq!(class.to_str(), name);
+    }
+}
+
+#[test]
+fn key_type() {
+    use KeyType::*;
+    for key_type in [Rsa, Dsa, Ec, Kyber, Dilithium, Falcon, Generic, Rc4, Des, Des3, Aes, Any] {
+        assert_eq!(decode(TLVMapping::OBJ_ATTR_KEY_TYPE, &[key_type as u8]), TLVValue::KeyType(key_type));
+    }
+    assert_eq!(Aes.to_str(), "aes");
+    assert_eq!(Generic.to_str(), "generic-secret");
+}
+
+#[test]
+fn bool() {
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_SENSITIVE, &[1]), TLVValue::Bool(Bool(true)));
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_SENSITIVE, &[0]), TLVValue::Bool(Bool(false)));
+    assert_eq!(TLVValue::Bool(Bool(true)).as_bool(), Some(true));
+    assert_eq!(TLVValue::Bool(Bool(true)).to_str(), "true");
+}
+
+#[test]
+fn hex_str_128() {
+    let id = "0123456789abcdef".repeat(8);
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_ID, id.as_bytes()), TLVValue::HexStr128(HexStr128(id.clone())));
+}
+
+#[test]
+fn bytes() {
+    let value = decode(TLVMapping::OBJ_ATTR_MODULUS, &[0x00, 0xc3, 0xff]);
+    assert_eq!(value, TLVValue::Bytes(Bytes("00c3ff".into())));
+    assert_eq!(value.to_str(), "00c3ff");
+}
+
+#[test]
+fn byte_str() {
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_LABEL, b"my-key"), TLVValue::ByteStr(ByteStr("my-key".into())));
+
+    // labels are NUL terminated on the wire, the terminator isn't part of the value
+    let label = TLVMapping::OBJ_ATTR_LABEL.encode(b"my-key\0", 7);
+    assert_eq!(label, TLVValue::ByteStr(ByteStr("my-key".into())));
+}
+
+#[test]
+fn int() {
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_MODULUS_BITS, &[0, 0, 8, 0]), TLVValue::Int(Int(2048)));
+    // shorter integers decode the same, they're written back as four bytes
+    let exponent = TLVMapping::OBJ_ATTR_PUBLIC_EXPONENT.encode(&[1, 0, 1], 3);
+    assert_eq!(exponent.as_int(), Some(65537));
+    assert_eq!(exponent.to_bytes(), vec![0, 1, 0, 1]);
+}
+
+#[test]
+fn curve() {
+    for (curve, name) in [
+        (Curve::P256, "P-256"),
+        (Curve::P384, "P-384"),
+        (Curve::P521, "P-521"),
+        (Curve::Secp256k1, "secp256k1"),
+    ] {
+        assert_eq!(decode(TLVMapping::OBJ_ATTR_EC_PARAMS, curve.der()), TLVValue::Curve(curve));
+        assert_eq!(curve.to_str(), name);
+    }
+    assert_eq!(Curve::P384.oid(), "1.3.132.0.34");
+}
+
+#[test]
+fn ec_point() {
+    for curve in Curve::ALL {
+        let len = curve.field_len();
+        let mut point = vec![0x04];
+        point.extend((0..2 * len).map(|i| i as u8));
+        let (x, y) = (point[1..=len].to_vec(), point[len + 1..].to_vec());
+
+        // as PKCS#11 has it, in an OCTET STRING (two byte length for P-521)
+        let mut wrapped = vec![0x04];
+        if point.len() >= 0x80 {
+            wrapped.push(0x81);
+        }
+        wrapped.push(point.len() as u8);
+        wrapped.extend_from_slice(&point);
+        let value = decode(TLVMapping::OBJ_ATTR_EC_POINT, &wrapped);
+        assert_eq!(value, TLVValue::EcPoint(EcPoint::new(x.clone(), y.clone())), "{:?}", curve);
+
+        let value = decode(TLVMapping::OBJ_ATTR_EC_POINT, &point);
+        assert_eq!(value, TLVValue::EcPoint(EcPoint { x, y, wrapped: false }), "{:?}", curve);
+    }
+    let point = EcPoint::new(vec![0xab; 32], vec![0xcd; 32]);
+    assert_eq!(point.to_str(), format!("x: {}, y: {}", "ab".repeat(32), "cd".repeat(32)));
+}
+
+#[test]
+fn ec_values_that_do_not_decode() {
+    // unknown curve (brainpoolP256r1), compressed point, coordinates no curve has
+    let brainpool = [0x06, 0x09, 0x2b, 0x24, 0x03, 0x03, 0x02, 0x08, 0x01, 0x01, 0x07];
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_EC_PARAMS, &brainpool), TLVValue::RawBytes(RawBytes(brainpool.to_vec())));
+    let compressed = [vec![0x04, 0x21, 0x02], vec![7; 32]].concat();
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_EC_POINT, &compressed), TLVValue::RawBytes(RawBytes(compressed)));
+    let short = [vec![0x04], vec![7; 40]].concat();
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_EC_POINT, &short), TLVValue::RawBytes(RawBytes(short)));
+}
+
+#[test]
+fn raw_bytes_for_values_that_do_not_decode() {
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_CLASS, &[9]), TLVValue::RawBytes(RawBytes(vec![9])));
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_ID, &[0xff, 0xfe]), TLVValue::RawBytes(RawBytes(vec![0xff, 0xfe])));
+    assert_eq!(decode(TLVMapping::OBJ_ATTR_LOCAL, &[]), TLVValue::RawBytes(RawBytes(vec![])));
+}
+
+#[test]
+fn unknown_types_keep_their_bytes() {
+    assert_eq!(TLVMapping::from_int(0x4242), TLVMapping::UNKNOWN);
+    assert_eq!(decode(TLVMapping::UNKNOWN, &[0xab, 0xcd]), TLVValue::Bytes(Bytes("abcd".into())));
+}
+
+#[test]
+fn mapping_ids_and_names() {
+    for mapping in [
+        TLVMapping::OBJ_ATTR_CLASS,
+        TLVMapping::OBJ_ATTR_KEY_TYPE,
+        TLVMapping::OBJ_ATTR_EKCV,
+        TLVMapping::OBJ_ATTR_SPLITTABLE,
+        TLVMapping::OBJ_ATTR_DERIVE_KEY_MECHANISMS,
+    ] {
+        assert_eq!(TLVMapping::from_int(mapping.to_int()), mapping);
+        assert_eq!(TLVMapping::from_name(&mapping.to_string()), Some(mapping));
+    }
+    assert_eq!(TLVMapping::from_name("OBJ_ATTR_KCV"), Some(TLVMapping::OBJ_ATTR_KCV));
+    assert_eq!(TLVMapping::from_name("ObjAttrNope"), None);
+}
+
+#[test]
+fn pkcs11_names() {
+    let extractable = TLVMapping::OBJ_ATTR_EXTRACTABLE.pkcs11().unwrap();
+    assert_eq!(extractable, Pkcs11Attribute { id: pkcs11::CKA_EXTRACTABLE, name: "CKA_EXTRACTABLE" });
+    assert_eq!(extractable.to_string(), "CKA_EXTRACTABLE (0x0162)");
+    // the only attribute the HSM doesn't write under its PKCS#11 type
+    assert_eq!(TLVMapping::OBJ_ATTR_KCV.pkcs11().unwrap().id, 0x0090);
+    assert_eq!(TLVMapping::from_pkcs11(pkcs11::CKA_CHECK_VALUE), Some(TLVMapping::OBJ_ATTR_KCV));
+    for id in [0x0000, 0x0003, 0x0103, 0x0121, 0x0164, 0x0181, 0x0210] {
+        let mapping = TLVMapping::from_int(id);
+        assert_eq!(mapping.pkcs11().map(|attribute| attribute.id), Some(id), "{:?}", mapping);
+    }
+    for mapping in [TLVMapping::OBJ_ATTR_EKCV, TLVMapping::OBJ_ATTR_SPLITTABLE, TLVMapping::UNKNOWN] {
+        assert_eq!(mapping.pkcs11(), None, "{:?}", mapping);
+    }
+
+    assert_eq!(TLVMapping::from_pkcs11_name("CKA_MODULUS_BITS"), Some(TLVMapping::OBJ_ATTR_MODULUS_BITS));
+    assert_eq!(TLVMapping::from_name("CKA_NEVER_EXTRACTABLE"), Some(TLVMapping::OBJ_ATTR_NEVER_EXTRACTABLE));
+    assert_eq!(TLVMapping::from_pkcs11_name("ObjAttrLabel"), None);
+    assert_eq!(Pkcs11Attribute::from_name("CKA_EC_POINT").unwrap().mapping(), TLVMapping::OBJ_ATTR_EC_POINT);
+    assert_eq!(Pkcs11Attribute::from_id(0x0173), None);
+
+    assert_eq!(TLVMapping::OBJ_ATTR_LOCAL.label(NameStyle::Pkcs11), "CKA_LOCAL");
+    assert_eq!(TLVMapping::OBJ_ATTR_LOCAL.label(NameStyle::Cavium), "ObjAttrLocal");
+    assert_eq!(TLVMapping::OBJ_ATTR_EKCV.label(NameStyle::Pkcs11), "ObjAttrEkcv");
+}
+
+#[test]
+fn descriptions_and_relevance() {
+    assert_eq!(TLVMapping::OBJ_ATTR_EXTRACTABLE.description(), "Indicates if key can be extracted.");
+    assert_eq!(TLVMapping::OBJ_ATTR_EXTRACTABLE.security_relevance(), SecurityRelevance::High);
+    assert_eq!(TLVMapping::OBJ_ATTR_MODULUS_BITS.security_relevance(), SecurityRelevance::Medium);
+    assert_eq!(TLVMapping::OBJ_ATTR_LABEL.security_relevance(), SecurityRelevance::Info);
+    assert_eq!(TLVMapping::UNKNOWN.description(), "Attribute type without a mapping.");
+    for id in [0x0000, 0x0103, 0x0181, 0x1003, 0x80000180] {
+        let mapping = TLVMapping::from_int(id);
+        assert!(mapping.description().ends_with('.') && !mapping.description().starts_with(' '), "{:?}", mapping);
+    }
+}
+
+#[test]
+fn weakening_values() {
+    for (mapping, weak, strong) in [
+        (TLVMapping::OBJ_ATTR_EXTRACTABLE, TLVValue::Bool(Bool(true)), TLVValue::Bool(Bool(false))),
+        (TLVMapping::OBJ_ATTR_NEVER_EXTRACTABLE, TLVValue::Bool(Bool(false)), TLVValue::Bool(Bool(true))),
+        (TLVMapping::OBJ_ATTR_LOCAL, TLVValue::Bool(Bool(false)), TLVValue::Bool(Bool(true))),
+        (TLVMapping::OBJ_ATTR_MODULUS_BITS, TLVValue::Int(Int(1024)), TLVValue::Int(Int(2048))),
+        (TLVMapping::OBJ_ATTR_VALUE_LEN, TLVValue::Int(Int(8)), TLVValue::Int(Int(32))),
+    ] {
+        assert!(mapping.weakens(&weak), "{:?}", mapping);
+        assert!(!mapping.weakens(&strong), "{:?}", mapping);
+    }
+    assert!(!TLVMapping::OBJ_ATTR_SIGN.weakens(&TLVValue::Bool(Bool(true))));
+    assert!(!TLVMapping::OBJ_ATTR_EXTRACTABLE.weakens(&TLVValue::RawBytes(RawBytes(vec![1, 1]))));
+
+    // an extractable public key is no weakness, an extractable secret key is
+    let (public, _) = ec_sections();
+    let secret = aes_section().with(TLVMapping::OBJ_ATTR_EXTRACTABLE, TLVValue::Bool(Bool(true)));
+    for (section, weak) in [(public, false), (secret, true)] {
+        let section = section.with(TLVMapping::OBJ_ATTR_EXTRACTABLE, TLVValue::Bool(Bool(true)));
+        let bundle = AttestationBuilder::new().with_section(section).with_signature(vec![0; 256]).build().unwrap();
+        let attestation = parse(&bundle).unwrap();
+        let weaknesses = attestation.sections[0].weaknesses().map(|attr| attr.mapping).collect::<Vec<_>>();
+        assert_eq!(weaknesses.contains(&TLVMapping::OBJ_ATTR_EXTRACTABLE), weak, "{:?}", weaknesses);
+    }
+}
+
+#[test]
+fn every_value_type_survives_the_parser() {
+    let section = KeySection::new(1)
+        .with(TLVMapping::OBJ_ATTR_CLASS, TLVValue::ClassKey(ClassKey::Secret))
+        .with(TLVMapping::OBJ_ATTR_KEY_TYPE, TLVValue::KeyType(KeyType::Des3))
+        .with(TLVMapping::OBJ_ATTR_PRIVATE, TLVValue::Bool(Bool(true)))
+        .with(TLVMapping::OBJ_ATTR_ID, TLVValue::HexStr128(HexStr128("fe".repeat(64))))
+        .with(TLVMapping::OBJ_ATTR_KCV, TLVValue::Bytes(Bytes("a1b2c3".into())))
+        .with(TLVMapping::OBJ_ATTR_LABEL, TLVValue::ByteStr(ByteStr("label".into())))
+        .with(TLVMapping::OBJ_ATTR_VALUE_LEN, TLVValue::Int(Int(24)))
+        .with_raw(TLVMapping::OBJ_ATTR_TRUSTED.to_int(), vec![])
+        .with_raw(0x4242, vec![1, 2, 3]);
+    let bundle = AttestationBuilder::new()
+        .with_section(section.clone())
+        .with_signature(vec![0; 256])
+        .build()
+        .unwrap();
+
+    let attestation = parse(&bundle).unwrap();
+    assert_eq!(attestation.sections[0].attributes, section.attributes);
+}
+
+#[test]
+fn declared_length_is_not_trusted() {
+    // the length comes off the wire, nothing should be sized by it
+    let value = TLVMapping::OBJ_ATTR_MODULUS.encode(&[0xab], u32::MAX);
+    assert_eq!(value, TLVValue::Bytes(Bytes("ab".into())));
+    let value = TLVMapping::OBJ_ATTR_LABEL.encode(b"label", u32::MAX);
+    assert_eq!(value, TLVValue::ByteStr(ByteStr("label".into())));
+}
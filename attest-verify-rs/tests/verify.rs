@@ -0,0 +1,86 @@
+mod common;
+
+use common::*;
+use hsmattest::error::ParseError;
+use hsmattest::verify::{split_signature, verify_bundle, AttestationKey, SignatureVerdict};
+use rsa::pkcs1::EncodeRsaPublicKey;
+use rsa::pkcs8::EncodePublicKey;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPublicKey;
+
+// the public half of owner.key (`openssl pkey -pubout`)
+const OWNER_PUBLIC_KEY: &str = include_str!("fixtures/owner.pub.pem");
+
+#[test]
+fn owner_keys_read_from_pem_and_der() {
+    let key = owner_key().public_key();
+    assert_eq!(AttestationKey::from_pem(OWNER_PUBLIC_KEY).unwrap(), key);
+    assert_eq!(AttestationKey::from_bytes(OWNER_PUBLIC_KEY.as_bytes()).unwrap(), key);
+    assert_eq!(key.signature_len(), 256);
+
+    let rsa = key.public_key();
+    let pkcs1 = rsa.to_pkcs1_pem(Default::default()).unwrap();
+    assert!(pkcs1.starts_with("-----BEGIN RSA PUBLIC KEY-----"));
+    assert_eq!(AttestationKey::from_pem(&pkcs1).unwrap(), key);
+    let spki_der = rsa.to_public_key_der().unwrap();
+    assert_eq!(AttestationKey::from_bytes(spki_der.as_bytes()).unwrap(), key);
+    let pkcs1_der = rsa.to_pkcs1_der().unwrap();
+    assert_eq!(AttestationKey::from_der(pkcs1_der.as_bytes()).unwrap(), key);
+}
+
+#[test]
+fn keys_that_do_not_read() {
+    let empty_pem = b"-----BEGIN PUBLIC KEY-----\n-----END PUBLIC KEY-----\n";
+    for bytes in [&b""[..], empty_pem, &[0x30, 0x03, 0x02, 0x01, 0x01]] {
+        let err = AttestationKey::from_bytes(bytes).unwrap_err();
+        assert!(matches!(err, ParseError::InvalidPublicKey(_)), "{:?}", err);
+    }
+    // EC keys cannot sign a bundle
+    let err = AttestationKey::from_pem(EC_KEY).unwrap_err();
+    assert!(matches!(err, ParseError::InvalidPublicKey(_)), "{:?}", err);
+}
+
+#[test]
+fn signatures_over_the_whole_bundle() {
+    let key = owner_key().public_key();
+    for (name, bundle) in bundles() {
+        assert_eq!(verify_bundle(&bundle, &key).unwrap(), SignatureVerdict::Valid, "{}", name);
+
+        // the header, the attributes and the signature itself are all covered
+        for at in [0, 8, bundle.len() / 2, bundle.len() - 257, bundle.len() - 1] {
+            let mut tampered = bundle.clone();
+            tampered[at] ^= 0x80;
+            assert_eq!(verify_bundle(&tampered, &key).unwrap(), SignatureVerdict::Invalid, "{} at {}", name, at);
+        }
+    }
+}
+
+#[test]
+fn signatures_by_another_key_are_invalid() {
+    let owner = owner_key().public_key();
+    let other = AttestationKey::from(RsaPublicKey::new(owner.public_key().n().clone(), 3u32.into()).unwrap());
+    let bundle = rsa_builder().build().unwrap();
+    let verdict = verify_bundle(&bundle, &other).unwrap();
+    assert_eq!(verdict, SignatureVerdict::Invalid);
+    assert!(!verdict.is_valid());
+    assert_eq!(verdict.to_string(), "invalid");
+    assert_eq!(SignatureVerdict::Valid.to_string(), "valid");
+
+    // a signature of the right length, but no signature at all
+    let unsigned = rsa_builder().with_signature(vec![0; 256]).build().unwrap();
+    assert_eq!(verify_bundle(&unsigned, &owner).unwrap(), SignatureVerdict::Invalid);
+}
+
+#[test]
+fn bundles_too_short_for_the_signature() {
+    let key = owner_key().public_key();
+    for len in [0, 1, 256] {
+        let err = verify_bundle(&vec![0; len], &key).unwrap_err();
+        assert!(matches!(err, ParseError::BundleTooShort(l) if l == len), "{:?}", err);
+    }
+
+    let bundle = aes_builder().build().unwrap();
+    let (data, signature) = split_signature(&bundle, 256).unwrap();
+    assert_eq!(data, &bundle[..bundle.len() - 256]);
+    assert_eq!(signature, parse(&bundle).unwrap().signature);
+}
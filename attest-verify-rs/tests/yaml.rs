@@ -0,0 +1,112 @@
+mod common;
+
+use common::*;
+use hsmattest::writer::{to_yaml, Writer};
+use serde_json::json;
+
+// keys come out sorted, as serde_json keeps them
+
+#[test]
+fn strings_that_read_as_yaml_syntax_are_quoted() {
+    let document = json!({
+        "colon": "a: b",
+        "comment": "# not a comment",
+        "dash": "- not an item",
+        "empty": "",
+        "newline": "two\nlines",
+        "no": "No",
+        "null": "null",
+        "number": "0x0102",
+        "quote": "say \"hi\" \\ bye",
+        "tilde": "~",
+        "yes": "yes",
+    });
+    let expected = concat!(
+        "colon: \"a: b\"\n",
+        "comment: \"# not a comment\"\n",
+        "dash: \"- not an item\"\n",
+        "empty: \"\"\n",
+        "newline: \"two\\nlines\"\n",
+        "\"no\": \"No\"\n",
+        "\"null\": \"null\"\n",
+        "number: \"0x0102\"\n",
+        "quote: \"say \\\"hi\\\" \\\\ bye\"\n",
+        "tilde: \"~\"\n",
+        "\"yes\": \"yes\"\n",
+    );
+    assert_eq!(to_yaml(&document), expected);
+}
+
+#[test]
+fn keys_stay_plain_only_when_unambiguous() {
+    let document = json!({
+        "": 1,
+        "#": 2,
+        "-": 3,
+        "123": 4,
+        "On": 5,
+        "Y": 6,
+        "a: b": 7,
+        "key with spaces": 8,
+        "plain_key": 9,
+        "two\nlines": 10,
+    });
+    let expected = concat!(
+        "\"\": 1\n",
+        "\"#\": 2\n",
+        "\"-\": 3\n",
+        "\"123\": 4\n",
+        "\"On\": 5\n",
+        "\"Y\": 6\n",
+        "\"a: b\": 7\n",
+        "\"key with spaces\": 8\n",
+        "plain_key: 9\n",
+        "\"two\\nlines\": 10\n",
+    );
+    assert_eq!(to_yaml(&document), expected);
+}
+
+#[test]
+fn characters_yaml_wont_take_unescaped() {
+    let document = json!(["tab\there", "del\u{7f}", "next\u{85}line", "\u{feff}bom", "bell\u{7}", "héllo"]);
+    let expected = concat!(
+        "- \"tab\\there\"\n",
+        "- \"del\\u007f\"\n",
+        "- \"next\\u0085line\"\n",
+        "- \"\\ufeffbom\"\n",
+        "- \"bell\\u0007\"\n",
+        "- \"héllo\"\n",
+    );
+    assert_eq!(to_yaml(&document), expected);
+}
+
+#[test]
+fn nested_blocks_and_scalars() {
+    let document = json!({
+        "map": {},
+        "none": null,
+        "sections": [{ "attributes": [{ "id": 1, "ok": true }], "mode": "Primary" }, { "empty": [] }],
+    });
+    let expected = concat!(
+        "map: {}\n",
+        "none: null\n",
+        "sections:\n",
+        "  - attributes:\n",
+        "      - id: 1\n",
+        "        ok: true\n",
+        "    mode: \"Primary\"\n",
+        "  - empty: []\n",
+    );
+    assert_eq!(to_yaml(&document), expected);
+    assert_eq!(to_yaml(&json!("a: b")), "\"a: b\"\n");
+    assert_eq!(to_yaml(&json!([])), "[]\n");
+}
+
+#[test]
+fn attestations_render_as_yaml() {
+    let attestation = parse(&aes_builder().build().unwrap()).unwrap();
+    let yaml = to_yaml(&Writer::new(&attestation).to_json());
+    assert!(yaml.contains("mode: \"Symmetric\"\n"), "{}", yaml);
+    let first = "sections:\n  - attr_count: 10\n    attr_size: 106\n    attributes:\n      - description: ";
+    assert!(yaml.contains(first), "{}", yaml);
+}
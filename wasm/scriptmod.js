@@ -1,5 +1,4 @@
 var Singleton = (async () => {
-    let decompressed_attestation;
     const { instance } = await WebAssembly.instantiateStreaming(
       fetch("./hsmattest.wasm"),
       {
@@ -15,7 +14,7 @@ var Singleton = (async () => {
     fileListener.addEventListener("change", (ev) => {
       handleUserFiles(ev.target.files)
       .then(({ name, lastModified, size }) => {
-        console.log(`self decompressed ${name} (${lastModified}, ${size})` + decompressed_attestation); });
+        console.log(`parsed ${name} (${lastModified}, ${size})`); });
         handleStepper();
     });
 
@@ -47,17 +46,39 @@ var Singleton = (async () => {
     }
   }
 
+  function escapeHtml(value) {
+    return String(value)
+      .replace(/&/g, "&amp;")
+      .replace(/</g, "&lt;")
+      .replace(/>/g, "&gt;")
+      .replace(/"/g, "&quot;");
+  }
+
+  // renders a document laid out as in attest-verify-rs/schema/attestation.schema.json
   function toTable(parsed_attestation) {
     let table = '<table class="uk-table uk-table-striped attr_table" style="table-layout: fixed; width: 100%">';
     table += "<tr><th>Attribute Name</th><th>Value</th></tr>";
-
-    for (i = 0; i < parsed_attestation.length; i++) {
-      Object.keys(parsed_attestation[i].pairs)
-        .map(x => {
-           let key = x;
-           let val = parsed_attestation[i].pairs[key];
-           table += `<tr><td>${key}</td><td style="word-wrap: break-word">${val}</td><tr>`;
-        });
+    let row = (key, val, description = "", weakens = false) => {
+      // EC points come as {x, y}
+      if (typeof val === "object") val = JSON.stringify(val);
+      let explanation = description ? `<span class="attr_description">${escapeHtml(description)}</span>` : "";
+      table += `<tr${weakens ? ' class="weakens"' : ""}><td>${escapeHtml(key)}${explanation}</td>`
+        + `<td style="word-wrap: break-word">${escapeHtml(val)}</td></tr>`;
+    };
+
+    for (const section of parsed_attestation.sections) {
+      row("KeyMode", section.mode);
+      for (const attr of section.attributes) {
+        // values that weaken the key are highlighted, with the attribute's relevance next to its description
+        let description = `[${attr.relevance}] ${attr.description}` + (attr.weakens ? " (weakens the key)" : "");
+        row(attr.name, attr.value, description, attr.weakens);
+      }
+    }
+    if (parsed_attestation.signature) {
+      row("Signature", parsed_attestation.signature);
+    }
+    for (const pair of parsed_attestation.extra) {
+      row(pair.name, pair.value);
     }
 
     table += '</table>';
@@ -85,10 +106,53 @@ var Singleton = (async () => {
     return parseAttestation(attestation_data, getInstance());
   }
 
+  // same as parse, with a PEM or DER owner public key to check the bundle signature against.
+  // the verdict is reported as a `SignatureVerification` pair.
+  function parseVerify(attestation_data, public_key) {
+    let instance = getInstance();
+    let byte_buffer_ptr = copyMemory(attestation_data, instance);
+    let key_ptr = copyMemory(public_key, instance);
+
+    let [json_ptr, len] = instance.exports.parse_verify(byte_buffer_ptr, attestation_data.length, key_ptr, public_key.length);
+    return fromJsonMemory(json_ptr, len, instance);
+  }
+
+  // same as parse, checking ObjAttrId against the KeyID of a Cloud KMS key version resource name.
+  // the outcome is reported as `KeyID` and `KeyIDVerification` pairs.
+  function parseGcp(attestation_data, key_name) {
+    let instance = getInstance();
+    let name = new TextEncoder().encode(key_name);
+    let byte_buffer_ptr = copyMemory(attestation_data, instance);
+    let name_ptr = copyMemory(name, instance);
+
+    let [json_ptr, len] = instance.exports.parse_gcp(byte_buffer_ptr, attestation_data.length, name_ptr, name.length);
+    return fromJsonMemory(json_ptr, len, instance);
+  }
+
+  // the attested public key as `pem`, `der`, `jwk` or `openssh`; DER comes back as a Uint8Array,
+  // the others as text. bundles without a public key are reported on the console.
+  function exportPubkey(attestation_data, format = 'pem') {
+    let instance = getInstance();
+    let name = new TextEncoder().encode(format);
+    let byte_buffer_ptr = copyMemory(attestation_data, instance);
+    let format_ptr = copyMemory(name, instance);
+
+    let [ptr, len] = instance.exports.export_pubkey(byte_buffer_ptr, attestation_data.length, format_ptr, name.length);
+    if (len == 0) return;
+
+    let exported = new Uint8Array(instance.exports.memory.buffer, ptr, len).slice();
+    instance.exports.dealloc(ptr, len);
+    return format == 'der' ? exported : new TextDecoder().decode(exported);
+  }
+
   function parseAttestation(attestation, instance) {
     let byte_buffer_ptr = copyMemory(attestation, instance);
 
     let [json_ptr, len] = instance.exports.parse(byte_buffer_ptr, attestation.length);
+    return fromJsonMemory(json_ptr, len, instance);
+  }
+
+  function fromJsonMemory(json_ptr, len, instance) {
     if (len == 0) return;
 
     try {
@@ -123,31 +187,13 @@ var Singleton = (async () => {
       let { name, lastModified, size } = files[0];
       let promise = new Promise(handleFile(files[0]));
 
+      // compressed (CAVIUM_V2_COMPRESSED) bundles are inflated inside the wasm module
       return promise.then(data => {
-        return data;
-      }).then(async data => {
-        // check if its a compressed version, and if so, decompress it.
-        try {
-          const ds = new DecompressionStream("gzip");
-          const decomp = new Blob([data]).stream().pipeThrough(ds);
-          let resp = await new Response(decomp).arrayBuffer();
-          console.dir(resp);
-          return new Uint8Array(resp);
-          //await new Response(decomp).blob();
-        } catch (e) {
-          return data;
-        }
-
-      }).then(data => {
-
-        decompressed_attestation = [...data];
-        //console.log(Array.apply([], data).join(","));
-
-        // check if the file is compressed
         clearTable();
 
+        // malformed bundles are reported on the console by the wasm module
         let parsed_data = parse(data);
-        toTable(parsed_data);
+        if (parsed_data) toTable(parsed_data);
         return { name, lastModified, size };
       });
   }
@@ -155,6 +201,9 @@ var Singleton = (async () => {
   return {
     getInstance,
     parse,
+    parseVerify,
+    parseGcp,
+    exportPubkey,
     toTable,
   }
 
@@ -1,5 +1,11 @@
 use std::ffi::{c_char, CString};
-use hsmattest::{state_transitions, Machine};
+use hsmattest::attestation::Attestation;
+use hsmattest::gcp::{match_key_id, KeyVersionName};
+use hsmattest::gzip::Decoder;
+use hsmattest::pubkey::{KeyFormat, PublicKey};
+use hsmattest::verify::{verify_bundle, AttestationKey};
+use hsmattest::writer::Writer;
+use hsmattest::Machine;
 
 extern "C" {
     fn consoleLog(p: *mut c_char);
@@ -20,35 +26,155 @@ fn dealloc_cstring(p: *mut c_char) {
         CString::from_raw(p)
     };
 }
-macro_rules! println {
-    ($($tt:tt)*) => {
-        $crate::log(format!($($tt)*))
-    }
-}
 
 #[no_mangle]
 #[allow(improper_ctypes_definitions)]
-pub extern fn multi() -> (u32, u32) {
+pub extern "C" fn multi() -> (u32, u32) {
     (101, 102)
 }
 
+// the parsed attestation and the inflated bundle bytes, as covered by the signature
+fn read_bundle(byte_buff: &[u8], key: Option<&AttestationKey>) -> Option<(Attestation, Vec<u8>)> {
+    let mut machine = match key {
+        Some(key) => Machine::new_with_signature_len(key.signature_len()),
+        None => Machine::new(),
+    };
+
+    // compressed bundles are inflated here rather than in the browser
+    let mut bundle = Vec::with_capacity(byte_buff.len());
+    let mut sink = |raw: &[u8]| {
+        machine.run_buf(raw);
+        bundle.extend_from_slice(raw);
+    };
+    let mut decoder = Decoder::new();
+    if let Err(e) = decoder.feed(byte_buff, &mut sink).and_then(|_| decoder.finish(&mut sink)) {
+        log(e.to_string());
+    }
+
+    match machine.finish() {
+        Ok(attestation) => Some((attestation, bundle)),
+        Err(e) => {
+            log(e.to_string());
+            None
+        }
+    }
+}
+
+// hands the buffer over to the caller, who frees it with `dealloc(ptr, len)`
+fn into_raw(bytes: Vec<u8>) -> (*mut u8, u32) {
+    let len = bytes.len();
+    (Box::into_raw(bytes.into_boxed_slice()) as *mut u8, len as u32)
+}
+
+fn parse_bundle(byte_buff: &[u8], key: Option<&AttestationKey>, key_name: Option<&KeyVersionName>) -> (*mut u8, u32) {
+    let Some((attestation, bundle)) = read_bundle(byte_buff, key) else {
+        return (std::ptr::null_mut(), 0);
+    };
+    let mut writer = Writer::new(&attestation);
+    if let Some(key) = key {
+        let verdict = match verify_bundle(&bundle, key) {
+            Ok(verdict) => verdict.to_string(),
+            Err(e) => e.to_string(),
+        };
+        writer = writer.with_pair("SignatureVerification".to_string(), verdict);
+    }
+    if let Some(key_name) = key_name {
+        let matched = match_key_id(&attestation, key_name)
+            .into_iter()
+            .find(|m| m.is_match())
+            .and_then(|m| m.matched)
+            .map(|half| format!("match ({:?} half)", half).to_lowercase())
+            .unwrap_or_else(|| "mismatch".to_string());
+        writer = writer
+            .with_pair("KeyID".to_string(), key_name.key_id())
+            .with_pair("KeyIDVerification".to_string(), matched);
+    }
+
+    into_raw(writer.to_json_bytes())
+}
+
+/// # Safety
+/// `byte_buffer_ptr` must come from `alloc(byte_buffer_len)`; ownership passes to this call.
 #[no_mangle]
 pub unsafe fn parse(byte_buffer_ptr: *mut u8, byte_buffer_len: u32) -> (*mut u8, u32) {
+    let byte_buff = Vec::from_raw_parts(byte_buffer_ptr, byte_buffer_len as usize, byte_buffer_len as usize);
+    parse_bundle(&byte_buff, None, None)
+}
 
-    let mut machine  = Machine::new().with_writer();
-    state_transitions::register_functions(&mut machine);
+/// Same as `parse`, additionally checking the bundle signature against a PEM or DER public key.
+///
+/// # Safety
+/// Both buffers must come from `alloc`; ownership of each passes to this call.
+#[no_mangle]
+pub unsafe fn parse_verify(
+    byte_buffer_ptr: *mut u8,
+    byte_buffer_len: u32,
+    key_ptr: *mut u8,
+    key_len: u32,
+) -> (*mut u8, u32) {
+    let byte_buff = Vec::from_raw_parts(byte_buffer_ptr, byte_buffer_len as usize, byte_buffer_len as usize);
+    let key_buff = Vec::from_raw_parts(key_ptr, key_len as usize, key_len as usize);
+    match AttestationKey::from_bytes(&key_buff) {
+        Ok(key) => parse_bundle(&byte_buff, Some(&key), None),
+        Err(e) => {
+            log(e.to_string());
+            parse_bundle(&byte_buff, None, None)
+        }
+    }
+}
 
+/// Same as `parse`, additionally checking `ObjAttrId` against the KeyID derived from a Cloud KMS
+/// key version resource name (utf-8).
+///
+/// # Safety
+/// Both buffers must come from `alloc`; ownership of each passes to this call.
+#[no_mangle]
+pub unsafe fn parse_gcp(
+    byte_buffer_ptr: *mut u8,
+    byte_buffer_len: u32,
+    name_ptr: *mut u8,
+    name_len: u32,
+) -> (*mut u8, u32) {
     let byte_buff = Vec::from_raw_parts(byte_buffer_ptr, byte_buffer_len as usize, byte_buffer_len as usize);
-    for item in byte_buff {
-        machine.parse(item);
+    let name_buff = Vec::from_raw_parts(name_ptr, name_len as usize, name_len as usize);
+    let key_name = String::from_utf8_lossy(&name_buff);
+    match KeyVersionName::parse(&key_name) {
+        Ok(key_name) => parse_bundle(&byte_buff, None, Some(&key_name)),
+        Err(e) => {
+            log(e.to_string());
+            parse_bundle(&byte_buff, None, None)
+        }
     }
-    if let Some(mut json_str) = machine.to_json_bytes() {
-        let ptr = json_str.as_mut_ptr();
-        let len = json_str.len();
-        std::mem::forget(json_str);
-        return (ptr, len as u32);
+}
+
+/// The attested public key in the named format (utf-8: `pem`, `der`, `jwk` or `openssh`); PEM,
+/// JWK and OpenSSH come back as utf-8 text, DER as raw bytes.
+///
+/// # Safety
+/// Both buffers must come from `alloc`; ownership of each passes to this call.
+#[no_mangle]
+pub unsafe fn export_pubkey(
+    byte_buffer_ptr: *mut u8,
+    byte_buffer_len: u32,
+    format_ptr: *mut u8,
+    format_len: u32,
+) -> (*mut u8, u32) {
+    let byte_buff = Vec::from_raw_parts(byte_buffer_ptr, byte_buffer_len as usize, byte_buffer_len as usize);
+    let format_buff = Vec::from_raw_parts(format_ptr, format_len as usize, format_len as usize);
+    let Some(format) = KeyFormat::from_name(&String::from_utf8_lossy(&format_buff)) else {
+        log(format!("Unknown key format '{}'", String::from_utf8_lossy(&format_buff)));
+        return (std::ptr::null_mut(), 0);
+    };
+    let Some((attestation, _)) = read_bundle(&byte_buff, None) else {
+        return (std::ptr::null_mut(), 0);
+    };
+    match PublicKey::from_attestation(&attestation).and_then(|key| key.export(format)) {
+        Ok(exported) => into_raw(exported),
+        Err(e) => {
+            log(e.to_string());
+            (std::ptr::null_mut(), 0)
+        }
     }
-    (0 as *mut _, 0)
 }
 
 #[no_mangle]
@@ -64,15 +190,19 @@ pub fn alloc(len: usize) -> *mut u8 {
     std::mem::forget(buf);
     // return the pointer so the runtime
     // can write data at this offset
-    return ptr;
+    ptr
 }
 
+/// # Safety
+/// `ptr` and `size` must describe a buffer previously handed out by `alloc` or `parse`.
 #[no_mangle]
 pub unsafe fn dealloc(ptr: *mut u8, size: usize) {
     let data = Vec::from_raw_parts(ptr, size, size);
     std::mem::drop(data)
 }
 
+/// # Safety
+/// `ptr` must come from `alloc(len)`; ownership passes to this call.
 #[no_mangle]
 pub unsafe fn array_sum(ptr: *mut u8, len: usize) -> u8 {
     // create a Vec<u8> from the pointer to the
@@ -84,8 +214,8 @@ pub unsafe fn array_sum(ptr: *mut u8, len: usize) -> u8 {
 }
 
 #[no_mangle]
-pub extern fn the_answer() -> u32 {
+pub extern "C" fn the_answer() -> u32 {
     let _m  = Machine::new();
-    println!("got the answer!");
+    log("got the answer!".to_string());
     0
 }